@@ -0,0 +1,298 @@
+use crate::error::Error;
+use crate::options::{
+    BranchStyle, ExpressionStyle, FieldOrder, IndentStyle, LineEnding, Options, Placement,
+    TypeUseStyle,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The file names that are searched for, in order of precedence.
+pub const CONFIG_FILE_NAMES: [&str; 2] = ["wasmfmt.toml", ".wasmfmt.toml"];
+
+/// Formatting settings read from a `wasmfmt.toml` file,
+/// an `.editorconfig` file or from command-line flags.
+///
+/// Every setting is optional; settings that are not present
+/// fall back to those of a less specific source, and finally to `Options::default()`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub resolve_names: Option<bool>,
+    pub name_indices: Option<bool>,
+    pub synthesize_names: Option<bool>,
+    pub max_width: Option<usize>,
+    pub indent_style: Option<IndentStyle>,
+    pub indent_width: Option<usize>,
+    pub end_of_line: Option<LineEnding>,
+    pub insert_final_newline: Option<bool>,
+    pub expression_style: Option<ExpressionStyle>,
+    pub branch_style: Option<BranchStyle>,
+    pub label_ends: Option<bool>,
+    pub minify: Option<bool>,
+    pub field_order: Option<FieldOrder>,
+    pub imports: Option<Placement>,
+    pub exports: Option<Placement>,
+    pub type_uses: Option<TypeUseStyle>,
+    pub dedupe_types: Option<bool>,
+}
+
+impl Config {
+    /// Parse and validate the contents of a configuration file.
+    pub fn parse(source: &str, path: &Path) -> Result<Self, Error> {
+        let config: Config = toml::from_str(source).map_err(|err| Error::Config {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+        config.validate(path)?;
+        Ok(config)
+    }
+
+    /// Read and validate a configuration file.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let source = fs::read_to_string(path)?;
+        Self::parse(&source, path)
+    }
+
+    /// Find the configuration file that applies to `path`
+    /// by walking up the directory tree, starting at `path` itself
+    /// if it is a directory, or at its parent otherwise.
+    pub fn discover(path: &Path) -> Result<Option<(PathBuf, Self)>, Error> {
+        let path = absolute(path)?;
+        let start = if path.is_dir() {
+            Some(path.as_path())
+        } else {
+            path.parent()
+        };
+        for dir in start.into_iter().flat_map(Path::ancestors) {
+            for name in CONFIG_FILE_NAMES.iter() {
+                let candidate = dir.join(name);
+                if candidate.is_file() {
+                    let config = Self::from_file(&candidate)?;
+                    return Ok(Some((candidate, config)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read the settings that apply to `path` from the nearest configuration file
+    /// and any `.editorconfig` files, in that order of precedence,
+    /// together with the files they were read from.
+    pub fn resolve(path: &Path) -> Result<(Vec<PathBuf>, Self), Error> {
+        let (mut files, editorconfig) = Self::from_editorconfig(path)?;
+        match Self::discover(path)? {
+            Some((file, config)) => {
+                files.insert(0, file);
+                Ok((files, editorconfig.merge(config)))
+            }
+            None => Ok((files, editorconfig)),
+        }
+    }
+
+    /// Combine two configurations.
+    /// Settings in `other` take precedence over those in `self`.
+    pub fn merge(self, other: Config) -> Config {
+        Config {
+            resolve_names: other.resolve_names.or(self.resolve_names),
+            name_indices: other.name_indices.or(self.name_indices),
+            synthesize_names: other.synthesize_names.or(self.synthesize_names),
+            max_width: other.max_width.or(self.max_width),
+            indent_style: other.indent_style.or(self.indent_style),
+            indent_width: other.indent_width.or(self.indent_width),
+            end_of_line: other.end_of_line.or(self.end_of_line),
+            insert_final_newline: other.insert_final_newline.or(self.insert_final_newline),
+            expression_style: other.expression_style.or(self.expression_style),
+            branch_style: other.branch_style.or(self.branch_style),
+            label_ends: other.label_ends.or(self.label_ends),
+            minify: other.minify.or(self.minify),
+            field_order: other.field_order.or(self.field_order),
+            imports: other.imports.or(self.imports),
+            exports: other.exports.or(self.exports),
+            type_uses: other.type_uses.or(self.type_uses),
+            dedupe_types: other.dedupe_types.or(self.dedupe_types),
+        }
+    }
+
+    /// Resolve the configuration into a complete set of options.
+    pub fn to_options(&self) -> Options {
+        let default = Options::default();
+        Options {
+            resolve_names: self.resolve_names.unwrap_or(default.resolve_names),
+            name_indices: self.name_indices.unwrap_or(default.name_indices),
+            synthesize_names: self.synthesize_names.unwrap_or(default.synthesize_names),
+            max_width: self.max_width.unwrap_or(default.max_width),
+            indent_style: self.indent_style.unwrap_or(default.indent_style),
+            indent_width: self.indent_width.unwrap_or(default.indent_width),
+            end_of_line: self.end_of_line.unwrap_or(default.end_of_line),
+            insert_final_newline: self
+                .insert_final_newline
+                .unwrap_or(default.insert_final_newline),
+            expression_style: self.expression_style.unwrap_or(default.expression_style),
+            branch_style: self.branch_style.unwrap_or(default.branch_style),
+            label_ends: self.label_ends.unwrap_or(default.label_ends),
+            minify: self.minify.unwrap_or(default.minify),
+            field_order: self.field_order.unwrap_or(default.field_order),
+            imports: self.imports.unwrap_or(default.imports),
+            exports: self.exports.unwrap_or(default.exports),
+            type_uses: self.type_uses.unwrap_or(default.type_uses),
+            dedupe_types: self.dedupe_types.unwrap_or(default.dedupe_types),
+        }
+    }
+
+    fn validate(&self, path: &Path) -> Result<(), Error> {
+        let invalid = |message: &str| Error::Config {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        };
+        if self.max_width == Some(0) {
+            return Err(invalid("`max_width` must be greater than zero"));
+        }
+        if self.indent_width == Some(0) {
+            return Err(invalid("`indent_width` must be greater than zero"));
+        }
+        Ok(())
+    }
+}
+
+/// Make `path` absolute by joining it onto the current directory.
+pub(crate) fn absolute(path: &Path) -> io::Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(std::env::current_dir()?.join(path))
+    }
+}
+
+impl From<&Options> for Config {
+    fn from(options: &Options) -> Self {
+        Config {
+            resolve_names: Some(options.resolve_names),
+            name_indices: Some(options.name_indices),
+            synthesize_names: Some(options.synthesize_names),
+            max_width: Some(options.max_width),
+            indent_style: Some(options.indent_style),
+            indent_width: Some(options.indent_width),
+            end_of_line: Some(options.end_of_line),
+            insert_final_newline: Some(options.insert_final_newline),
+            expression_style: Some(options.expression_style),
+            branch_style: Some(options.branch_style),
+            label_ends: Some(options.label_ends),
+            minify: Some(options.minify),
+            field_order: Some(options.field_order),
+            imports: Some(options.imports),
+            exports: Some(options.exports),
+            type_uses: Some(options.type_uses),
+            dedupe_types: Some(options.dedupe_types),
+        }
+    }
+}
+
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let toml = toml::to_string(self).map_err(|_| std::fmt::Error)?;
+        f.write_str(&toml)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Config;
+    use crate::error::Error;
+    use crate::options::{
+        BranchStyle, ExpressionStyle, FieldOrder, IndentStyle, LineEnding, Placement, TypeUseStyle,
+    };
+    use assert_matches::assert_matches;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn parse_should_read_all_settings() {
+        let source = "
+            resolve_names = true
+            name_indices = true
+            synthesize_names = true
+            max_width = 80
+            indent_style = \"spaces\"
+            indent_width = 2
+            end_of_line = \"crlf\"
+            insert_final_newline = false
+            expression_style = \"folded\"
+            branch_style = \"labels\"
+            label_ends = true
+            minify = true
+            field_order = \"names\"
+            imports = \"separate\"
+            exports = \"inline\"
+            type_uses = \"reference\"
+            dedupe_types = true
+        ";
+        let config = Config::parse(source, Path::new("wasmfmt.toml")).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                resolve_names: Some(true),
+                name_indices: Some(true),
+                synthesize_names: Some(true),
+                max_width: Some(80),
+                indent_style: Some(IndentStyle::Spaces),
+                indent_width: Some(2),
+                end_of_line: Some(LineEnding::Crlf),
+                insert_final_newline: Some(false),
+                expression_style: Some(ExpressionStyle::Folded),
+                branch_style: Some(BranchStyle::Labels),
+                label_ends: Some(true),
+                minify: Some(true),
+                field_order: Some(FieldOrder::Names),
+                imports: Some(Placement::Separate),
+                exports: Some(Placement::Inline),
+                type_uses: Some(TypeUseStyle::Reference),
+                dedupe_types: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_should_reject_unknown_keys() {
+        let result = Config::parse("tab_width = 2", Path::new("wasmfmt.toml"));
+        assert_matches!(result, Err(Error::Config { message, .. }) if message.contains("unknown field `tab_width`"));
+    }
+
+    #[test]
+    fn parse_should_reject_invalid_values() {
+        let result = Config::parse("indent_width = 0", Path::new("wasmfmt.toml"));
+        assert_matches!(result, Err(Error::Config { .. }));
+    }
+
+    #[test]
+    fn merge_should_prefer_other() {
+        let file = Config {
+            max_width: Some(80),
+            indent_width: Some(2),
+            ..Config::default()
+        };
+        let flags = Config {
+            max_width: Some(120),
+            ..Config::default()
+        };
+        let options = file.merge(flags).to_options();
+        assert_eq!(options.max_width, 120);
+        assert_eq!(options.indent_width, 2);
+    }
+
+    #[test]
+    fn discover_should_find_nearest_ancestor() {
+        let root = env::temp_dir().join("wasmfmt-config-discover");
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join("wasmfmt.toml"), "max_width = 80").unwrap();
+        fs::write(root.join("a").join(".wasmfmt.toml"), "max_width = 60").unwrap();
+        let result = Config::discover(&nested.join("file.wat")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        let (path, config) = result.expect("config");
+        assert_eq!(path, root.join("a").join(".wasmfmt.toml"));
+        assert_eq!(config.max_width, Some(60));
+    }
+}
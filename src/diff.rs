@@ -1,4 +1,5 @@
 use std::fmt;
+use std::ops::Range;
 
 /// Created from two different strings.
 /// Presents the difference when formatted.
@@ -15,6 +16,56 @@ impl<'src, 'fmt> Diff<'src, 'fmt> {
             None
         }
     }
+
+    /// The ranges of lines that differ between the source and the formatted code.
+    pub fn hunks(&self) -> Vec<Hunk> {
+        let source: Vec<&str> = self.source.split_inclusive('\n').collect();
+        let formatted: Vec<&str> = self.formatted.split_inclusive('\n').collect();
+        let mut hunks = Vec::new();
+        let mut current: Option<Hunk> = None;
+        // The next line number in the source and the formatted code.
+        let (mut left, mut right) = (1, 1);
+        for result in diff::slice(&source, &formatted) {
+            let hunk = current.get_or_insert(Hunk {
+                source: left..left,
+                formatted: right..right,
+            });
+            match result {
+                diff::Result::Left(..) => {
+                    left += 1;
+                    hunk.source.end = left;
+                }
+                diff::Result::Right(..) => {
+                    right += 1;
+                    hunk.formatted.end = right;
+                }
+                diff::Result::Both(..) => {
+                    left += 1;
+                    right += 1;
+                    hunks.extend(current.take().filter(|hunk| !hunk.is_empty()));
+                }
+            }
+        }
+        hunks.extend(current.filter(|hunk| !hunk.is_empty()));
+        hunks
+    }
+}
+
+/// A range of lines that differ between the source and the formatted code.
+///
+/// Line numbers start at 1 and the ranges are exclusive at the end.
+/// A range is empty if lines are only inserted or only removed,
+/// in which case it starts at the line the change precedes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub source: Range<usize>,
+    pub formatted: Range<usize>,
+}
+
+impl Hunk {
+    fn is_empty(&self) -> bool {
+        self.source.is_empty() && self.formatted.is_empty()
+    }
 }
 
 impl<'src, 'fmt> fmt::Display for Diff<'src, 'fmt> {
@@ -30,10 +81,11 @@ impl<'src, 'fmt> fmt::Display for Diff<'src, 'fmt> {
 
 #[cfg(test)]
 mod test {
-    use super::Diff;
+    use super::{Diff, Hunk};
     use unindent::Unindent;
 
     #[test]
+    #[allow(clippy::redundant_pattern_matching)]
     fn check_should_return_none_for_no_diff() {
         let result = Diff::from("foo", "foo");
         assert!(matches!(result, None));
@@ -62,4 +114,36 @@ mod test {
             .unindent()
         );
     }
+
+    #[test]
+    fn hunks_should_contain_changed_lines() {
+        let source = "a\nb\nc\nd\ne\n";
+        let formatted = "a\nB\nc\nd\nE\nF\n";
+        let hunks = Diff::from(source, formatted).unwrap().hunks();
+        assert_eq!(
+            hunks,
+            vec![
+                Hunk {
+                    source: 2..3,
+                    formatted: 2..3,
+                },
+                Hunk {
+                    source: 5..6,
+                    formatted: 5..7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hunks_should_include_missing_final_newline() {
+        let hunks = Diff::from("a\nb", "a\nb\n").unwrap().hunks();
+        assert_eq!(
+            hunks,
+            vec![Hunk {
+                source: 2..3,
+                formatted: 2..3,
+            }]
+        );
+    }
 }
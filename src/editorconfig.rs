@@ -0,0 +1,297 @@
+use crate::config::{absolute, Config};
+use crate::error::Error;
+use crate::options::{IndentStyle, LineEnding};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the file that is searched for.
+pub const EDITORCONFIG_FILE_NAME: &str = ".editorconfig";
+
+/// A file name used to match sections when settings are resolved
+/// for a directory rather than for a specific file.
+const PLACEHOLDER_FILE_NAME: &str = "file.wat";
+
+/// The properties of an `.editorconfig` section that `wasmfmt` understands.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Properties {
+    indent_style: Option<IndentStyle>,
+    indent_size: Option<IndentSize>,
+    tab_width: Option<usize>,
+    end_of_line: Option<LineEnding>,
+    insert_final_newline: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum IndentSize {
+    Columns(usize),
+    Tab,
+}
+
+impl Properties {
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.to_ascii_lowercase();
+        match key {
+            "indent_style" => {
+                self.indent_style = match value.as_str() {
+                    "tab" => Some(IndentStyle::Tabs),
+                    "space" => Some(IndentStyle::Spaces),
+                    _ => None,
+                }
+            }
+            "indent_size" => {
+                self.indent_size = match value.as_str() {
+                    "tab" => Some(IndentSize::Tab),
+                    n => n.parse().ok().filter(|n| *n > 0).map(IndentSize::Columns),
+                }
+            }
+            "tab_width" => self.tab_width = value.parse().ok().filter(|n| *n > 0),
+            "end_of_line" => self.end_of_line = value.parse().ok(),
+            "insert_final_newline" => self.insert_final_newline = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    /// Apply `other` on top of `self`.
+    fn merge(self, other: Properties) -> Properties {
+        Properties {
+            indent_style: other.indent_style.or(self.indent_style),
+            indent_size: other.indent_size.or(self.indent_size),
+            tab_width: other.tab_width.or(self.tab_width),
+            end_of_line: other.end_of_line.or(self.end_of_line),
+            insert_final_newline: other.insert_final_newline.or(self.insert_final_newline),
+        }
+    }
+}
+
+impl From<Properties> for Config {
+    fn from(properties: Properties) -> Config {
+        let indent_width = match properties.indent_size {
+            Some(IndentSize::Columns(n)) => Some(n),
+            Some(IndentSize::Tab) => properties.tab_width,
+            None => properties.tab_width,
+        };
+        Config {
+            indent_style: properties.indent_style,
+            indent_width,
+            end_of_line: properties.end_of_line,
+            insert_final_newline: properties.insert_final_newline,
+            ..Config::default()
+        }
+    }
+}
+
+/// A parsed `.editorconfig` file.
+struct EditorConfig {
+    root: bool,
+    sections: Vec<(String, Properties)>,
+}
+
+impl EditorConfig {
+    fn parse(source: &str) -> Self {
+        let mut root = false;
+        let mut sections: Vec<(String, Properties)> = Vec::new();
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                sections.push((glob.to_string(), Properties::default()));
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let key = key.trim().to_ascii_lowercase();
+                let value = value.trim();
+                match sections.last_mut() {
+                    Some((_, properties)) => properties.set(&key, value),
+                    None if key == "root" => root = value.eq_ignore_ascii_case("true"),
+                    None => {}
+                }
+            }
+        }
+        Self { root, sections }
+    }
+
+    /// The properties of every section matching `path`,
+    /// which is relative to the directory of this file.
+    fn properties(&self, path: &str) -> Properties {
+        self.sections
+            .iter()
+            .filter(|(glob, _)| section_matches(glob, path))
+            .fold(Properties::default(), |acc, (_, properties)| {
+                acc.merge(properties.clone())
+            })
+    }
+}
+
+impl Config {
+    /// Read the settings that the `.editorconfig` files
+    /// in the ancestors of `path` declare for it.
+    ///
+    /// Files closer to `path` take precedence,
+    /// and the search stops at a file that declares `root = true`.
+    /// If `path` is a directory, the settings for a `.wat` file in it are returned.
+    pub fn from_editorconfig(path: &Path) -> Result<(Vec<PathBuf>, Self), Error> {
+        let mut path = absolute(path)?;
+        if path.is_dir() {
+            path.push(PLACEHOLDER_FILE_NAME);
+        }
+        let mut files = Vec::new();
+        let mut properties = Properties::default();
+        for dir in path.ancestors().skip(1) {
+            let candidate = dir.join(EDITORCONFIG_FILE_NAME);
+            if !candidate.is_file() {
+                continue;
+            }
+            let editorconfig = EditorConfig::parse(&fs::read_to_string(&candidate)?);
+            let relative = path.strip_prefix(dir).expect("ancestor");
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            properties = editorconfig.properties(&relative).merge(properties);
+            files.push(candidate);
+            if editorconfig.root {
+                break;
+            }
+        }
+        Ok((files, properties.into()))
+    }
+}
+
+/// Determine if a section glob applies to `path`.
+/// A glob without a `/` matches files with that name in any directory.
+fn section_matches(glob: &str, path: &str) -> bool {
+    let glob = if let Some(glob) = glob.strip_prefix('/') {
+        glob.to_string()
+    } else if glob.contains('/') {
+        glob.to_string()
+    } else {
+        format!("**/{}", glob)
+    };
+    let glob: Vec<char> = glob.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    // A leading `**/` also matches files at the top level.
+    glob_matches(&glob, &path)
+        || (glob.starts_with(&['*', '*', '/']) && glob_matches(&glob[3..], &path))
+}
+
+/// Match `path` against a glob supporting `*`, `**`, `?`, `[...]` and `{a,b}`.
+fn glob_matches(glob: &[char], path: &[char]) -> bool {
+    match glob.first() {
+        None => path.is_empty(),
+        Some('*') if glob.get(1) == Some(&'*') => {
+            let rest = &glob[2..];
+            (0..=path.len()).any(|i| glob_matches(rest, &path[i..]))
+        }
+        Some('*') => {
+            let rest = &glob[1..];
+            (0..=path.len())
+                .take_while(|&i| i == 0 || path[i - 1] != '/')
+                .any(|i| glob_matches(rest, &path[i..]))
+        }
+        Some('?') => match path.first() {
+            Some(c) if *c != '/' => glob_matches(&glob[1..], &path[1..]),
+            _ => false,
+        },
+        Some('[') => match (glob.iter().position(|c| *c == ']'), path.first()) {
+            (Some(end), Some(c)) if end > 1 => {
+                let (negated, set) = match glob[1] {
+                    '!' | '^' => (true, &glob[2..end]),
+                    _ => (false, &glob[1..end]),
+                };
+                set.contains(c) != negated && glob_matches(&glob[end + 1..], &path[1..])
+            }
+            _ => false,
+        },
+        Some('{') => match glob.iter().position(|c| *c == '}') {
+            Some(end) => {
+                let rest = &glob[end + 1..];
+                glob[1..end].split(|c| *c == ',').any(|alternative| {
+                    let mut candidate = alternative.to_vec();
+                    candidate.extend_from_slice(rest);
+                    glob_matches(&candidate, path)
+                })
+            }
+            None => path.first() == Some(&'{') && glob_matches(&glob[1..], &path[1..]),
+        },
+        Some('\\') if glob.len() > 1 => {
+            path.first() == Some(&glob[1]) && glob_matches(&glob[2..], &path[1..])
+        }
+        Some(c) => path.first() == Some(c) && glob_matches(&glob[1..], &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{section_matches, EditorConfig, IndentSize, Properties};
+    use crate::config::Config;
+    use crate::options::{IndentStyle, LineEnding};
+
+    #[test]
+    fn section_should_match_file_name_in_any_directory() {
+        assert!(section_matches("*.wat", "a.wat"));
+        assert!(section_matches("*.wat", "src/nested/a.wat"));
+        assert!(!section_matches("*.wat", "a.wast"));
+        assert!(section_matches("*", "src/a.wat"));
+    }
+
+    #[test]
+    fn section_should_match_relative_paths() {
+        assert!(section_matches("src/*.wat", "src/a.wat"));
+        assert!(!section_matches("src/*.wat", "src/nested/a.wat"));
+        assert!(section_matches("/src/**.wat", "src/nested/a.wat"));
+        assert!(!section_matches("src/*.wat", "a.wat"));
+    }
+
+    #[test]
+    fn section_should_match_alternatives_and_sets() {
+        assert!(section_matches("*.{wat,wast}", "a.wast"));
+        assert!(section_matches("[ab].wat", "b.wat"));
+        assert!(!section_matches("[!ab].wat", "b.wat"));
+        assert!(section_matches("?.wat", "c.wat"));
+    }
+
+    #[test]
+    fn later_sections_should_take_precedence() {
+        let editorconfig = EditorConfig::parse(
+            "
+            root = true
+
+            [*]
+            indent_style = tab
+            end_of_line = lf
+
+            [*.wat]
+            indent_style = space
+            indent_size = 2
+            insert_final_newline = false
+            ",
+        );
+        assert!(editorconfig.root);
+        assert_eq!(
+            editorconfig.properties("a.wat"),
+            Properties {
+                indent_style: Some(IndentStyle::Spaces),
+                indent_size: Some(IndentSize::Columns(2)),
+                tab_width: None,
+                end_of_line: Some(LineEnding::Lf),
+                insert_final_newline: Some(false),
+            }
+        );
+    }
+
+    #[test]
+    fn indent_size_tab_should_use_tab_width() {
+        let editorconfig = EditorConfig::parse(
+            "
+            [*.wat]
+            indent_style = tab
+            indent_size = tab
+            tab_width = 8
+            end_of_line = CRLF
+            ",
+        );
+        let config = Config::from(editorconfig.properties("a.wat"));
+        assert_eq!(config.indent_style, Some(IndentStyle::Tabs));
+        assert_eq!(config.indent_width, Some(8));
+        assert_eq!(config.end_of_line, Some(LineEnding::Crlf));
+    }
+}
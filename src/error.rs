@@ -1,10 +1,51 @@
+use std::fmt;
 use std::io;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
+    /// A configuration file could not be parsed or contains invalid settings.
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+    /// The source code could not be parsed, or its names could not be resolved.
+    Parse(String),
+    /// The source code contains a construct that cannot be formatted.
+    Unsupported(String),
+    /// The formatted code does not describe the same module as the input.
+    Verify(String),
+    /// Formatting the formatted code again changes it.
+    Idempotency(String),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Config { path, message } => {
+                write!(
+                    f,
+                    "invalid configuration in {}: {}",
+                    path.display(),
+                    message
+                )
+            }
+            Error::Parse(message) => write!(f, "{}", message),
+            Error::Unsupported(construct) => write!(f, "{} cannot be formatted yet", construct),
+            Error::Verify(message) => {
+                write!(f, "formatting would change the module: {}", message)
+            }
+            Error::Idempotency(message) => {
+                write!(f, "formatting is not idempotent: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
         Error::Io(err)
@@ -0,0 +1,123 @@
+use super::utils::id_is_gensym;
+use wast::core::{FuncKind, FunctionType, ItemKind, ModuleField, TypeDef, TypeUse};
+use wast::token::{Id, Index};
+
+/// The number of parameters and results of a function type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub params: usize,
+    pub results: usize,
+}
+
+impl Signature {
+    fn of(functy: &FunctionType) -> Self {
+        Signature {
+            params: functy.params.len(),
+            results: functy.results.len(),
+        }
+    }
+}
+
+/// An item in one of the module's index spaces.
+#[derive(Clone)]
+struct Entry {
+    id: Option<String>,
+    signature: Option<Signature>,
+}
+
+/// Information about the module that is being formatted,
+/// used by nodes that need to look up items by index.
+#[derive(Clone, Default)]
+pub struct ModuleContext {
+    types: Vec<Entry>,
+    funcs: Vec<Entry>,
+}
+
+impl ModuleContext {
+    pub fn new(fields: &[ModuleField]) -> Self {
+        let mut context = ModuleContext::default();
+        for field in fields {
+            if let ModuleField::Type(ty) = field {
+                let signature = match &ty.def {
+                    TypeDef::Func(functy) => Some(Signature::of(functy)),
+                    TypeDef::Struct(..) | TypeDef::Array(..) => None,
+                };
+                context.types.push(Entry {
+                    id: ty.id.as_ref().and_then(id_name),
+                    signature,
+                });
+            }
+        }
+        // Imported functions precede defined functions in the index space.
+        let mut defined = Vec::new();
+        for field in fields {
+            match field {
+                ModuleField::Import(import) => {
+                    if let ItemKind::Func(ty) = &import.item.kind {
+                        let entry = Entry {
+                            id: import.item.id.as_ref().and_then(id_name),
+                            signature: context.type_use_signature(ty),
+                        };
+                        context.funcs.push(entry);
+                    }
+                }
+                ModuleField::Func(func) => {
+                    let entry = Entry {
+                        id: func.id.as_ref().and_then(id_name),
+                        signature: context.type_use_signature(&func.ty),
+                    };
+                    match func.kind {
+                        FuncKind::Import(..) => context.funcs.push(entry),
+                        FuncKind::Inline { .. } => defined.push(entry),
+                    }
+                }
+                _ => {}
+            }
+        }
+        context.funcs.extend(defined);
+        context
+    }
+
+    /// The signature of the type at `index`.
+    pub fn type_signature(&self, index: &Index) -> Option<Signature> {
+        lookup(&self.types, index)
+    }
+
+    /// The signature of the function at `index`.
+    pub fn func_signature(&self, index: &Index) -> Option<Signature> {
+        lookup(&self.funcs, index)
+    }
+
+    /// The signature described by a type use,
+    /// preferring the inline parameters and results if they are present.
+    pub fn type_use_signature(&self, ty: &TypeUse<FunctionType>) -> Option<Signature> {
+        match (&ty.inline, &ty.index) {
+            (Some(functy), _) => Some(Signature::of(functy)),
+            (None, Some(index)) => self.type_signature(index),
+            (None, None) => Some(Signature {
+                params: 0,
+                results: 0,
+            }),
+        }
+    }
+}
+
+fn lookup(entries: &[Entry], index: &Index) -> Option<Signature> {
+    match index {
+        Index::Num(n, ..) => entries.get(*n as usize)?.signature,
+        Index::Id(id) => {
+            entries
+                .iter()
+                .find(|entry| entry.id.as_deref() == Some(id.name()))?
+                .signature
+        }
+    }
+}
+
+fn id_name(id: &Id) -> Option<String> {
+    if id_is_gensym(id) {
+        None
+    } else {
+        Some(id.name().to_string())
+    }
+}
@@ -0,0 +1,223 @@
+use super::type_uses::{defined_types, field_type_uses};
+use super::utils::{func_ty_signature_eq, id_is_gensym};
+use std::collections::HashMap;
+use wast::core::{FunctionType, ModuleField, ModuleKind, Type, TypeDef};
+use wast::token::{Id, Index, Span};
+
+/// Remove function types with the same parameters and results as an earlier one,
+/// and refer to that type wherever they were referred to.
+///
+/// The type that is kept takes the first id and name of the types it replaces, if it has none.
+/// Types in recursion groups and types with a parent are left as they are.
+/// Returns the number of types that were removed.
+pub(crate) fn dedupe_types(kind: &mut ModuleKind) -> usize {
+    let fields = match kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return 0,
+    };
+    let types = fields
+        .iter()
+        .flat_map(|field| match field {
+            ModuleField::Type(ty) => vec![Some(ty)],
+            ModuleField::Rec(rec) => vec![None; rec.types.len()],
+            _ => Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    // The index of the type that each type is merged into.
+    let mut kept = (0..types.len()).collect::<Vec<_>>();
+    for (index, ty) in types.iter().enumerate() {
+        let functy = match ty.and_then(dedupable) {
+            Some(functy) => functy,
+            None => continue,
+        };
+        let first = types[..index].iter().position(|other| {
+            other
+                .and_then(dedupable)
+                .is_some_and(|other| func_ty_signature_eq(other, functy))
+        });
+        if let Some(first) = first {
+            kept[index] = first;
+        }
+    }
+    let removed = kept
+        .iter()
+        .enumerate()
+        .filter(|(index, kept)| index != *kept)
+        .count();
+    if removed == 0 {
+        return 0;
+    }
+
+    // The id and name of each kept type, taken from the first of its types that has one.
+    let mut names = HashMap::new();
+    for (index, ty) in types.iter().enumerate() {
+        if let Some(ty) = ty {
+            if let Some(id) = ty.id.filter(|id| !id_is_gensym(id)) {
+                names.entry(kept[index]).or_insert((id, ty.name));
+            }
+        }
+    }
+    let mut renumbered = Vec::with_capacity(types.len());
+    let mut next = 0;
+    for (index, kept) in kept.iter().enumerate() {
+        if index == *kept {
+            renumbered.push(next);
+            next += 1;
+        } else {
+            renumbered.push(renumbered[*kept]);
+        }
+    }
+    let references = Renumbering {
+        defined: defined_types(fields) as u32,
+        removed: removed as u32,
+        numbers: kept.iter().map(|kept| renumbered[*kept]).collect(),
+        ids: kept
+            .iter()
+            .map(|kept| names.get(kept).map(|(id, _)| *id))
+            .collect(),
+        indices: types
+            .iter()
+            .enumerate()
+            .filter_map(|(index, ty)| Some((ty.as_ref()?.id?, index)))
+            .collect(),
+    };
+
+    let mut index = 0;
+    fields.retain_mut(|field| {
+        let ty = match field {
+            ModuleField::Type(ty) => ty,
+            ModuleField::Rec(rec) => {
+                index += rec.types.len();
+                return true;
+            }
+            _ => return true,
+        };
+        index += 1;
+        if kept[index - 1] != index - 1 {
+            return false;
+        }
+        if let Some((id, name)) = names.get(&(index - 1)) {
+            ty.id = Some(*id);
+            ty.name = ty.name.or(*name);
+        }
+        true
+    });
+    for field in fields.iter_mut() {
+        match field {
+            ModuleField::Type(ty) => references.parent(ty),
+            ModuleField::Rec(rec) => rec.types.iter_mut().for_each(|ty| references.parent(ty)),
+            field => field_type_uses(field, &mut |ty, _| {
+                if let Some(index) = &mut ty.index {
+                    references.rewrite(index);
+                }
+            }),
+        }
+    }
+    removed
+}
+
+/// The function type of a type definition that can be merged with others.
+fn dedupable<'a, 'src>(ty: &'a Type<'src>) -> Option<&'a FunctionType<'src>> {
+    match &ty.def {
+        TypeDef::Func(functy) if ty.parent.is_none() => Some(functy),
+        _ => None,
+    }
+}
+
+/// How references to the types of a module change once duplicates are removed.
+struct Renumbering<'src> {
+    /// The number of [`defined_types`].
+    defined: u32,
+    removed: u32,
+    /// The new index of each defined type.
+    numbers: Vec<u32>,
+    /// The new id of each defined type.
+    ids: Vec<Option<Id<'src>>>,
+    /// The index of each defined type with an id.
+    indices: HashMap<Id<'src>, usize>,
+}
+
+impl<'src> Renumbering<'src> {
+    fn rewrite(&self, index: &mut Index<'src>) {
+        match index {
+            Index::Num(n, ..) if *n >= self.defined => *n -= self.removed,
+            Index::Num(n, ..) => *n = self.numbers[*n as usize],
+            Index::Id(id) => {
+                if let Some(defined) = self.indices.get(id) {
+                    *index = match self.ids[*defined] {
+                        Some(id) => Index::Id(id),
+                        None => Index::Num(self.numbers[*defined], Span::from_offset(0)),
+                    };
+                }
+            }
+        }
+    }
+
+    fn parent(&self, ty: &mut Type<'src>) {
+        if let Some(parent) = &mut ty.parent {
+            self.rewrite(parent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fmt::{fmt, fmt_with_summary};
+    use crate::options::Options;
+    use pretty_assertions::assert_eq;
+
+    const SOURCE: &str = "(module
+  (type (func (param i32) (result i32)))
+  (type $b (func (param i32) (result i32)))
+  (type $c (func))
+  (type $d (func))
+  (func (type 1) (local.get 0))
+  (func (type $d))
+  (func (type 4)
+    (call_indirect (type $b) (i32.const 0) (i32.const 0))
+    (drop))
+  (table 1 funcref))";
+
+    #[test]
+    fn dedupe_types_should_refer_to_the_first_identical_type() {
+        let options = Options {
+            dedupe_types: true,
+            ..Options::default()
+        };
+        let expected = "(module
+\t(type $b (func (param i32) (result i32)))
+\t(type $c (func))
+\t(func (type 0)
+\t\t(local.get 0)
+\t)
+\t(func (type $c))
+\t(func (type 2)
+\t\t(i32.const 0)
+\t\t(i32.const 0)
+\t\t(call_indirect (type $b))
+\t\tdrop
+\t)
+\t(table 1 funcref)
+)
+";
+        assert_eq!(fmt(SOURCE, options), expected);
+    }
+
+    #[test]
+    fn fmt_with_summary_should_count_removed_types() {
+        let options = Options {
+            dedupe_types: true,
+            ..Options::default()
+        };
+        let (formatted, summary) = fmt_with_summary(SOURCE, options.clone()).unwrap();
+        assert_eq!(formatted, fmt(SOURCE, options));
+        assert_eq!(summary.removed_types, 2);
+        assert_eq!(
+            fmt_with_summary(SOURCE, Options::default())
+                .unwrap()
+                .1
+                .removed_types,
+            0
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use crate::options::Options;
+
+/// A single element of the document produced by a `Formatter`.
+///
+/// The document is a flat stream in the style of Oppen's pretty printer:
+/// groups are delimited by `Begin` and `End`, and the renderer decides
+/// for each group whether its `Break`s are rendered as spaces or as newlines.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Doc {
+    /// Literal text. Never contains a newline.
+    Text(String),
+    /// The indentation at the start of a line.
+    Indentation,
+    /// A mandatory line break.
+    Newline,
+    /// Increase the indentation of subsequent lines.
+    Indent,
+    /// Decrease the indentation of subsequent lines.
+    Dedent,
+    /// A space if the enclosing group fits on the line, otherwise a line break.
+    Break,
+    /// The start of a group.
+    /// If `indent` is set, the group's line breaks are always indented one level
+    /// deeper than the line on which the group starts. Otherwise, a group that is nested
+    /// in a broken group lines up with the items of the enclosing group.
+    Begin { breaks: Breaks, indent: bool },
+    /// The end of the most recent group.
+    End,
+    /// Records that the next text corresponds to the given byte offset in the source.
+    Mark(usize),
+}
+
+/// Describes how the breaks of a group are rendered
+/// when the group does not fit on a single line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Breaks {
+    /// Either every break in the group is a line break, or none are.
+    Consistent,
+    /// Each break is a line break only if the next item does not fit.
+    Fill,
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Flat,
+    Broken(Breaks),
+}
+
+struct Frame {
+    mode: Mode,
+    level: usize,
+}
+
+/// Render a document into a string,
+/// breaking groups that would exceed the maximum width.
+pub(crate) fn render(docs: &[Doc], options: &Options) -> String {
+    render_marked(docs, options).0
+}
+
+/// Render a document like `render`, also returning the source offset of each mark
+/// paired with the offset in the output of the text that follows it.
+pub(crate) fn render_marked(docs: &[Doc], options: &Options) -> (String, Vec<(usize, usize)>) {
+    if options.minify {
+        return render_compact(docs, options);
+    }
+    let max_width = options.max_width;
+    let unit = options.indent_unit();
+    let line_ending = options.end_of_line.as_str();
+    let mut output = String::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut indentation = 0;
+    let mut level = 0;
+    let mut column = 0;
+    let mut marks = Vec::new();
+    let mut pending = Vec::new();
+    for (i, doc) in docs.iter().enumerate() {
+        match doc {
+            Doc::Text(text) => {
+                marks.extend(pending.drain(..).map(|mark| (mark, output.len())));
+                output.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Indentation => {
+                level = indentation;
+                output.push_str(&unit.repeat(level));
+                column = level * options.indent_width;
+            }
+            Doc::Newline if i + 1 == docs.len() && !options.insert_final_newline => {}
+            Doc::Newline => {
+                output.push_str(line_ending);
+                column = 0;
+            }
+            Doc::Indent => indentation += 1,
+            Doc::Dedent => indentation -= 1,
+            Doc::Begin { breaks, indent } => {
+                let mode = match stack.last() {
+                    Some(Frame {
+                        mode: Mode::Flat, ..
+                    }) => Mode::Flat,
+                    _ => match flat_width(&docs[i + 1..]) {
+                        Some(width) if column + width <= max_width => Mode::Flat,
+                        _ => Mode::Broken(*breaks),
+                    },
+                };
+                // A group nested in a broken group continues at the same level,
+                // so that its items line up with those of the enclosing group.
+                let level = match stack.last() {
+                    Some(Frame {
+                        mode: Mode::Broken(..),
+                        level,
+                    }) if !indent => *level,
+                    _ => level + 1,
+                };
+                stack.push(Frame { mode, level });
+            }
+            Doc::End => {
+                stack.pop();
+            }
+            Doc::Mark(mark) => pending.push(*mark),
+            Doc::Break => {
+                let frame = stack.last();
+                let newline = match frame.map(|frame| frame.mode) {
+                    None | Some(Mode::Flat) => false,
+                    Some(Mode::Broken(Breaks::Consistent)) => true,
+                    Some(Mode::Broken(Breaks::Fill)) => match next_width(&docs[i + 1..]) {
+                        Some(width) => column + 1 + width > max_width,
+                        None => true,
+                    },
+                };
+                if let (true, Some(frame)) = (newline, frame) {
+                    level = frame.level;
+                    output.push_str(line_ending);
+                    output.push_str(&unit.repeat(level));
+                    column = level * options.indent_width;
+                } else {
+                    output.push(' ');
+                    column += 1;
+                }
+            }
+        }
+    }
+    debug_assert!(stack.is_empty(), "unbalanced group");
+    (output, marks)
+}
+
+/// Render a document on a single line, ignoring its groups and indentation
+/// and separating its items by no more whitespace than needed.
+fn render_compact(docs: &[Doc], options: &Options) -> (String, Vec<(usize, usize)>) {
+    let mut output = String::new();
+    let mut marks = Vec::new();
+    let mut pending = Vec::new();
+    let mut space = false;
+    for (i, doc) in docs.iter().enumerate() {
+        match doc {
+            Doc::Text(text) => {
+                if text.starts_with(')') {
+                    if output.ends_with(' ') {
+                        output.pop();
+                    }
+                } else if space && !output.is_empty() && !output.ends_with([' ', '(']) {
+                    output.push(' ');
+                }
+                space = false;
+                marks.extend(pending.drain(..).map(|mark| (mark, output.len())));
+                output.push_str(text);
+            }
+            Doc::Newline if i + 1 == docs.len() => {
+                if options.insert_final_newline {
+                    output.push_str(options.end_of_line.as_str());
+                }
+            }
+            Doc::Newline | Doc::Break => space = true,
+            Doc::Mark(mark) => pending.push(*mark),
+            Doc::Indentation | Doc::Indent | Doc::Dedent | Doc::Begin { .. } | Doc::End => {}
+        }
+    }
+    (output, marks)
+}
+
+/// The width of a group when rendered flat, given the documents following its `Begin`.
+/// Includes any text that trails the group up to the next break opportunity.
+/// Returns `None` if the group contains a mandatory line break.
+fn flat_width(docs: &[Doc]) -> Option<usize> {
+    let mut width = 0;
+    let mut depth = 1;
+    for doc in docs {
+        match doc {
+            Doc::Text(text) => width += text.chars().count(),
+            Doc::Break if depth > 0 => width += 1,
+            Doc::Break => break,
+            Doc::Indentation | Doc::Newline if depth > 0 => return None,
+            Doc::Indentation | Doc::Newline => break,
+            Doc::Begin { .. } if depth == 0 => break,
+            Doc::Begin { .. } => depth += 1,
+            Doc::End if depth > 0 => depth -= 1,
+            Doc::End | Doc::Indent | Doc::Dedent | Doc::Mark(..) => {}
+        }
+    }
+    Some(width)
+}
+
+/// The width of the item following a break in a fill group,
+/// up to the next break in the same group.
+/// Returns `None` if the item contains a mandatory line break.
+fn next_width(docs: &[Doc]) -> Option<usize> {
+    let mut width = 0;
+    for (i, doc) in docs.iter().enumerate() {
+        match doc {
+            Doc::Text(text) => width += text.chars().count(),
+            Doc::Break | Doc::End => break,
+            Doc::Indentation | Doc::Newline => return None,
+            // A nested group is measured flat, along with the text that trails it.
+            Doc::Begin { .. } => return flat_width(&docs[i + 1..]).map(|nested| width + nested),
+            Doc::Indent | Doc::Dedent | Doc::Mark(..) => {}
+        }
+    }
+    Some(width)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Breaks, Doc};
+    use crate::options::Options;
+
+    fn render(docs: &[Doc], max_width: usize) -> String {
+        let options = Options {
+            max_width,
+            ..Options::default()
+        };
+        super::render(docs, &options)
+    }
+
+    fn text(s: &str) -> Doc {
+        Doc::Text(s.to_string())
+    }
+
+    fn words(breaks: Breaks, words: &[&str]) -> Vec<Doc> {
+        let mut docs = vec![Doc::Begin {
+            breaks,
+            indent: false,
+        }];
+        for (i, word) in words.iter().enumerate() {
+            if i > 0 {
+                docs.push(Doc::Break);
+            }
+            docs.push(text(word));
+        }
+        docs.push(Doc::End);
+        docs
+    }
+
+    #[test]
+    fn group_should_stay_flat_when_it_fits() {
+        let docs = words(Breaks::Consistent, &["aaa", "bbb", "ccc"]);
+        assert_eq!(render(&docs, 11), "aaa bbb ccc");
+    }
+
+    #[test]
+    fn consistent_group_should_break_every_break() {
+        let docs = words(Breaks::Consistent, &["aaa", "bbb", "ccc"]);
+        assert_eq!(render(&docs, 10), "aaa\n\tbbb\n\tccc");
+    }
+
+    #[test]
+    fn fill_group_should_break_only_when_needed() {
+        let docs = words(Breaks::Fill, &["aaa", "bbb", "ccc"]);
+        assert_eq!(render(&docs, 10), "aaa bbb\n\tccc");
+    }
+
+    #[test]
+    fn trailing_text_should_count_towards_width() {
+        let mut docs = words(Breaks::Consistent, &["aaa", "bbb"]);
+        docs.push(text(")"));
+        assert_eq!(render(&docs, 7), "aaa\n\tbbb)");
+    }
+
+    #[test]
+    fn nested_group_should_line_up_with_broken_group() {
+        let mut docs = vec![
+            Doc::Begin {
+                breaks: Breaks::Consistent,
+                indent: false,
+            },
+            text("aaa"),
+            Doc::Break,
+        ];
+        docs.extend(words(Breaks::Consistent, &["bbb", "ccc"]));
+        docs.push(Doc::End);
+        assert_eq!(render(&docs, 6), "aaa\n\tbbb\n\tccc");
+    }
+
+    #[test]
+    fn indented_group_should_nest_in_broken_group() {
+        let docs = vec![
+            Doc::Begin {
+                breaks: Breaks::Consistent,
+                indent: false,
+            },
+            text("aaa"),
+            Doc::Break,
+            Doc::Begin {
+                breaks: Breaks::Consistent,
+                indent: true,
+            },
+            text("bbb"),
+            Doc::Break,
+            text("ccc"),
+            Doc::End,
+            Doc::End,
+        ];
+        assert_eq!(render(&docs, 6), "aaa\n\tbbb\n\t\tccc");
+    }
+
+    #[test]
+    fn continuation_should_be_indented_relative_to_line() {
+        let mut docs = vec![Doc::Indent, Doc::Indentation];
+        docs.extend(words(Breaks::Consistent, &["aaa", "bbb"]));
+        assert_eq!(render(&docs, 8), "\taaa\n\t\tbbb");
+    }
+}
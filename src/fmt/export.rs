@@ -31,6 +31,7 @@ impl Fmt for ExportKind {
 
 impl<'src> Fmt for &InlineExport<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
+        formatter.group();
         let mut names = self.names.iter();
         if let Some(name) = names.next() {
             formatter.write("(export ");
@@ -38,10 +39,11 @@ impl<'src> Fmt for &InlineExport<'src> {
             formatter.write(")");
         }
         for name in names {
-            formatter.write(" ");
+            formatter.soft_break();
             formatter.write("(export ");
             formatter.fmt(*name);
             formatter.write(")");
         }
+        formatter.end_group();
     }
 }
@@ -0,0 +1,340 @@
+use super::range::{field_origins, field_spans};
+use super::shape::instruction_spans;
+use super::{describe_field, format};
+use crate::error::Error;
+use crate::options::Options;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use wast::core::{FuncKind, Instruction, ModuleField, ModuleKind};
+
+/// A module field or instruction that was copied from the source as it is,
+/// because it contains a construct that cannot be formatted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fallback {
+    /// The line of the source on which the field or instruction starts, counting from 1.
+    pub line: usize,
+    /// A description of the field, such as ``func $f``,
+    /// or the source text of the instruction, such as ``select (result i32)``.
+    pub field: String,
+    /// A description of the construct that cannot be formatted.
+    pub construct: String,
+}
+
+impl fmt::Display for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {} cannot be formatted yet, so `{}` was left as it is",
+            self.line, self.construct, self.field
+        )
+    }
+}
+
+/// Format `.wat` source code like [`try_fmt`](super::try_fmt),
+/// copying instructions and module fields that cannot be formatted from the source as they are.
+///
+/// Returns the formatted code together with the instructions and fields that were copied.
+/// Constructs outside of module fields, such as binary modules,
+/// are still reported as errors.
+///
+/// Copied code keeps its ids and indices, so it cannot be combined with options
+/// that rewrite the references of other fields, namely `minify` and `dedupe_types`.
+/// No source map is recorded, unlike [`fmt_with_source_map`](super::fmt_with_source_map).
+pub fn fmt_best_effort(source: &str, options: Options) -> Result<(String, Vec<Fallback>), Error> {
+    if options.minify {
+        return Err(Error::Unsupported(
+            "copied code in minified output".to_string(),
+        ));
+    }
+    if options.dedupe_types {
+        return Err(Error::Unsupported(
+            "copied code with deduplicated types".to_string(),
+        ));
+    }
+    let (mut formatter, _) = format(source, options, Mode::BestEffort)?;
+    let fallbacks = formatter.fallbacks.take().unwrap_or_default().fallbacks;
+    Ok((formatter.into(), fallbacks))
+}
+
+/// How a module is formatted.
+/// The modes exclude each other, since copied code has no anchors for a source map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// Fail on constructs that cannot be formatted.
+    Strict,
+    /// Record source offsets for a source map.
+    Anchored,
+    /// Copy instructions and fields that cannot be formatted.
+    BestEffort,
+}
+
+/// The source text of each module field and instruction,
+/// for those that cannot be formatted.
+#[derive(Debug, Default)]
+pub(crate) struct Fallbacks {
+    /// The line and source text of each source field.
+    fields: Vec<(usize, String)>,
+    /// The byte range of each source field.
+    spans: Vec<Range<usize>>,
+    /// The index of the source field that each module field was parsed from.
+    origins: Vec<Option<usize>>,
+    /// Whether some source fields were merged into others, such as exports written inline.
+    merged: bool,
+    /// The line and source text of each instruction,
+    /// keyed by the address of the instruction in the parsed module.
+    instructions: HashMap<usize, (usize, String)>,
+    /// The source field of each copied field, and the index of the copied field.
+    copied: Vec<(usize, usize)>,
+    /// The source fields of the fields that were formatted.
+    formatted: Vec<usize>,
+    /// The instructions copied in the field being formatted,
+    /// which are dropped if the whole field is copied.
+    pending: Vec<Fallback>,
+    pub(crate) fallbacks: Vec<Fallback>,
+}
+
+impl Fallbacks {
+    /// The fallbacks of `source`, whose module `kind` has yet to be prepared.
+    /// Returns `None` if the fields cannot be located in the source.
+    pub(crate) fn new(source: &str, kind: &ModuleKind) -> Option<Self> {
+        let count = match kind {
+            ModuleKind::Text(fields) => fields.len(),
+            ModuleKind::Binary(..) => return None,
+        };
+        let spans = field_spans(source).filter(|spans| spans.len() == count)?;
+        let fields = spans
+            .iter()
+            .map(|span| (line(source, span.start), source[span.clone()].to_string()))
+            .collect();
+        Some(Fallbacks {
+            fields,
+            origins: (0..count).map(Some).collect(),
+            spans,
+            ..Fallbacks::default()
+        })
+    }
+
+    /// Apply the fallbacks to the fields of the prepared module `kind`,
+    /// which may have been added, removed or moved.
+    pub(crate) fn follow(&mut self, source: &str, kind: &ModuleKind) {
+        let fields = match kind {
+            ModuleKind::Text(fields) => fields,
+            ModuleKind::Binary(..) => return,
+        };
+        self.origins = field_origins(&self.spans, fields);
+        self.merged = (0..self.spans.len()).any(|index| !self.origins.contains(&Some(index)));
+        for field in fields {
+            let (func, expression) = match field {
+                ModuleField::Func(func) => match &func.kind {
+                    FuncKind::Inline { expression, .. } => (func, expression),
+                    FuncKind::Import(..) => continue,
+                },
+                _ => continue,
+            };
+            let spans = instruction_spans(source, func.span.offset())
+                .filter(|spans| spans.len() == expression.instrs.len());
+            for (instruction, span) in expression.instrs.iter().zip(spans.iter().flatten()) {
+                let text = match span.clone().map(|span| &source[span]) {
+                    // Copied instructions are written on the line of the instruction they replace.
+                    Some(text) if !text.contains('\n') => text,
+                    _ => continue,
+                };
+                let line = line(source, span.as_ref().map_or(0, |span| span.start));
+                self.instructions
+                    .insert(address(instruction), (line, text.to_string()));
+            }
+        }
+    }
+
+    /// Whether the field at `index` is written as part of the source text of another field,
+    /// because it was split from the field that was copied.
+    pub(crate) fn skip(&self, index: usize) -> bool {
+        let origin = self.origins.get(index).copied().flatten();
+        self.copied
+            .iter()
+            .any(|(copied, other)| Some(*copied) == origin && *other != index)
+    }
+
+    /// Record that the field at `index` was formatted, along with the instructions copied in it.
+    pub(crate) fn keep(&mut self, index: usize) {
+        self.fallbacks.append(&mut self.pending);
+        if let Some(origin) = self.origins.get(index).copied().flatten() {
+            self.formatted.push(origin);
+        }
+    }
+
+    /// Whether fields were formatted that the source text of a copied field repeats,
+    /// since they were split from it and written before it.
+    pub(crate) fn repeats(&self) -> bool {
+        self.copied
+            .iter()
+            .any(|(origin, _)| self.formatted.contains(origin))
+    }
+
+    /// The fallbacks for formatting the module again, skipping the fields that `repeats` found.
+    pub(crate) fn restart(self) -> Self {
+        Fallbacks {
+            fallbacks: Vec::new(),
+            pending: Vec::new(),
+            formatted: Vec::new(),
+            ..self
+        }
+    }
+
+    /// Record that the field at `index` cannot be formatted,
+    /// returning its source text, or the construct to report if it cannot be copied.
+    pub(crate) fn fall_back(
+        &mut self,
+        index: usize,
+        field: &ModuleField,
+        construct: String,
+    ) -> Result<String, String> {
+        self.pending.clear();
+        let origin = match self.origins.get(index).copied().flatten() {
+            Some(origin) => origin,
+            None => return Err(construct),
+        };
+        // The source text would omit the fields that were merged into others.
+        if self.merged {
+            return Err(format!("{} in a module with merged fields", construct));
+        }
+        let (line, text) = &self.fields[origin];
+        if !self.copied.contains(&(origin, index)) {
+            self.copied.push((origin, index));
+        }
+        self.fallbacks.push(Fallback {
+            line: *line,
+            field: describe_field(field),
+            construct,
+        });
+        Ok(text.clone())
+    }
+
+    /// Record that `instruction` cannot be formatted, returning its source text.
+    pub(crate) fn fall_back_instruction(
+        &mut self,
+        instruction: &Instruction,
+        construct: &str,
+    ) -> Option<String> {
+        let (line, text) = self.instructions.get(&address(instruction))?;
+        self.pending.push(Fallback {
+            line: *line,
+            field: text.clone(),
+            construct: construct.to_string(),
+        });
+        Some(text.clone())
+    }
+}
+
+/// The line of `source` that `offset` is on, counting from 1.
+fn line(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+fn address(instruction: &Instruction) -> usize {
+    instruction as *const Instruction as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::fmt_best_effort;
+    use crate::error::Error;
+    use crate::options::Options;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn best_effort_should_copy_unsupported_fields() {
+        let source = "(module
+  (global $a i32   (i32.const 1))
+  (elem   funcref (ref.null func))
+  (global $b i32   (i32.const 2)))";
+        let (formatted, fallbacks) = fmt_best_effort(source, Options::default()).unwrap();
+        let expected = "(module
+\t(global $a i32 (i32.const 1))
+\t(elem   funcref (ref.null func))
+\t(global $b i32 (i32.const 2))
+)
+";
+        assert_eq!(formatted, expected);
+        assert_eq!(fallbacks.len(), 1);
+        assert_eq!(
+            fallbacks[0].to_string(),
+            "line 3: passive element segments cannot be formatted yet, so `elem` was left as it is"
+        );
+    }
+
+    #[test]
+    fn best_effort_should_copy_unsupported_instructions() {
+        let source = "(module
+  (func (result i32)
+    (select (result i32) (i32.const 1) (i32.const 2) (i32.const 0))))";
+        let (formatted, fallbacks) = fmt_best_effort(source, Options::default()).unwrap();
+        let expected = "(module
+\t(func (result i32)
+\t\t(i32.const 1)
+\t\t(i32.const 2)
+\t\t(i32.const 0)
+\t\tselect (result i32)
+\t)
+)
+";
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            fallbacks[0].to_string(),
+            "line 3: the `Select` instruction cannot be formatted yet, so `select (result i32)` was left as it is"
+        );
+    }
+
+    #[test]
+    fn best_effort_should_copy_fields_that_options_change() {
+        let source = "(module
+  (tag $t)
+  (func $f (export \"f\") nop))";
+        let options = Options {
+            resolve_names: true,
+            ..Options::default()
+        };
+        let (formatted, fallbacks) = fmt_best_effort(source, options).unwrap();
+        let expected = "(module
+\t(tag $t)
+\t(export \"f\" (func 0))
+\t(func $f (type 0)
+\t\tnop
+\t)
+\t(type (func))
+)
+";
+        assert_eq!(formatted, expected);
+        assert_eq!(fallbacks.len(), 1);
+    }
+
+    #[test]
+    fn best_effort_should_copy_fields_with_the_fields_split_from_them() {
+        let source = "(module
+  (tag $t (export \"t\")))";
+        let options = Options {
+            resolve_names: true,
+            ..Options::default()
+        };
+        let (formatted, _) = fmt_best_effort(source, options).unwrap();
+        let expected = "(module
+\t(tag $t (export \"t\"))
+\t(type (func))
+)
+";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn best_effort_should_reject_minified_output() {
+        let options = Options {
+            minify: true,
+            ..Options::default()
+        };
+        assert!(matches!(
+            fmt_best_effort("(module)", options),
+            Err(Error::Unsupported(..))
+        ));
+    }
+}
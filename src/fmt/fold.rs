@@ -0,0 +1,676 @@
+use super::context::{ModuleContext, Signature};
+use super::instruction::{instr_args, instr_is_valid, instr_name};
+use super::shape::Segment;
+use super::utils::{fmt_instructions, fmt_long_expression};
+use super::Formatter;
+use std::slice;
+use wast::core::{BlockType, Expression, Instruction};
+use wast::token::Index;
+
+/// An instruction together with the instructions that produce its operands.
+enum Node<'a, 'src> {
+    Plain {
+        instruction: &'a Instruction<'src>,
+        operands: Vec<Node<'a, 'src>>,
+        results: usize,
+    },
+    Block {
+        instruction: &'a Instruction<'src>,
+        body: Vec<Node<'a, 'src>>,
+        results: usize,
+    },
+    If {
+        instruction: &'a Instruction<'src>,
+        condition: Vec<Node<'a, 'src>>,
+        then: Vec<Node<'a, 'src>>,
+        otherwise: Option<Vec<Node<'a, 'src>>>,
+        results: usize,
+    },
+    /// An instruction, or a block with its body, that cannot be folded safely.
+    Flat { instrs: &'a [Instruction<'src>] },
+}
+
+impl<'a, 'src> Node<'a, 'src> {
+    /// The number of results that later nodes can take as operands.
+    /// The results of flat instructions stay on the stack.
+    fn results(&self) -> usize {
+        match self {
+            Node::Plain { results, .. }
+            | Node::Block { results, .. }
+            | Node::If { results, .. } => *results,
+            Node::Flat { .. } => 0,
+        }
+    }
+
+    fn is_block(&self) -> bool {
+        match self {
+            Node::Plain { operands, .. } => operands.iter().any(Node::is_block),
+            Node::Block { .. } | Node::If { .. } | Node::Flat { .. } => true,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Label<'src> {
+    name: Option<&'src str>,
+    /// The number of values a branch to the label carries, if known.
+    arity: Option<usize>,
+}
+
+impl<'src> Label<'src> {
+    fn new(instruction: &Instruction, bt: &BlockType<'src>, signature: Option<Signature>) -> Self {
+        let arity = signature.map(|signature| match instruction {
+            Instruction::Loop(..) => signature.params,
+            _ => signature.results,
+        });
+        Label {
+            name: bt.label.as_ref().map(|id| id.name()),
+            arity,
+        }
+    }
+}
+
+/// The signature of a block.
+/// Blocks with parameters or multiple results are not folded.
+fn block_signature(module: &ModuleContext, bt: &BlockType) -> Option<Signature> {
+    let signature = module.type_use_signature(&bt.ty)?;
+    if signature.params > 0 || signature.results > 1 {
+        return None;
+    }
+    Some(signature)
+}
+
+/// Rebuilds the nested structure of a flat instruction sequence.
+struct Folder<'a, 'm, 'src> {
+    module: &'m ModuleContext,
+    instrs: &'a [Instruction<'src>],
+    position: usize,
+    labels: Vec<Label<'src>>,
+    results: usize,
+}
+
+impl<'a, 'm, 'src> Folder<'a, 'm, 'src> {
+    /// Fold a sequence of instructions up to the next `else` or `end`.
+    /// Instructions and blocks that cannot be folded safely are kept flat.
+    /// Returns `None` if the blocks of the sequence are not properly nested.
+    fn fold_sequence(&mut self) -> Option<Vec<Node<'a, 'src>>> {
+        let mut nodes: Vec<Node<'a, 'src>> = Vec::new();
+        // After an instruction that never falls through,
+        // the stack is polymorphic and operands cannot be determined.
+        let mut unreachable = false;
+        while let Some(instruction) = self.instrs.get(self.position) {
+            match instruction {
+                Instruction::End(..) | Instruction::Else(..) => break,
+                Instruction::Block(..) | Instruction::Loop(..) | Instruction::If(..) => {
+                    let (start, labels) = (self.position, self.labels.len());
+                    match self.fold_block(instruction) {
+                        Some(Node::If {
+                            instruction,
+                            then,
+                            otherwise,
+                            results,
+                            ..
+                        }) => {
+                            let condition = if unreachable {
+                                Vec::new()
+                            } else {
+                                take_operands(&mut nodes, 1)
+                            };
+                            nodes.push(Node::If {
+                                instruction,
+                                condition,
+                                then,
+                                otherwise,
+                                results,
+                            });
+                        }
+                        Some(node) => nodes.push(node),
+                        None => {
+                            self.position = start;
+                            self.labels.truncate(labels);
+                            nodes.push(self.flat_block()?);
+                        }
+                    }
+                }
+                Instruction::Try(..) => nodes.push(self.flat_block()?),
+                _ => {
+                    self.position += 1;
+                    match self.arity(instruction) {
+                        Some((params, results)) => {
+                            let operands = if unreachable {
+                                Vec::new()
+                            } else {
+                                take_operands(&mut nodes, params)
+                            };
+                            nodes.push(Node::Plain {
+                                instruction,
+                                operands,
+                                results,
+                            });
+                        }
+                        None => nodes.push(Node::Flat {
+                            instrs: slice::from_ref(instruction),
+                        }),
+                    }
+                    if instr_is_unconditional_branch(instruction) {
+                        unreachable = true;
+                    }
+                }
+            }
+        }
+        Some(nodes)
+    }
+
+    /// Fold the block that starts with `instruction`, leaving the condition of an `if` empty.
+    /// Returns `None` if the block cannot be folded safely.
+    fn fold_block(&mut self, instruction: &'a Instruction<'src>) -> Option<Node<'a, 'src>> {
+        self.position += 1;
+        let bt = match instruction {
+            Instruction::Block(bt) | Instruction::Loop(bt) | Instruction::If(bt) => bt,
+            _ => return None,
+        };
+        let signature = block_signature(self.module, bt)?;
+        self.labels
+            .push(Label::new(instruction, bt, Some(signature)));
+        let node = match instruction {
+            Instruction::If(..) => {
+                let then = self.fold_sequence()?;
+                let otherwise = match self.instrs.get(self.position) {
+                    Some(Instruction::Else(..)) => {
+                        self.position += 1;
+                        Some(self.fold_sequence()?)
+                    }
+                    _ => None,
+                };
+                Node::If {
+                    instruction,
+                    condition: Vec::new(),
+                    then,
+                    otherwise,
+                    results: signature.results,
+                }
+            }
+            _ => Node::Block {
+                instruction,
+                body: self.fold_sequence()?,
+                results: signature.results,
+            },
+        };
+        self.expect_end()?;
+        self.labels.pop();
+        Some(node)
+    }
+
+    /// Keep the block that starts at the current position flat, up to its `end` or `delegate`.
+    fn flat_block(&mut self) -> Option<Node<'a, 'src>> {
+        let start = self.position;
+        let mut depth = 0;
+        while let Some(instruction) = self.instrs.get(self.position) {
+            self.position += 1;
+            match instruction {
+                Instruction::Block(..)
+                | Instruction::Loop(..)
+                | Instruction::If(..)
+                | Instruction::Try(..) => depth += 1,
+                Instruction::End(..) | Instruction::Delegate(..) => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Some(Node::Flat {
+                    instrs: &self.instrs[start..self.position],
+                });
+            }
+        }
+        None
+    }
+
+    fn expect_end(&mut self) -> Option<()> {
+        match self.instrs.get(self.position) {
+            Some(Instruction::End(..)) => {
+                self.position += 1;
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    fn label_arity(&self, index: &Index) -> Option<usize> {
+        let label = match index {
+            Index::Num(depth, ..) => {
+                let depth = *depth as usize;
+                if depth == self.labels.len() {
+                    // The outermost label is the function body itself.
+                    return Some(self.results);
+                }
+                self.labels.iter().rev().nth(depth)?
+            }
+            Index::Id(id) => self
+                .labels
+                .iter()
+                .rev()
+                .find(|label| label.name == Some(id.name()))?,
+        };
+        label.arity
+    }
+
+    /// The number of operands an instruction consumes and the number of results it produces.
+    fn arity(&self, instruction: &Instruction) -> Option<(usize, usize)> {
+        let arity = match instruction {
+            Instruction::Br(index) => (self.label_arity(index)?, 0),
+            Instruction::BrIf(index) => {
+                let arity = self.label_arity(index)?;
+                (arity + 1, arity)
+            }
+            Instruction::BrTable(indices) => (self.label_arity(&indices.default)? + 1, 0),
+            Instruction::Return => (self.results, 0),
+            Instruction::Call(index) => {
+                let signature = self.module.func_signature(index)?;
+                (signature.params, signature.results)
+            }
+            Instruction::CallIndirect(call_indirect) => {
+                let signature = self.module.type_use_signature(&call_indirect.ty)?;
+                (signature.params + 1, signature.results)
+            }
+            _ => instr_arity(instruction)?,
+        };
+        Some(arity)
+    }
+}
+
+/// Remove the operands of an instruction from the end of `nodes`.
+/// Only nodes producing a single value are used as operands;
+/// any remaining operands are taken from the stack as usual.
+fn take_operands<'a, 'src>(nodes: &mut Vec<Node<'a, 'src>>, count: usize) -> Vec<Node<'a, 'src>> {
+    let available = nodes
+        .iter()
+        .rev()
+        .take(count)
+        .take_while(|node| node.results() == 1)
+        .count();
+    nodes.split_off(nodes.len() - available)
+}
+
+fn instr_is_unconditional_branch(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Unreachable
+            | Instruction::Br(..)
+            | Instruction::BrTable(..)
+            | Instruction::Return
+    )
+}
+
+/// The number of operands and results of instructions with a fixed signature.
+fn instr_arity(instruction: &Instruction) -> Option<(usize, usize)> {
+    use Instruction::*;
+    let arity = match instruction {
+        Unreachable | Nop => (0, 0),
+        Drop => (1, 0),
+        Select(..) => (3, 1),
+
+        I32Const(..) | I64Const(..) | F32Const(..) | F64Const(..) => (0, 1),
+        LocalGet(..) | GlobalGet(..) | MemorySize(..) => (0, 1),
+        LocalSet(..) | GlobalSet(..) => (1, 0),
+        LocalTee(..) | MemoryGrow(..) => (1, 1),
+
+        I32Load(..) | I64Load(..) | F32Load(..) | F64Load(..) | I32Load8s(..) | I32Load8u(..)
+        | I32Load16s(..) | I32Load16u(..) | I64Load8s(..) | I64Load8u(..) | I64Load16s(..)
+        | I64Load16u(..) | I64Load32s(..) | I64Load32u(..) => (1, 1),
+        I32Store(..) | I64Store(..) | F32Store(..) | F64Store(..) | I32Store8(..)
+        | I32Store16(..) | I64Store8(..) | I64Store16(..) | I64Store32(..) => (2, 0),
+
+        I32Clz | I32Ctz | I32Popcnt | I64Clz | I64Ctz | I64Popcnt | F32Abs | F32Neg | F32Sqrt
+        | F32Ceil | F32Floor | F32Trunc | F32Nearest | F64Abs | F64Neg | F64Sqrt | F64Ceil
+        | F64Floor | F64Trunc | F64Nearest | I32Eqz | I64Eqz => (1, 1),
+
+        I32WrapI64 | I32TruncF32S | I32TruncF32U | I32TruncF64S | I32TruncF64U
+        | I32TruncSatF32S | I32TruncSatF32U | I32TruncSatF64S | I32TruncSatF64U | I64ExtendI32S
+        | I64ExtendI32U | I64TruncF32S | I64TruncF32U | I64TruncF64S | I64TruncF64U
+        | I64TruncSatF32S | I64TruncSatF32U | I64TruncSatF64S | I64TruncSatF64U
+        | F32ConvertI32S | F32ConvertI32U | F32ConvertI64S | F32ConvertI64U | F32DemoteF64
+        | F64ConvertI32S | F64ConvertI32U | F64ConvertI64S | F64ConvertI64U | F64PromoteF32
+        | I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64
+        | I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S => (1, 1),
+
+        I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU | I32And | I32Or
+        | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr | I64Add | I64Sub | I64Mul
+        | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor | I64Shl | I64ShrS
+        | I64ShrU | I64Rotl | I64Rotr | F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max
+        | F32Copysign | F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => (2, 1),
+
+        I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU
+        | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU
+        | F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge | F64Eq | F64Ne | F64Lt | F64Gt | F64Le
+        | F64Ge => (2, 1),
+
+        _ => return None,
+    };
+    Some(arity)
+}
+
+/// Format the body of a function as folded S-expressions.
+/// Instructions and blocks that cannot be folded safely are written in the flat form,
+/// as is the whole body if the results of the function are not known.
+pub fn fmt_folded_expression(
+    expression: &Expression,
+    signature: Option<Signature>,
+    formatter: &mut Formatter,
+) {
+    let nodes = signature.and_then(|signature| {
+        fold(
+            &formatter.module,
+            &expression.instrs,
+            Vec::new(),
+            signature.results,
+        )
+    });
+    match nodes {
+        Some(nodes) => fmt_nodes(&nodes, formatter),
+        None => fmt_long_expression(expression, formatter),
+    }
+}
+
+/// Format the body of a function in the style it was written in,
+/// folding only the runs of instructions that were folded in the source.
+/// Falls back to the flat form if the runs do not match the body.
+pub fn fmt_preserved_expression(
+    expression: &Expression,
+    segments: Option<Vec<Segment>>,
+    signature: Option<Signature>,
+    formatter: &mut Formatter,
+) {
+    let segments = match segments {
+        Some(segments)
+            if segments.iter().map(|s| s.len).sum::<usize>() == expression.instrs.len() =>
+        {
+            segments
+        }
+        _ => return fmt_long_expression(expression, formatter),
+    };
+    let mut position = 0;
+    // The labels of the flat blocks enclosing the current segment.
+    let mut labels = Vec::new();
+    for segment in segments {
+        let instrs = &expression.instrs[position..position + segment.len];
+        position += segment.len;
+        let nodes = match signature {
+            Some(signature) if segment.folded => {
+                fold(&formatter.module, instrs, labels.clone(), signature.results)
+            }
+            _ => None,
+        };
+        for instruction in instrs {
+            match instruction {
+                Instruction::Block(bt) | Instruction::Loop(bt) | Instruction::If(bt) => {
+                    let signature = block_signature(&formatter.module, bt);
+                    labels.push(Label::new(instruction, bt, signature));
+                }
+                Instruction::End(..) => {
+                    labels.pop();
+                }
+                _ => {}
+            }
+        }
+        match nodes {
+            Some(nodes) => fmt_nodes(&nodes, formatter),
+            None => fmt_instructions(instrs, formatter),
+        }
+    }
+}
+
+/// Fold a sequence of instructions in a function returning `results` values,
+/// within the blocks whose labels are in `labels`.
+/// Returns `None` if the sequence cannot be folded safely.
+fn fold<'a, 'src>(
+    module: &ModuleContext,
+    instrs: &'a [Instruction<'src>],
+    labels: Vec<Label<'src>>,
+    results: usize,
+) -> Option<Vec<Node<'a, 'src>>> {
+    let mut folder = Folder {
+        module,
+        instrs,
+        position: 0,
+        labels,
+        results,
+    };
+    let nodes = folder.fold_sequence()?;
+    // The sequence must have ended at the end of the instructions.
+    if folder.position == instrs.len() {
+        Some(nodes)
+    } else {
+        None
+    }
+}
+
+fn fmt_nodes(nodes: &[Node], formatter: &mut Formatter) {
+    for node in nodes {
+        fmt_node(node, "", formatter);
+    }
+}
+
+/// Format the operands of a node that contains blocks on their own lines,
+/// writing `closing` at the end of the last line.
+fn fmt_operands(operands: &[Node], closing: &str, formatter: &mut Formatter) {
+    for (i, operand) in operands.iter().enumerate() {
+        let closing = if i + 1 == operands.len() { closing } else { "" };
+        if operand.is_block() {
+            fmt_node(operand, closing, formatter);
+        } else {
+            formatter.start_line();
+            fmt_inline_node(operand, formatter);
+            formatter.write(closing);
+            formatter.end_line();
+        }
+    }
+}
+
+/// Format a node on its own line(s), writing `closing` at the end of the last line,
+/// so that the parentheses of nested nodes close together whether or not they fit on a line.
+fn fmt_node(node: &Node, closing: &str, formatter: &mut Formatter) {
+    match node {
+        Node::Plain { operands, .. } if !node.is_block() && !operands.is_empty() => {
+            formatter.start_line();
+            fmt_inline_node(node, formatter);
+            formatter.write(closing);
+            formatter.end_line();
+        }
+        Node::Plain {
+            instruction,
+            operands,
+            ..
+        } => {
+            formatter.start_line();
+            if operands.is_empty() {
+                formatter.fmt(*instruction);
+                formatter.write(closing);
+                formatter.end_line();
+            } else {
+                fmt_head(instruction, formatter);
+                formatter.end_line();
+                formatter.indent();
+                fmt_operands(operands, &format!("){}", closing), formatter);
+                formatter.deindent();
+            }
+        }
+        Node::Block {
+            instruction, body, ..
+        } => {
+            formatter.start_line();
+            fmt_head(instruction, formatter);
+            if body.is_empty() {
+                formatter.write(")");
+                formatter.write(closing);
+                formatter.end_line();
+            } else {
+                formatter.end_line();
+                formatter.indent();
+                fmt_nodes(body, formatter);
+                formatter.deindent();
+                formatter.write_line(&format!("){}", closing));
+            }
+        }
+        Node::If {
+            instruction,
+            condition,
+            then,
+            otherwise,
+            ..
+        } => {
+            formatter.start_line();
+            fmt_head(instruction, formatter);
+            formatter.end_line();
+            formatter.indent();
+            fmt_nodes(condition, formatter);
+            fmt_branch("(then", then, formatter);
+            if let Some(otherwise) = otherwise {
+                fmt_branch("(else", otherwise, formatter);
+            }
+            formatter.deindent();
+            formatter.write_line(&format!("){}", closing));
+        }
+        // Flat nodes produce no operands, so nothing is closed after them.
+        Node::Flat { instrs } => fmt_instructions(instrs, formatter),
+    }
+}
+
+fn fmt_branch(head: &str, nodes: &[Node], formatter: &mut Formatter) {
+    formatter.start_line();
+    formatter.write(head);
+    if nodes.is_empty() {
+        formatter.write(")");
+        formatter.end_line();
+    } else {
+        formatter.end_line();
+        formatter.indent();
+        fmt_nodes(nodes, formatter);
+        formatter.deindent();
+        formatter.write_line(")");
+    }
+}
+
+/// Format a node that contains no blocks within a single group,
+/// so that it is written on one line if it fits.
+/// Operands are always folded, even if they take their own operands from the stack.
+fn fmt_inline_node(node: &Node, formatter: &mut Formatter) {
+    if let Node::Plain {
+        instruction,
+        operands,
+        ..
+    } = node
+    {
+        if operands.is_empty() {
+            fmt_head(instruction, formatter);
+            formatter.write(")");
+            return;
+        }
+        formatter.indented_group();
+        fmt_head(instruction, formatter);
+        for operand in operands {
+            formatter.soft_break();
+            fmt_inline_node(operand, formatter);
+        }
+        formatter.end_group();
+        formatter.write(")");
+    }
+}
+
+/// Write the opening parenthesis, name and immediate arguments of an instruction.
+fn fmt_head(instruction: &Instruction, formatter: &mut Formatter) {
+    formatter.write("(");
+    if !instr_is_valid(instruction) {
+        return formatter.unsupported_instruction(instruction);
+    }
+    formatter.mark_instruction(instruction);
+    formatter.write(instr_name(instruction));
+    if let Some(args) = instr_args(instruction, &formatter.options) {
+        formatter.write(" ");
+        formatter.append(args);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fmt::fmt;
+    use crate::options::{ExpressionStyle, Options};
+    use pretty_assertions::assert_eq;
+
+    fn options() -> Options {
+        Options {
+            expression_style: ExpressionStyle::Folded,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn folded_should_keep_only_blocks_with_params_flat() {
+        let source = "(module
+  (func (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    block (param i32 i32) (result i32)
+      i32.add
+    end
+    i32.const 2
+    i32.mul))";
+        let expected = "(module
+\t(func (param i32) (result i32)
+\t\t(local.get 0)
+\t\t(i32.const 1)
+\t\tblock (param i32 i32) (result i32)
+\t\t\ti32.add
+\t\tend
+\t\t(i32.mul (i32.const 2))
+\t)
+)
+";
+        assert_eq!(fmt(source, options()), expected);
+    }
+
+    #[test]
+    fn folded_should_take_multiple_results_from_the_stack() {
+        let source = "(module
+  (func $pair (result i32 i32)
+    i32.const 1
+    i32.const 2)
+  (func (result i32)
+    call $pair
+    i32.add
+    i32.const 3
+    i32.add))";
+        let expected = "(module
+\t(func $pair (result i32 i32)
+\t\t(i32.const 1)
+\t\t(i32.const 2)
+\t)
+\t(func (result i32)
+\t\t(call $pair)
+\t\t(i32.add (i32.add) (i32.const 3))
+\t)
+)
+";
+        assert_eq!(fmt(source, options()), expected);
+    }
+
+    #[test]
+    fn folded_should_close_operands_with_blocks_on_their_last_line() {
+        let source = "(module
+  (func (result i32)
+    block (result i32)
+      i32.const 1
+    end
+    i32.const 2
+    i32.add))";
+        let expected = "(module
+\t(func (result i32)
+\t\t(i32.add
+\t\t\t(block (result i32)
+\t\t\t\t(i32.const 1)
+\t\t\t)
+\t\t\t(i32.const 2))
+\t)
+)
+";
+        assert_eq!(fmt(source, options()), expected);
+    }
+}
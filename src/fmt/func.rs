@@ -1,5 +1,8 @@
+use super::fold::{fmt_folded_expression, fmt_preserved_expression};
+use super::shape::body_segments;
 use super::utils::{fmt_long_expression, id_is_gensym, inline_export_is_empty, ty_use_is_empty};
 use super::{Fmt, Formatter};
+use crate::options::ExpressionStyle;
 use wast::core::{Func, FuncKind, FunctionType, Local, TypeUse, ValType};
 use wast::kw;
 use wast::token::{Id, ItemRef, NameAnnotation};
@@ -7,6 +10,7 @@ use wast::token::{Id, ItemRef, NameAnnotation};
 impl<'src> Fmt for &Func<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         formatter.start_line();
+        formatter.group();
         formatter.write("(func");
         if let Some(id) = &self.id {
             if !id_is_gensym(id) {
@@ -15,23 +19,35 @@ impl<'src> Fmt for &Func<'src> {
             }
         }
         if !inline_export_is_empty(&self.exports) {
-            formatter.write(" ");
+            formatter.soft_break();
             formatter.fmt(&self.exports);
         }
         if let FuncKind::Import(inline_import) = &self.kind {
-            formatter.write(" ");
+            formatter.soft_break();
             formatter.fmt(inline_import);
         }
         if !ty_use_is_empty(&self.ty) {
-            formatter.write(" ");
+            formatter.soft_break();
             formatter.fmt(&self.ty);
         }
+        formatter.end_group();
         if let FuncKind::Inline { locals, expression } = &self.kind {
             if !locals.is_empty() || !expression.instrs.is_empty() {
                 formatter.end_line();
                 formatter.indent();
                 formatter.fmt(locals);
-                fmt_long_expression(expression, formatter);
+                match formatter.options.expression_style {
+                    ExpressionStyle::Flat => fmt_long_expression(expression, formatter),
+                    ExpressionStyle::Folded => {
+                        let signature = formatter.module.type_use_signature(&self.ty);
+                        fmt_folded_expression(expression, signature, formatter);
+                    }
+                    ExpressionStyle::Preserve => {
+                        let signature = formatter.module.type_use_signature(&self.ty);
+                        let segments = body_segments(&formatter.source, self.span.offset());
+                        fmt_preserved_expression(expression, segments, signature, formatter);
+                    }
+                }
                 formatter.deindent();
                 formatter.start_line();
             }
@@ -43,6 +59,7 @@ impl<'src> Fmt for &Func<'src> {
 
 impl<'src> Fmt for &TypeUse<'src, FunctionType<'src>> {
     fn fmt(&self, formatter: &mut Formatter) {
+        formatter.group();
         if let Some(index) = &self.index {
             formatter.write("(type ");
             formatter.fmt(index);
@@ -52,17 +69,18 @@ impl<'src> Fmt for &TypeUse<'src, FunctionType<'src>> {
         if let Some(functy) = &self.inline {
             if !functy.params.is_empty() {
                 if self.index.is_some() {
-                    formatter.write(" ");
+                    formatter.soft_break();
                 }
                 formatter.fmt(&*functy.params);
             }
             if !functy.results.is_empty() {
                 if self.index.is_some() || !functy.params.is_empty() {
-                    formatter.write(" ");
+                    formatter.soft_break();
                 }
                 formatter.fmt(&*functy.results);
             }
         };
+        formatter.end_group();
     }
 }
 
@@ -84,11 +102,17 @@ impl<'src> Fmt for &[Param<'src>] {
     fn fmt(&self, formatter: &mut Formatter) {
         if !self.is_empty() {
             if params_can_be_abbreviated(self) {
-                formatter.write("(param");
-                for param in self.iter() {
-                    formatter.write(" ");
+                formatter.write("(param ");
+                formatter.fill();
+                let mut params = self.iter();
+                if let Some(param) = params.next() {
                     formatter.fmt(&param.2);
                 }
+                for param in params {
+                    formatter.soft_break();
+                    formatter.fmt(&param.2);
+                }
+                formatter.end_group();
                 formatter.write(")");
             } else {
                 let mut params = self.iter();
@@ -102,7 +126,8 @@ impl<'src> Fmt for &[Param<'src>] {
                     formatter.write(")");
                 }
                 for (id, _, ty) in params {
-                    formatter.write(" (param ");
+                    formatter.soft_break();
+                    formatter.write("(param ");
                     if let Some(id) = id {
                         formatter.fmt(id);
                         formatter.write(" ");
@@ -143,8 +168,8 @@ impl<'src> Fmt for &ValType<'src> {
             ValType::I64 => formatter.write("i64"),
             ValType::F32 => formatter.write("f32"),
             ValType::F64 => formatter.write("f64"),
-            ValType::V128 => unimplemented!(),
-            ValType::Ref(..) => unimplemented!(),
+            ValType::V128 => formatter.unsupported("the `v128` type"),
+            ValType::Ref(..) => formatter.unsupported("reference types in this position"),
         };
     }
 }
@@ -5,6 +5,7 @@ use wast::core::{Global, GlobalKind, GlobalType};
 impl<'src> Fmt for &Global<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         formatter.start_line();
+        formatter.group();
         formatter.write("(global ");
         if let Some(id) = &self.id {
             if !id_is_gensym(id) {
@@ -15,11 +16,12 @@ impl<'src> Fmt for &Global<'src> {
 
         if !self.exports.names.is_empty() {
             formatter.fmt(&self.exports);
-            formatter.write(" ");
+            formatter.soft_break();
         };
 
         if let GlobalKind::Import(inline_import) = &self.kind {
             formatter.fmt(inline_import);
+            formatter.soft_break();
         };
 
         formatter.fmt(&self.ty);
@@ -27,6 +29,7 @@ impl<'src> Fmt for &Global<'src> {
             formatter.write(" ");
             formatter.fmt(expression);
         };
+        formatter.end_group();
         formatter.write(")");
         formatter.end_line();
     }
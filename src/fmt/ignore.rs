@@ -0,0 +1,321 @@
+use super::range::{field_origins, field_spans};
+use super::scan::{Extent, Scan};
+use crate::error::Error;
+use std::ops::Range;
+use wast::core::ModuleKind;
+use wast::lexer::Token;
+
+/// The comment that excludes the next module field from formatting.
+const IGNORE: &str = "wasmfmt-ignore";
+/// The comment that starts a region excluded from formatting.
+const IGNORE_START: &str = "wasmfmt-ignore-start";
+/// The comment that ends a region excluded from formatting.
+const IGNORE_END: &str = "wasmfmt-ignore-end";
+/// The annotation that excludes the field it precedes or appears in from formatting,
+/// as in `(@wasmfmt skip)`.
+const ANNOTATION: &str = "@wasmfmt";
+
+/// How a module field is treated by the directives in the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Directive {
+    /// Format the field.
+    Format,
+    /// Write this source text as it is, in place of the field
+    /// and any other fields in the same ignored region.
+    Verbatim(String),
+    /// The field is part of an ignored region that was written already.
+    Skip,
+}
+
+/// The regions of the source that are excluded from formatting.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Directives {
+    /// The indices of the source fields in each region, and the source text of the region.
+    regions: Vec<(Range<usize>, String)>,
+    /// The byte range of each source field.
+    spans: Vec<Range<usize>>,
+    /// The index of the source field that each module field was parsed from,
+    /// once the fields may have been added, removed or moved.
+    origins: Option<Vec<Option<usize>>>,
+}
+
+/// A directive found in the source.
+enum Marker {
+    /// Ignore the next field, starting the region at the given offset.
+    Next(usize),
+    /// Ignore the field containing the given offset.
+    Within(usize),
+    /// Start a region at the given offset.
+    Start(usize),
+    /// End a region at the given offset.
+    End(usize),
+}
+
+impl Directives {
+    /// The directives of `source`, whose module `kind` has yet to be prepared.
+    pub(crate) fn new(source: &str, kind: &ModuleKind) -> Self {
+        let count = match kind {
+            ModuleKind::Text(fields) => fields.len(),
+            ModuleKind::Binary(..) => return Directives::default(),
+        };
+        if !source.contains("wasmfmt") {
+            return Directives::default();
+        }
+        match field_spans(source).filter(|spans| spans.len() == count) {
+            Some(spans) => Self::scan(source, &spans)
+                .map(|directives| Directives {
+                    spans,
+                    ..directives
+                })
+                .unwrap_or_default(),
+            None => Directives::default(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Apply the directives to the fields of the prepared module `kind`,
+    /// which may have been added, removed or moved.
+    ///
+    /// Returns an error if every field of an ignored region was removed,
+    /// since the region would then be lost.
+    pub(crate) fn follow(&mut self, kind: &ModuleKind) -> Result<(), Error> {
+        let fields = match kind {
+            ModuleKind::Text(fields) if !self.is_empty() => fields,
+            _ => return Ok(()),
+        };
+        let origins = field_origins(&self.spans, fields);
+        for (region, _) in &self.regions {
+            if !origins
+                .iter()
+                .flatten()
+                .any(|origin| region.contains(origin))
+            {
+                return Err(Error::Unsupported(
+                    "`wasmfmt-ignore` directives on fields that the options remove".to_string(),
+                ));
+            }
+        }
+        self.origins = Some(origins);
+        Ok(())
+    }
+
+    /// How the field at `index` is treated.
+    pub(crate) fn get(&self, index: usize) -> Directive {
+        let origin = match &self.origins {
+            Some(origins) => match origins.get(index).copied().flatten() {
+                Some(origin) => origin,
+                None => return Directive::Format,
+            },
+            None => index,
+        };
+        for (fields, text) in &self.regions {
+            if !fields.contains(&origin) {
+                continue;
+            }
+            // The region is written in place of the first field that came from it.
+            let first = match &self.origins {
+                Some(origins) => origins
+                    .iter()
+                    .position(|origin| origin.is_some_and(|origin| fields.contains(&origin))),
+                None => Some(fields.start),
+            };
+            return match first == Some(index) {
+                true => Directive::Verbatim(text.clone()),
+                false => Directive::Skip,
+            };
+        }
+        Directive::Format
+    }
+
+    fn scan(source: &str, spans: &[Range<usize>]) -> Option<Self> {
+        let tokens = Scan::new(source, Extent::Trivia).complete()?;
+        let mut markers = Vec::new();
+        for (i, (token, offset)) in tokens.iter().enumerate() {
+            let comment = match token {
+                Token::LineComment(text) => text.trim_start_matches(';'),
+                Token::BlockComment(text) => text.trim_start_matches("(;").trim_end_matches(";)"),
+                Token::Reserved(ANNOTATION) => {
+                    let is_skip = tokens[i + 1..]
+                        .iter()
+                        .find(|(token, _)| !matches!(token, Token::Whitespace(..)))
+                        .is_some_and(|(token, _)| matches!(token, Token::Keyword("skip")));
+                    // The annotation starts at the preceding parenthesis.
+                    if is_skip && *offset > 0 {
+                        let start = offset - 1;
+                        if spans.iter().any(|span| span.contains(&start)) {
+                            markers.push(Marker::Within(start));
+                        } else {
+                            markers.push(Marker::Next(start));
+                        }
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            // Directives within fields are not supported.
+            if spans.iter().any(|span| span.contains(offset)) {
+                continue;
+            }
+            match comment.trim() {
+                IGNORE => markers.push(Marker::Next(*offset)),
+                IGNORE_START => markers.push(Marker::Start(*offset)),
+                IGNORE_END => markers.push(Marker::End(offset + token.src().len())),
+                _ => {}
+            }
+        }
+
+        let mut directives = Directives::default();
+        let mut start = None;
+        for marker in markers {
+            match marker {
+                Marker::Next(offset) if start.is_none() => {
+                    if let Some(index) = spans.iter().position(|span| span.start > offset) {
+                        directives.push(source, index..index + 1, offset..spans[index].end);
+                    }
+                }
+                Marker::Within(offset) if start.is_none() => {
+                    if let Some(index) = spans.iter().position(|span| span.contains(&offset)) {
+                        directives.push(source, index..index + 1, spans[index].clone());
+                    }
+                }
+                Marker::Start(offset) if start.is_none() => start = Some(offset),
+                Marker::End(end) => {
+                    if let Some(start) = start.take() {
+                        directives.push_region(source, spans, start..end);
+                    }
+                }
+                _ => {}
+            }
+        }
+        // A region that is not ended extends to the last field.
+        if let (Some(start), Some(last)) = (start, spans.last()) {
+            directives.push_region(source, spans, start..last.end);
+        }
+        Some(directives)
+    }
+
+    fn push_region(&mut self, source: &str, spans: &[Range<usize>], bytes: Range<usize>) {
+        let first = spans.iter().position(|span| span.start >= bytes.start);
+        let last = spans.iter().rposition(|span| span.end <= bytes.end);
+        if let (Some(first), Some(last)) = (first, last) {
+            if first <= last {
+                self.push(source, first..last + 1, bytes);
+            }
+        }
+    }
+
+    fn push(&mut self, source: &str, fields: Range<usize>, bytes: Range<usize>) {
+        // A field that is part of an earlier region is not written twice.
+        if self
+            .regions
+            .iter()
+            .any(|(region, _)| region.contains(&fields.start))
+        {
+            return;
+        }
+        self.regions.push((fields, source[bytes].to_string()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Error;
+    use crate::fmt::{fmt, try_fmt};
+    use crate::options::Options;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn ignore_should_keep_the_next_field() {
+        let source = "(module
+  ;; wasmfmt-ignore
+  (global $a i32   (i32.const 1))
+  (global $b i32   (i32.const 2)))";
+        let expected = "(module
+\t;; wasmfmt-ignore
+  (global $a i32   (i32.const 1))
+\t(global $b i32 (i32.const 2))
+)
+";
+        assert_eq!(fmt(source, Options::default()), expected);
+        assert_eq!(fmt(expected, Options::default()), expected);
+    }
+
+    #[test]
+    fn ignore_should_keep_regions() {
+        let source = "(module
+  (global $a i32   (i32.const 1))
+  ;; wasmfmt-ignore-start
+  (global $b i32   (i32.const 2))
+
+  (global $c i32   (i32.const 3))
+  ;; wasmfmt-ignore-end
+  (global $d i32   (i32.const 4)))";
+        let expected = "(module
+\t(global $a i32 (i32.const 1))
+\t;; wasmfmt-ignore-start
+  (global $b i32   (i32.const 2))
+
+  (global $c i32   (i32.const 3))
+  ;; wasmfmt-ignore-end
+\t(global $d i32 (i32.const 4))
+)
+";
+        assert_eq!(fmt(source, Options::default()), expected);
+    }
+
+    #[test]
+    fn ignore_should_honor_annotations() {
+        let source = "(module
+  (@wasmfmt skip) (global $a i32   (i32.const 1))
+  (global $b (@wasmfmt skip) i32   (i32.const 2))
+  (global $c i32   (i32.const 3)))";
+        let expected = "(module
+\t(@wasmfmt skip) (global $a i32   (i32.const 1))
+\t(global $b (@wasmfmt skip) i32   (i32.const 2))
+\t(global $c i32 (i32.const 3))
+)
+";
+        assert_eq!(fmt(source, Options::default()), expected);
+    }
+
+    #[test]
+    fn ignore_should_keep_fields_that_options_change() {
+        let source = "(module
+  ;; wasmfmt-ignore
+  (func $keep (param i32)   nop)
+  (func $f (param i32) (local.get 0) (drop)))";
+        let options = Options {
+            resolve_names: true,
+            ..Options::default()
+        };
+        let expected = "(module
+\t;; wasmfmt-ignore
+  (func $keep (param i32)   nop)
+\t(func $f (type 0) (param i32)
+\t\t(local.get 0)
+\t\tdrop
+\t)
+\t(type (func (param i32)))
+)
+";
+        assert_eq!(fmt(source, options), expected);
+    }
+
+    #[test]
+    fn ignore_should_reject_minified_output() {
+        let source = "(module
+  ;; wasmfmt-ignore
+  (global $a i32   (i32.const 1)))";
+        let options = Options {
+            minify: true,
+            ..Options::default()
+        };
+        assert!(matches!(
+            try_fmt(source, options),
+            Err(Error::Unsupported(..))
+        ));
+    }
+}
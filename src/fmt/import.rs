@@ -1,4 +1,4 @@
-use super::utils::id_is_gensym;
+use super::utils::{id_is_gensym, ty_use_is_empty};
 use super::{Fmt, Formatter};
 use wast::core::{Import, InlineImport, ItemKind, ItemSig};
 
@@ -21,14 +21,17 @@ impl<'src> Fmt for &ItemSig<'src> {
         // TODO: This should be an impl on `ItemKind`
         match &self.kind {
             ItemKind::Func(ty_use) => {
-                formatter.write("(func ");
+                formatter.write("(func");
                 if let Some(id) = &self.id {
                     if !id_is_gensym(id) {
-                        formatter.fmt(id);
                         formatter.write(" ");
+                        formatter.fmt(id);
                     }
                 }
-                formatter.fmt(ty_use);
+                if !ty_use_is_empty(ty_use) {
+                    formatter.write(" ");
+                    formatter.fmt(ty_use);
+                }
                 formatter.write(")");
             }
             ItemKind::Table(table_ty) => {
@@ -64,7 +67,7 @@ impl<'src> Fmt for &ItemSig<'src> {
                 formatter.fmt(global_ty);
                 formatter.write(")");
             }
-            ItemKind::Tag(..) => unimplemented!(),
+            ItemKind::Tag(..) => formatter.unsupported("tag imports"),
         }
     }
 }
@@ -1,41 +1,52 @@
-use super::utils::{bt_is_empty, instr_is_block_start, memory_arg_is_valid, ty_use_is_empty};
+use super::utils::{
+    bt_is_empty, instr_is_block_end, instr_is_block_start, memory_arg_is_valid, ty_use_is_empty,
+};
 use super::{Fmt, Formatter};
+use crate::options::Options;
 use wast::core::{BlockType, BrTableIndices, Instruction, MemArg};
 
 impl<'src> Fmt for &Instruction<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         if !instr_is_valid(self) {
-            unimplemented!();
+            return formatter.unsupported_instruction(self);
         }
         let name = instr_name(self);
-        let args = instr_args(self);
+        let args = instr_args(self, &formatter.options);
         if let Some(args) = args {
-            if instr_is_block_start(self) {
+            if instr_is_block_start(self) || instr_is_block_end(self) {
+                formatter.mark_instruction(self);
                 formatter.write(name);
                 formatter.write(" ");
-                formatter.write(&args);
+                formatter.append(args);
             } else {
                 formatter.write("(");
+                formatter.mark_instruction(self);
                 formatter.write(name);
                 formatter.write(" ");
-                formatter.write(&args);
+                formatter.append(args);
                 formatter.write(")");
             }
         } else {
+            formatter.mark_instruction(self);
             formatter.write(name);
         }
     }
 }
 
-fn instr_is_valid(instruction: &Instruction) -> bool {
+pub fn instr_is_valid(instruction: &Instruction) -> bool {
+    if known_instr_name(instruction).is_none() {
+        return false;
+    }
     match instruction {
         Instruction::MemorySize(arg) | Instruction::MemoryGrow(arg) => memory_arg_is_valid(arg),
+        // The types of a typed `select` are not written yet.
+        Instruction::Select(types) => types.tys.is_none(),
         _ => true,
     }
 }
 
-fn instr_args(instruction: &Instruction) -> Option<String> {
-    let mut formatter = Formatter::new();
+pub fn instr_args(instruction: &Instruction, options: &Options) -> Option<Formatter> {
+    let mut formatter = Formatter::with_options(options);
     match instruction {
         Instruction::I32Const(n) => {
             formatter.fmt(*n);
@@ -74,6 +85,9 @@ fn instr_args(instruction: &Instruction) -> Option<String> {
             }
             formatter.fmt(bt);
         }
+        Instruction::Else(Some(label)) | Instruction::End(Some(label)) => {
+            formatter.fmt(label);
+        }
         Instruction::I32Load8s(memarg)
         | Instruction::I32Load8u(memarg)
         | Instruction::I64Load8s(memarg)
@@ -123,11 +137,15 @@ fn instr_args(instruction: &Instruction) -> Option<String> {
         }
         _ => return None,
     };
-    Some(formatter.into())
+    Some(formatter)
 }
 
-fn instr_name(instruction: &Instruction) -> &'static str {
-    match instruction {
+pub fn instr_name(instruction: &Instruction) -> &'static str {
+    known_instr_name(instruction).expect("supported instruction")
+}
+
+fn known_instr_name(instruction: &Instruction) -> Option<&'static str> {
+    let name = match instruction {
         // Numeric instructions
         Instruction::I32Const(..) => "i32.const",
         Instruction::I64Const(..) => "i64.const",
@@ -244,7 +262,7 @@ fn instr_name(instruction: &Instruction) -> &'static str {
         Instruction::I32TruncF32S => "i32.trunc_f32_s",
         Instruction::I32TruncF32U => "i32.trunc_f32_u",
         Instruction::I32TruncF64S => "i32.trunc_f64_s",
-        Instruction::I32TruncF64U => "i32.trunc_f32_u",
+        Instruction::I32TruncF64U => "i32.trunc_f64_u",
         Instruction::I32TruncSatF32S => "i32.trunc_sat_f32_s",
         Instruction::I32TruncSatF32U => "i32.trunc_sat_f32_u",
         Instruction::I32TruncSatF64S => "i32.trunc_sat_f64_s",
@@ -252,7 +270,7 @@ fn instr_name(instruction: &Instruction) -> &'static str {
         Instruction::I64ExtendI32S => "i64.extend_i32_s",
         Instruction::I64ExtendI32U => "i64.extend_i32_u",
         Instruction::I64TruncF32S => "i64.trunc_f32_s",
-        Instruction::I64TruncF32U => "i64.trunc_f_32_u",
+        Instruction::I64TruncF32U => "i64.trunc_f32_u",
         Instruction::I64TruncF64S => "i64.trunc_f64_s",
         Instruction::I64TruncF64U => "i64.trunc_f64_u",
         Instruction::I64TruncSatF32S => "i64.trunc_sat_f32_s",
@@ -283,7 +301,7 @@ fn instr_name(instruction: &Instruction) -> &'static str {
 
         // Parametric instructions
         Instruction::Drop => "drop",
-        Instruction::Select(_types) => "select",
+        Instruction::Select(..) => "select",
 
         // Variable instructions
         Instruction::LocalGet(..) => "local.get",
@@ -331,11 +349,11 @@ fn instr_name(instruction: &Instruction) -> &'static str {
         Instruction::Block(..) => "block",
         Instruction::Loop(..) => "loop",
         Instruction::If(..) => "if",
-        // TODO: id
         Instruction::Else(..) => "else",
         Instruction::End(..) => "end",
-        _ => unimplemented!(),
-    }
+        _ => return None,
+    };
+    Some(name)
 }
 
 impl<'src> Fmt for &BlockType<'src> {
@@ -352,10 +370,13 @@ impl<'src> Fmt for &BlockType<'src> {
 
 impl<'src> Fmt for &BrTableIndices<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
+        formatter.fill();
         for label in &self.labels {
             formatter.fmt(label);
+            formatter.soft_break();
         }
         formatter.fmt(&self.default);
+        formatter.end_group();
     }
 }
 
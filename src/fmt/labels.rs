@@ -0,0 +1,271 @@
+use std::iter;
+use wast::core::{Expression, FuncKind, Instruction, ModuleField, ModuleKind};
+use wast::token::{Id, Index};
+
+/// Whether each instruction of `instrs` starts a block that is the target of a branch.
+pub(crate) fn branch_targets(instrs: &[Instruction]) -> Vec<bool> {
+    let mut targets = vec![false; instrs.len()];
+    // The position and label of the enclosing blocks, innermost last.
+    let mut blocks = Vec::new();
+    for (position, instruction) in instrs.iter().enumerate() {
+        match instruction {
+            Instruction::Block(bt)
+            | Instruction::Loop(bt)
+            | Instruction::If(bt)
+            | Instruction::Try(bt) => blocks.push((position, bt.label)),
+            Instruction::End(..) | Instruction::Delegate(..) => {
+                blocks.pop();
+            }
+            _ => {}
+        }
+        for index in branch_indices(instruction) {
+            let block = match index {
+                Index::Num(depth, ..) => blocks
+                    .len()
+                    .checked_sub(*depth as usize + 1)
+                    .map(|block| blocks[block].0),
+                Index::Id(id) => blocks
+                    .iter()
+                    .rev()
+                    .find(|(_, label)| label.map(|label| label.name()) == Some(id.name()))
+                    .map(|(block, _)| *block),
+            };
+            if let Some(block) = block {
+                targets[block] = true;
+            }
+        }
+    }
+    targets
+}
+
+/// Replace the labels that branches refer to with relative depths,
+/// and remove the labels of blocks.
+pub(crate) fn label_depths(kind: &mut ModuleKind) {
+    for expression in func_bodies(kind) {
+        let mut labels: Vec<Option<Id>> = Vec::new();
+        for instruction in expression.instrs.iter_mut() {
+            match instruction {
+                Instruction::Block(bt)
+                | Instruction::Loop(bt)
+                | Instruction::If(bt)
+                | Instruction::Try(bt) => {
+                    labels.push(bt.label.take());
+                    bt.label_name = None;
+                }
+                Instruction::Else(id) => *id = None,
+                Instruction::End(id) => {
+                    labels.pop();
+                    *id = None;
+                }
+                // A `delegate` ends its `try`, and its label is counted from the enclosing block.
+                Instruction::Delegate(..) => {
+                    labels.pop();
+                }
+                _ => {}
+            }
+            for index in branch_indices_mut(instruction) {
+                if let Index::Id(id) = index {
+                    let depth = labels
+                        .iter()
+                        .rev()
+                        .position(|label| label.map(|label| label.name()) == Some(id.name()));
+                    if let Some(depth) = depth {
+                        *index = Index::Num(depth as u32, id.span());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Follow every `else` and `end` with the label of its block, if the block has one.
+pub(crate) fn label_ends(kind: &mut ModuleKind) {
+    for expression in func_bodies(kind) {
+        let mut labels = Vec::new();
+        for instruction in expression.instrs.iter_mut() {
+            match instruction {
+                Instruction::Block(bt)
+                | Instruction::Loop(bt)
+                | Instruction::If(bt)
+                | Instruction::Try(bt) => labels.push(bt.label),
+                Instruction::Else(id) => {
+                    if let Some(Some(label)) = labels.last() {
+                        *id = Some(*label);
+                    }
+                }
+                Instruction::End(id) => {
+                    if let Some(Some(label)) = labels.pop() {
+                        *id = Some(label);
+                    }
+                }
+                Instruction::Delegate(..) => {
+                    labels.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn func_bodies<'a, 'src>(kind: &'a mut ModuleKind<'src>) -> Vec<&'a mut Expression<'src>> {
+    let fields = match kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return Vec::new(),
+    };
+    fields
+        .iter_mut()
+        .filter_map(|field| match field {
+            ModuleField::Func(func) => match &mut func.kind {
+                FuncKind::Inline { expression, .. } => Some(expression),
+                FuncKind::Import(..) => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// The labels that a branch instruction refers to.
+fn branch_indices<'a, 'src>(instruction: &'a Instruction<'src>) -> Vec<&'a Index<'src>> {
+    match instruction {
+        Instruction::Br(index)
+        | Instruction::BrIf(index)
+        | Instruction::BrOnNull(index)
+        | Instruction::BrOnNonNull(index)
+        | Instruction::Delegate(index) => vec![index],
+        Instruction::BrTable(indices) => indices
+            .labels
+            .iter()
+            .chain(iter::once(&indices.default))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn branch_indices_mut<'a, 'src>(
+    instruction: &'a mut Instruction<'src>,
+) -> Vec<&'a mut Index<'src>> {
+    match instruction {
+        Instruction::Br(index)
+        | Instruction::BrIf(index)
+        | Instruction::BrOnNull(index)
+        | Instruction::BrOnNonNull(index)
+        | Instruction::Delegate(index) => vec![index],
+        Instruction::BrTable(indices) => indices
+            .labels
+            .iter_mut()
+            .chain(iter::once(&mut indices.default))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::label_depths;
+    use crate::fmt::fmt;
+    use crate::options::{BranchStyle, Options};
+    use pretty_assertions::assert_eq;
+    use wast::core::{Func, FuncKind, Instruction, ModuleField, ModuleKind};
+    use wast::parser::{parse, ParseBuffer};
+    use wast::token::Index;
+    use wast::Wat;
+
+    const SOURCE: &str = "(module
+  (func (param i32)
+    (block
+      (block
+        (loop
+          (br_table 0 1 2 (local.get 0))))
+      (if (local.get 0) (then (br 1)) (else nop)))))";
+
+    #[test]
+    fn labels_should_name_branch_targets() {
+        let options = Options {
+            branch_style: BranchStyle::Labels,
+            label_ends: true,
+            ..Options::default()
+        };
+        let expected = "(module
+\t(func (param i32)
+\t\tblock $B0
+\t\t\tblock $B1
+\t\t\t\tloop $L2
+\t\t\t\t\t(local.get 0)
+\t\t\t\t\t(br_table $L2 $B1 $B0)
+\t\t\t\tend $L2
+\t\t\tend $B1
+\t\t\t(local.get 0)
+\t\t\tif
+\t\t\t\t(br $B0)
+\t\t\telse
+\t\t\t\tnop
+\t\t\tend
+\t\tend $B0
+\t)
+)
+";
+        assert_eq!(fmt(SOURCE, options.clone()), expected);
+        assert_eq!(fmt(expected, options), expected);
+    }
+
+    #[test]
+    fn depths_should_remove_labels() {
+        let source = "(module
+  (func
+    (block $outer
+      (loop $inner
+        (br_if $outer (i32.const 1))
+        (br $inner)))))";
+        let options = Options {
+            branch_style: BranchStyle::Depths,
+            ..Options::default()
+        };
+        let expected = "(module
+\t(func
+\t\tblock
+\t\t\tloop
+\t\t\t\t(i32.const 1)
+\t\t\t\t(br_if 1)
+\t\t\t\t(br 0)
+\t\t\tend
+\t\tend
+\t)
+)
+";
+        assert_eq!(fmt(source, options), expected);
+    }
+
+    #[test]
+    fn label_depths_should_end_try_blocks_at_delegate() {
+        let source = "(module
+  (func
+    (block $outer
+      (try $t (do nop) (delegate $outer))
+      (br $outer))))";
+        let buffer = ParseBuffer::new(source).unwrap();
+        let mut module = match parse::<Wat>(&buffer).unwrap() {
+            Wat::Module(module) => module,
+            Wat::Component(..) => unreachable!("module"),
+        };
+        label_depths(&mut module.kind);
+        let instrs = match &module.kind {
+            ModuleKind::Text(fields) => match &fields[0] {
+                ModuleField::Func(Func {
+                    kind: FuncKind::Inline { expression, .. },
+                    ..
+                }) => &expression.instrs,
+                _ => unreachable!("function"),
+            },
+            ModuleKind::Binary(..) => unreachable!("text module"),
+        };
+        let depths = instrs
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Delegate(Index::Num(depth, ..))
+                | Instruction::Br(Index::Num(depth, ..)) => Some(*depth),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [0, 0]);
+    }
+}
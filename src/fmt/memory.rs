@@ -5,6 +5,7 @@ use wast::core::{Data, DataKind, DataVal, Memory, MemoryKind, MemoryType};
 impl<'src> Fmt for &Memory<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         formatter.start_line();
+        formatter.group();
         formatter.write("(memory ");
         if let Some(id) = &self.id {
             if !id_is_gensym(id) {
@@ -14,9 +15,10 @@ impl<'src> Fmt for &Memory<'src> {
         };
         if !self.exports.names.is_empty() {
             formatter.fmt(&self.exports);
-            formatter.write(" ");
+            formatter.soft_break();
         };
         formatter.fmt(&self.kind);
+        formatter.end_group();
         formatter.write(")");
         formatter.end_line();
     }
@@ -43,9 +45,7 @@ impl<'src> Fmt for &MemoryKind<'src> {
             MemoryKind::Inline {
                 is_32: false,
                 data: _,
-            } => {
-                unimplemented!()
-            }
+            } => formatter.unsupported("64-bit memories"),
         }
     }
 }
@@ -66,13 +66,13 @@ impl<'src> Fmt for &Vec<DataVal<'src>> {
 impl<'src> Fmt for &DataVal<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         match self {
-            DataVal::String(bytes) => {
-                let string = std::str::from_utf8(bytes).expect("valid utf8");
-                formatter.fmt(string);
-            }
+            DataVal::String(bytes) => match std::str::from_utf8(bytes) {
+                Ok(string) => formatter.fmt(string),
+                Err(..) => formatter.unsupported("data strings that are not valid UTF-8"),
+            },
             DataVal::Integral(..) => {
                 // https://github.com/WebAssembly/wat-numeric-values
-                unimplemented!();
+                formatter.unsupported("numeric data values");
             }
         }
     }
@@ -122,7 +122,7 @@ impl<'src> Fmt for &Data<'src> {
 impl<'src> Fmt for &DataKind<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         match self {
-            DataKind::Passive => todo!(),
+            DataKind::Passive => formatter.unsupported("passive data segments"),
             DataKind::Active { memory, offset } => {
                 if !index_is_default(memory) {
                     formatter.fmt(memory);
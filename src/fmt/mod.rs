@@ -1,28 +1,73 @@
+pub(crate) mod context;
+pub(crate) mod dedupe;
+pub(crate) mod doc;
 pub(crate) mod export;
 pub(crate) mod expression;
+pub(crate) mod fallback;
+pub(crate) mod fold;
 pub(crate) mod func;
 pub(crate) mod global;
+pub(crate) mod ignore;
 pub(crate) mod import;
 pub(crate) mod index;
 pub(crate) mod instruction;
+pub(crate) mod labels;
 pub(crate) mod memory;
 pub(crate) mod module;
+pub(crate) mod names;
+pub(crate) mod order;
+pub(crate) mod placement;
 pub(crate) mod primitives;
+pub(crate) mod range;
+pub(crate) mod recover;
+pub(crate) mod scan;
+pub(crate) mod shape;
+pub(crate) mod source_map;
 pub(crate) mod start;
 pub(crate) mod table;
 pub(crate) mod r#type;
+pub(crate) mod type_uses;
 pub(crate) mod utils;
 
+use crate::error::Error;
+use crate::options::{BranchStyle, ExpressionStyle, Options};
+use context::ModuleContext;
+use doc::{Breaks, Doc};
+pub use fallback::{fmt_best_effort, Fallback};
+use fallback::{Fallbacks, Mode};
+use ignore::Directives;
+use names::Naming;
+pub use range::fmt_range;
+pub use recover::{fmt_recover, Diagnostic};
+use source_map::Anchors;
+pub use source_map::{fmt_with_source_map, SourceMap};
 use wast::{
+    core::{Instruction, Module, ModuleField, ModuleKind},
     parser::{parse, ParseBuffer},
     Wat,
 };
 
 /// A formatter used to format individual AST nodes.
+///
+/// Nodes are written into a document of text, groups and soft line breaks.
+/// The document is laid out when the formatter is converted into a `String`,
+/// at which point groups that do not fit within the maximum width are broken.
 #[derive(Default)]
 pub struct Formatter {
-    buffer: String,
-    indentation: usize,
+    docs: Vec<Doc>,
+    options: Options,
+    module: ModuleContext,
+    source: String,
+    /// A description of the first construct that could not be formatted.
+    unsupported: Option<String>,
+    /// The source offsets of the nodes to record in a source map.
+    anchors: Anchors,
+    /// The module fields that are excluded from formatting.
+    directives: Directives,
+    /// The source text of the module fields, if fields that cannot be formatted are copied.
+    fallbacks: Option<Fallbacks>,
+    /// Options that could not be applied, and why.
+    warnings: Vec<String>,
 }
 
 impl Formatter {
@@ -31,24 +76,165 @@ impl Formatter {
         Self::default()
     }
 
+    /// Construct a new formatter that lays out its output according to `options`.
+    pub fn with_options(options: &Options) -> Self {
+        Self {
+            docs: Vec::new(),
+            options: options.clone(),
+            module: ModuleContext::default(),
+            source: String::new(),
+            unsupported: None,
+            anchors: Anchors::default(),
+            directives: Directives::default(),
+            fallbacks: None,
+            warnings: Vec::new(),
+        }
+    }
+
     fn indent(&mut self) {
-        self.indentation += 1;
+        self.docs.push(Doc::Indent);
     }
 
     fn deindent(&mut self) {
-        self.indentation -= 1;
+        self.docs.push(Doc::Dedent);
     }
 
     fn start_line(&mut self) {
-        self.buffer.push_str(&"\t".repeat(self.indentation));
+        self.docs.push(Doc::Indentation);
     }
 
     fn end_line(&mut self) {
-        self.buffer.push('\n');
+        self.docs.push(Doc::Newline);
     }
 
     fn write(&mut self, string: &str) {
-        self.buffer.push_str(string);
+        if let Some(Doc::Text(text)) = self.docs.last_mut() {
+            text.push_str(string);
+        } else {
+            self.docs.push(Doc::Text(string.to_string()));
+        }
+    }
+
+    /// Start a group whose breaks are either all spaces or all line breaks.
+    fn group(&mut self) {
+        self.docs.push(Doc::Begin {
+            breaks: Breaks::Consistent,
+            indent: false,
+        });
+    }
+
+    /// Start a group like `group`, whose line breaks are always indented
+    /// relative to the line on which it starts.
+    fn indented_group(&mut self) {
+        self.docs.push(Doc::Begin {
+            breaks: Breaks::Consistent,
+            indent: true,
+        });
+    }
+
+    /// Start a group whose breaks are line breaks only where needed.
+    fn fill(&mut self) {
+        self.docs.push(Doc::Begin {
+            breaks: Breaks::Fill,
+            indent: false,
+        });
+    }
+
+    fn end_group(&mut self) {
+        self.docs.push(Doc::End);
+    }
+
+    /// Write a space, or a line break if the enclosing group does not fit.
+    fn soft_break(&mut self) {
+        self.docs.push(Doc::Break);
+    }
+
+    /// Record that the next text written is the module field at `index`.
+    fn mark_field(&mut self, index: usize) {
+        if let Some(offset) = self.anchors.field(index) {
+            self.docs.push(Doc::Mark(offset));
+        }
+    }
+
+    /// Record that the next text written is the name of `instruction`.
+    fn mark_instruction(&mut self, instruction: &Instruction) {
+        if let Some(offset) = self.anchors.instruction(instruction) {
+            self.docs.push(Doc::Mark(offset));
+        }
+    }
+
+    /// If the module field at `index` could not be formatted and fields are copied,
+    /// replace everything written since `start` with the source text of the field.
+    fn fall_back(&mut self, index: usize, field: &ModuleField, start: usize) {
+        let fallbacks = match &mut self.fallbacks {
+            Some(fallbacks) => fallbacks,
+            None => return,
+        };
+        let construct = match self.unsupported.take() {
+            Some(construct) => construct,
+            None => return fallbacks.keep(index),
+        };
+        match fallbacks.fall_back(index, field, construct) {
+            Ok(text) => {
+                self.docs.truncate(start);
+                self.write_verbatim(&text);
+            }
+            Err(construct) => self.unsupported = Some(construct),
+        }
+    }
+
+    /// Record that the input contains a construct that cannot be formatted.
+    fn unsupported(&mut self, construct: &str) {
+        if self.unsupported.is_none() {
+            self.unsupported = Some(construct.to_string());
+        }
+    }
+
+    /// Record that an option could not be applied.
+    fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Record that `instruction` cannot be formatted,
+    /// or write its source text in place of its name and immediates if instructions are copied.
+    fn unsupported_instruction(&mut self, instruction: &Instruction) {
+        let debug = format!("{:?}", instruction);
+        let name = debug.split(|c: char| !c.is_alphanumeric()).next();
+        let construct = format!("the `{}` instruction", name.unwrap_or_default());
+        let text = self
+            .fallbacks
+            .as_mut()
+            .and_then(|fallbacks| fallbacks.fall_back_instruction(instruction, &construct));
+        match text {
+            Some(text) => self.write(&text),
+            None => self.unsupported(&construct),
+        }
+    }
+
+    /// Append everything written to `other`.
+    fn append(&mut self, other: Formatter) {
+        if let Some(construct) = other.unsupported {
+            self.unsupported(&construct);
+        }
+        for doc in other.docs {
+            match doc {
+                Doc::Text(text) => self.write(&text),
+                doc => self.docs.push(doc),
+            }
+        }
+    }
+
+    /// Write source text as it is on lines of its own.
+    /// Only the indentation of the first line is replaced.
+    fn write_verbatim(&mut self, text: &str) {
+        self.start_line();
+        for (i, line) in text.lines().enumerate() {
+            if i > 0 {
+                self.end_line();
+            }
+            self.write(line);
+        }
+        self.end_line();
     }
 
     fn write_line(&mut self, string: &str) {
@@ -64,7 +250,7 @@ impl Formatter {
 
 impl From<Formatter> for String {
     fn from(formatter: Formatter) -> Self {
-        formatter.buffer
+        doc::render(&formatter.docs, &formatter.options)
     }
 }
 
@@ -74,35 +260,237 @@ pub trait Fmt {
     fn fmt(&self, formatter: &mut Formatter);
 }
 
-#[derive(Clone, Default)]
-pub struct Options {
-    pub resolve_names: bool,
-}
-
 /// Format `.wat` source code.
-/// Uses tabs for indentation.
-/// Resolves symbolic identifiers and unfolds instruction expressions.
-/// Extracts inline exports and type definitions.
-/// Encodes all number literals in decimal notation.
+/// Uses tabs for indentation by default.
+/// Wraps constructs that exceed the maximum line width.
+/// Resolves symbolic identifiers if requested.
+/// Unfolds instruction expressions, unless another expression style is requested.
+/// Writes imports and exports inline or as separate fields if requested.
+/// Writes type uses with a type reference, a signature or both if requested.
+/// Removes duplicate function types if requested.
+/// Encodes all number literals in decimal notation, unless the output is minified.
+///
+/// # Panics
+///
+/// Panics if `source` cannot be parsed or contains constructs that cannot be formatted.
+/// Use [`try_fmt`] to handle these cases.
 pub fn fmt(source: &str, options: Options) -> String {
-    let buffer = ParseBuffer::new(source).expect("parse buffer");
-    let wat = parse::<Wat>(&buffer).expect("parse");
+    match try_fmt(source, options) {
+        Ok(formatted) => formatted,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+/// Format `.wat` source code like [`fmt`],
+/// returning an error if it cannot be parsed or formatted.
+pub fn try_fmt(source: &str, options: Options) -> Result<String, Error> {
+    format(source, options, Mode::Strict).map(|(formatter, _)| formatter.into())
+}
+
+/// What formatting did to a module besides writing it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// The number of duplicate types that were removed.
+    pub removed_types: usize,
+    /// Options that could not be applied, and why.
+    pub warnings: Vec<String>,
+}
+
+/// Format `.wat` source code like [`try_fmt`],
+/// also returning what formatting did besides writing the module.
+pub fn fmt_with_summary(source: &str, options: Options) -> Result<(String, Summary), Error> {
+    let (mut formatter, removed_types) = format(source, options, Mode::Strict)?;
+    let summary = Summary {
+        removed_types,
+        warnings: std::mem::take(&mut formatter.warnings),
+    };
+    Ok((formatter.into(), summary))
+}
+
+/// Format `source` into a formatter whose document has yet to be laid out,
+/// together with the number of duplicate types that were removed.
+fn format(source: &str, options: Options, mode: Mode) -> Result<(Formatter, usize), Error> {
+    let (mut ids, mut ids_buffer) = (String::new(), None);
+    let buffer = ParseBuffer::new(source).map_err(|err| parse_error(err, source))?;
+    let wat = parse::<Wat>(&buffer).map_err(|err| parse_error(err, source))?;
     match wat {
         Wat::Module(mut module) => {
-            if options.resolve_names {
-                module.resolve().expect("name resolution");
+            // Directives and fallbacks refer to the fields of the source,
+            // so they are found before preparing.
+            let mut directives = Directives::new(source, &module.kind);
+            let mut fallbacks = match mode {
+                Mode::BestEffort => Fallbacks::new(source, &module.kind),
+                Mode::Strict | Mode::Anchored => None,
+            };
+            let removed_types = prepare(&mut module, &options, &mut ids, &mut ids_buffer)
+                .map_err(|err| parse_error(err, source))?;
+            // Ignored fields would keep their line breaks.
+            if options.minify && !directives.is_empty() {
+                return Err(Error::Unsupported(
+                    "`wasmfmt-ignore` directives in minified output".to_string(),
+                ));
+            }
+            directives.follow(&module.kind)?;
+            if let Some(fallbacks) = &mut fallbacks {
+                fallbacks.follow(source, &module.kind);
             }
-            let mut formatter = Formatter::new();
+            let start = |fallbacks| {
+                let mut formatter = Formatter::with_options(&options);
+                formatter.directives = directives.clone();
+                formatter.fallbacks = fallbacks;
+                if options.expression_style == ExpressionStyle::Preserve {
+                    formatter.source = source.to_string();
+                }
+                if mode == Mode::Anchored {
+                    formatter.anchors = Anchors::new(source, &module.kind);
+                }
+                formatter
+            };
+            let mut formatter = start(fallbacks);
             module.fmt(&mut formatter);
-            formatter.into()
+            // Fields split from a copied field may have been written before it was copied.
+            if let Some(fallbacks) = formatter.fallbacks.take() {
+                if fallbacks.repeats() {
+                    formatter = start(Some(fallbacks.restart()));
+                    module.fmt(&mut formatter);
+                } else {
+                    formatter.fallbacks = Some(fallbacks);
+                }
+            }
+            match formatter.unsupported.take() {
+                Some(construct) => Err(Error::Unsupported(construct)),
+                None => Ok((formatter, removed_types)),
+            }
         }
-        Wat::Component(..) => unimplemented!(),
+        Wat::Component(..) => Err(Error::Unsupported("components".to_string())),
+    }
+}
+
+/// Resolve, synthesize, name or strip the ids of `module` and its labels as `options` ask.
+///
+/// Synthesized ids are written to `ids` and parsed into `ids_buffer`,
+/// which the module borrows them from.
+/// Returns the number of duplicate types that were removed.
+pub(crate) fn prepare<'a>(
+    module: &mut Module<'a>,
+    options: &Options,
+    ids: &'a mut String,
+    ids_buffer: &'a mut Option<ParseBuffer<'a>>,
+) -> Result<usize, wast::Error> {
+    if options.resolve_names || options.minify {
+        module.resolve()?;
+    }
+    placement::place_imports(&mut module.kind, options.imports);
+    placement::place_exports(&mut module.kind, options.exports);
+    let removed_types = match options.dedupe_types {
+        true => dedupe::dedupe_types(&mut module.kind),
+        false => 0,
+    };
+    type_uses::normalize_type_uses(&mut module.kind, options.type_uses);
+    let labels = options.branch_style == BranchStyle::Labels;
+    let synthesis = match (options.synthesize_names, labels) {
+        (true, _) => Some(Naming::All),
+        (false, true) => Some(Naming::Labels),
+        (false, false) => None,
+    };
+    if let Some(naming) = synthesis {
+        *ids = names::synthesize_ids(&mut module.kind, naming);
+        let ids: &'a str = ids;
+        *ids_buffer = Some(ParseBuffer::new(ids)?);
+        let ids_buffer: &'a Option<ParseBuffer<'a>> = ids_buffer;
+        if let Some(ids_buffer) = ids_buffer {
+            names::assign_ids(&mut module.kind, ids_buffer, naming)?;
+        }
+    }
+    match (options.name_indices || options.synthesize_names, labels) {
+        (true, _) => names::name_indices(&mut module.kind, Naming::All),
+        (false, true) => names::name_indices(&mut module.kind, Naming::Labels),
+        (false, false) => {}
+    }
+    if options.branch_style == BranchStyle::Depths {
+        labels::label_depths(&mut module.kind);
+    }
+    if options.label_ends {
+        labels::label_ends(&mut module.kind);
+    }
+    if options.minify {
+        module.id = None;
+        module.name = None;
+        names::strip_ids(&mut module.kind);
+    }
+    Ok(removed_types)
+}
+
+fn parse_error(mut err: wast::Error, source: &str) -> Error {
+    err.set_text(source);
+    Error::Parse(err.to_string())
+}
+
+/// Format each field of the module in `source` separately,
+/// returning a description of the field together with its formatted code.
+pub(crate) fn fmt_fields(source: &str, options: &Options) -> Result<Vec<(String, String)>, Error> {
+    let (mut ids, mut ids_buffer) = (String::new(), None);
+    let buffer = ParseBuffer::new(source).map_err(|err| parse_error(err, source))?;
+    let wat = parse::<Wat>(&buffer).map_err(|err| parse_error(err, source))?;
+    match wat {
+        Wat::Module(mut module) => {
+            prepare(&mut module, options, &mut ids, &mut ids_buffer)
+                .map_err(|err| parse_error(err, source))?;
+            let fields = match &module.kind {
+                ModuleKind::Text(fields) => fields,
+                ModuleKind::Binary(..) => {
+                    return Err(Error::Unsupported("binary modules".to_string()))
+                }
+            };
+            let context = ModuleContext::new(fields);
+            fields
+                .iter()
+                .map(|field| {
+                    let mut formatter = Formatter::with_options(options);
+                    formatter.module = context.clone();
+                    if options.expression_style == ExpressionStyle::Preserve {
+                        formatter.source = source.to_string();
+                    }
+                    formatter.fmt(field);
+                    match formatter.unsupported.take() {
+                        Some(construct) => Err(Error::Unsupported(construct)),
+                        None => Ok((describe_field(field), formatter.into())),
+                    }
+                })
+                .collect()
+        }
+        Wat::Component(..) => Err(Error::Unsupported("components".to_string())),
+    }
+}
+
+/// A short description of a module field, such as `func $main`.
+pub(crate) fn describe_field(field: &ModuleField) -> String {
+    let (kind, id) = match field {
+        ModuleField::Type(ty) => ("type", ty.id),
+        ModuleField::Rec(..) => ("rec", None),
+        ModuleField::Import(import) => ("import", import.item.id),
+        ModuleField::Func(func) => ("func", func.id),
+        ModuleField::Table(table) => ("table", table.id),
+        ModuleField::Memory(memory) => ("memory", memory.id),
+        ModuleField::Global(global) => ("global", global.id),
+        ModuleField::Export(..) => ("export", None),
+        ModuleField::Start(..) => ("start", None),
+        ModuleField::Elem(elem) => ("elem", elem.id),
+        ModuleField::Data(data) => ("data", data.id),
+        ModuleField::Tag(tag) => ("tag", tag.id),
+        ModuleField::Custom(..) => ("custom", None),
+    };
+    match id.filter(|id| !utils::id_is_gensym(id)) {
+        Some(id) => format!("{} ${}", kind, id.name()),
+        None => kind.to_string(),
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{fmt, Options};
+    use super::fmt;
+    use crate::options::{ExpressionStyle, IndentStyle, Options};
+    use crate::verify::verify;
     use assert_matches::assert_matches;
     use pretty_assertions::assert_eq;
     use wast::{
@@ -116,12 +504,22 @@ mod test {
     }
 
     mod default {
-        use super::{assert_eq, assert_matches, fmt, parse, Options};
+        use super::{assert_eq, assert_matches, fmt, parse, verify, Options};
 
         const OPTIONS: Options = Options {
             resolve_names: false,
+            ..Options::DEFAULT
         };
 
+        #[test]
+        fn conversions() {
+            let input = include_str!("../../tests/data/input/conversions.wat");
+            let expected = include_str!("../../tests/data/output/default/conversions.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(verify(input, &actual, &OPTIONS), Ok(()));
+        }
+
         #[test]
         fn data() {
             let input = include_str!("../../tests/data/input/data.wat");
@@ -257,6 +655,15 @@ mod test {
             assert_matches!(parse(&actual), Ok(..));
         }
 
+        #[test]
+        fn mixed() {
+            let input = include_str!("../../tests/data/input/mixed.wat");
+            let expected = include_str!("../../tests/data/output/default/mixed.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
         #[test]
         fn memory() {
             let input = include_str!("../../tests/data/input/memory.wat");
@@ -283,15 +690,34 @@ mod test {
             assert_eq!(actual, expected);
             assert_matches!(parse(&actual), Ok(..));
         }
+
+        #[test]
+        fn wrap() {
+            let input = include_str!("../../tests/data/input/wrap.wat");
+            let expected = include_str!("../../tests/data/output/default/wrap.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
     }
 
     mod resolved {
-        use super::{assert_eq, assert_matches, fmt, parse, Options};
+        use super::{assert_eq, assert_matches, fmt, parse, verify, Options};
 
         const OPTIONS: Options = Options {
             resolve_names: true,
+            ..Options::DEFAULT
         };
 
+        #[test]
+        fn conversions() {
+            let input = include_str!("../../tests/data/input/conversions.wat");
+            let expected = include_str!("../../tests/data/output/resolved/conversions.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(verify(input, &actual, &OPTIONS), Ok(()));
+        }
+
         #[test]
         fn data() {
             let input = include_str!("../../tests/data/input/data.wat");
@@ -427,6 +853,15 @@ mod test {
             assert_matches!(parse(&actual), Ok(..));
         }
 
+        #[test]
+        fn mixed() {
+            let input = include_str!("../../tests/data/input/mixed.wat");
+            let expected = include_str!("../../tests/data/output/resolved/mixed.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
         #[test]
         fn memory() {
             let input = include_str!("../../tests/data/input/memory.wat");
@@ -453,5 +888,228 @@ mod test {
             assert_eq!(actual, expected);
             assert_matches!(parse(&actual), Ok(..));
         }
+
+        #[test]
+        fn wrap() {
+            let input = include_str!("../../tests/data/input/wrap.wat");
+            let expected = include_str!("../../tests/data/output/resolved/wrap.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+    }
+
+    mod folded {
+        use super::{assert_eq, assert_matches, fmt, parse, ExpressionStyle, Options};
+
+        const OPTIONS: Options = Options {
+            expression_style: ExpressionStyle::Folded,
+            ..Options::DEFAULT
+        };
+
+        #[test]
+        fn f32() {
+            let input = include_str!("../../tests/data/input/f32.wat");
+            let expected = include_str!("../../tests/data/output/folded/f32.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn f64() {
+            let input = include_str!("../../tests/data/input/f64.wat");
+            let expected = include_str!("../../tests/data/output/folded/f64.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn fac() {
+            let input = include_str!("../../tests/data/input/fac.wat");
+            let expected = include_str!("../../tests/data/output/folded/fac.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn i32() {
+            let input = include_str!("../../tests/data/input/i32.wat");
+            let expected = include_str!("../../tests/data/output/folded/i32.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn i64() {
+            let input = include_str!("../../tests/data/input/i64.wat");
+            let expected = include_str!("../../tests/data/output/folded/i64.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn memory_grow() {
+            let input = include_str!("../../tests/data/input/memory_grow.wat");
+            let expected = include_str!("../../tests/data/output/folded/memory_grow.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn mixed() {
+            let input = include_str!("../../tests/data/input/mixed.wat");
+            let expected = include_str!("../../tests/data/output/folded/mixed.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn start() {
+            let input = include_str!("../../tests/data/input/start.wat");
+            let expected = include_str!("../../tests/data/output/folded/start.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn wrap() {
+            let input = include_str!("../../tests/data/input/wrap.wat");
+            let expected = include_str!("../../tests/data/output/folded/wrap.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+    }
+
+    mod preserve {
+        use super::{assert_eq, assert_matches, fmt, parse, ExpressionStyle, Options};
+
+        const OPTIONS: Options = Options {
+            expression_style: ExpressionStyle::Preserve,
+            ..Options::DEFAULT
+        };
+
+        #[test]
+        fn fac() {
+            let input = include_str!("../../tests/data/input/fac.wat");
+            let expected = include_str!("../../tests/data/output/preserve/fac.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn mixed() {
+            let input = include_str!("../../tests/data/input/mixed.wat");
+            let expected = include_str!("../../tests/data/output/preserve/mixed.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+
+        #[test]
+        fn wrap() {
+            let input = include_str!("../../tests/data/input/wrap.wat");
+            let expected = include_str!("../../tests/data/output/preserve/wrap.wat");
+            let actual = fmt(input, OPTIONS.clone());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+        }
+    }
+
+    mod errors {
+        use super::super::try_fmt;
+        use crate::error::Error;
+        use crate::options::Options;
+        use assert_matches::assert_matches;
+
+        #[test]
+        fn try_fmt_should_report_parse_errors() {
+            let result = try_fmt("(module (func (i32.add", Options::default());
+            assert_matches!(result, Err(Error::Parse(message)) if message.contains("expected"));
+        }
+
+        #[test]
+        fn try_fmt_should_report_unsupported_constructs() {
+            let result = try_fmt("(module (func (param v128)))", Options::default());
+            assert_matches!(result, Err(Error::Unsupported(construct)) if construct == "the `v128` type");
+        }
+    }
+
+    mod indentation {
+        use super::{assert_eq, fmt, IndentStyle, Options};
+
+        fn options(indent_style: IndentStyle) -> Options {
+            Options {
+                indent_style,
+                indent_width: 2,
+                ..Options::default()
+            }
+        }
+
+        #[test]
+        fn spaces_should_only_change_indentation() {
+            let input = include_str!("../../tests/data/input/fac.wat");
+            let tabs = fmt(input, options(IndentStyle::Tabs));
+            let spaces = fmt(input, options(IndentStyle::Spaces));
+            assert_eq!(spaces, tabs.replace('\t', "  "));
+        }
+
+        #[test]
+        fn spaces_should_not_change_wrapping() {
+            let input = include_str!("../../tests/data/input/wrap.wat");
+            let tabs = fmt(input, options(IndentStyle::Tabs));
+            let spaces = fmt(input, options(IndentStyle::Spaces));
+            assert_eq!(spaces, tabs.replace('\t', "  "));
+        }
+    }
+
+    mod minify {
+        use super::{assert_eq, assert_matches, fmt, parse, verify, Options};
+
+        fn options() -> Options {
+            Options {
+                minify: true,
+                ..Options::default()
+            }
+        }
+
+        #[test]
+        fn minify_should_write_one_line_without_ids() {
+            let input = "(module $m
+  (func $inc (export \"inc\") (param $x i32) (result i32)
+    (block $done
+      (br_if $done (i32.eqz (local.get $x))))
+    (i32.add (local.get $x) (i32.const 0xff))))";
+            let expected =
+                "(module (export \"inc\" (func 0)) (func (type 0) (param i32) (result i32) \
+block (local.get 0) i32.eqz (br_if 0) end (local.get 0) (i32.const 255) i32.add) \
+(type (func (param i32) (result i32))))\n";
+            let actual = fmt(input, options());
+            assert_eq!(actual, expected);
+            assert_matches!(parse(&actual), Ok(..));
+            assert_matches!(verify(input, &actual, &options()), Ok(()));
+        }
+
+        #[test]
+        fn minify_should_write_shortest_numbers() {
+            let input = "(module
+  (global i64 (i64.const 9223372036854775807))
+  (global f64 (f64.const 1e100))
+  (global f32 (f32.const 0.5)))";
+            let expected = "(module (global i64 (i64.const 0x7fffffffffffffff)) \
+(global f64 (f64.const 1e100)) (global f32 (f32.const 0.5)))\n";
+            let actual = fmt(input, options());
+            assert_eq!(actual, expected);
+            assert_matches!(verify(input, &actual, &options()), Ok(()));
+        }
     }
 }
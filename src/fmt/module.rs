@@ -1,10 +1,19 @@
+use super::context::ModuleContext;
+use super::ignore::Directive;
+use super::order::field_order;
 use super::start::Start;
 use super::{Fmt, Formatter};
 use wast::core::{Module, ModuleField, ModuleKind};
 
 impl<'src> Fmt for Module<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
-        formatter.write_line("(module");
+        formatter.start_line();
+        formatter.write("(module");
+        if let Some(id) = &self.id {
+            formatter.write(" ");
+            formatter.fmt(id);
+        }
+        formatter.end_line();
         formatter.indent();
         formatter.fmt(&self.kind);
         formatter.deindent();
@@ -16,17 +25,42 @@ impl<'src> Fmt for &ModuleKind<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         match self {
             ModuleKind::Text(fields) => {
+                formatter.module = ModuleContext::new(fields);
                 formatter.fmt(fields);
             }
-            ModuleKind::Binary(..) => unimplemented!(),
+            ModuleKind::Binary(..) => formatter.unsupported("binary modules"),
         }
     }
 }
 
 impl<'src> Fmt for &Vec<ModuleField<'src>> {
     fn fmt(&self, formatter: &mut Formatter) {
-        for field in self.iter() {
-            formatter.fmt(field);
+        let order = field_order(self, formatter.options.field_order, |index| {
+            formatter.directives.get(index) != Directive::Format
+        })
+        .unwrap_or_else(|warning| {
+            formatter.warn(warning);
+            (0..self.len()).collect()
+        });
+        for index in order {
+            let field = &self[index];
+            if formatter
+                .fallbacks
+                .as_ref()
+                .is_some_and(|fallbacks| fallbacks.skip(index))
+            {
+                continue;
+            }
+            match formatter.directives.get(index) {
+                Directive::Format => {
+                    let start = formatter.docs.len();
+                    formatter.mark_field(index);
+                    formatter.fmt(field);
+                    formatter.fall_back(index, field, start);
+                }
+                Directive::Verbatim(text) => formatter.write_verbatim(&text),
+                Directive::Skip => {}
+            }
         }
     }
 }
@@ -44,9 +78,9 @@ impl<'src> Fmt for &ModuleField<'src> {
             ModuleField::Export(export) => formatter.fmt(export),
             ModuleField::Import(import) => formatter.fmt(import),
             ModuleField::Start(index) => formatter.fmt(&Start::new(*index)),
-            ModuleField::Custom(..) => unimplemented!(),
-            ModuleField::Rec(..) => unimplemented!(),
-            ModuleField::Tag(..) => unimplemented!(),
+            ModuleField::Custom(..) => formatter.unsupported("custom sections"),
+            ModuleField::Rec(..) => formatter.unsupported("recursion groups"),
+            ModuleField::Tag(..) => formatter.unsupported("tags"),
         };
     }
 }
@@ -0,0 +1,960 @@
+use super::labels::{branch_targets, label_depths};
+use super::utils::id_is_gensym;
+use std::collections::{HashMap, HashSet};
+use std::iter;
+use wast::core::{
+    ElemKind, ElemPayload, ExportKind, Expression, FuncKind, FunctionType, GlobalKind, HeapType,
+    Instruction, ItemKind, MemoryKind, ModuleField, ModuleKind, RefType, TableKind, TagType, Type,
+    TypeDef, TypeUse, ValType,
+};
+use wast::parser::{parse, Parse, ParseBuffer, Parser};
+use wast::token::{Id, Index};
+
+/// The items that are named.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Naming {
+    /// Every item.
+    All,
+    /// Only blocks, and only those that are the target of a branch when ids are synthesized.
+    Labels,
+}
+
+/// Replace numeric indices with the ids of the items they refer to,
+/// wherever those items have an id.
+///
+/// This is the reverse of name resolution.
+/// Indices of items without an id, or whose id is shared by another item, are left as they are.
+pub(crate) fn name_indices(kind: &mut ModuleKind, naming: Naming) {
+    let fields = match kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return,
+    };
+    let names = match naming {
+        Naming::All => Names::new(fields),
+        Naming::Labels => Names::default(),
+    };
+    for field in fields.iter_mut() {
+        names.field(field);
+    }
+}
+
+/// Remove the ids, name annotations and custom sections of a module
+/// whose references have been resolved to indices.
+pub(crate) fn strip_ids(kind: &mut ModuleKind) {
+    let fields = match kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return,
+    };
+    fields.retain(|field| !matches!(field, ModuleField::Custom(..)));
+    for field in fields.iter_mut() {
+        match field {
+            ModuleField::Type(ty) => strip_type(ty),
+            ModuleField::Rec(rec) => rec.types.iter_mut().for_each(strip_type),
+            ModuleField::Import(import) => {
+                import.item.id = None;
+                import.item.name = None;
+                if let ItemKind::Func(ty) | ItemKind::Tag(TagType::Exception(ty)) =
+                    &mut import.item.kind
+                {
+                    strip_type_use(ty);
+                }
+            }
+            ModuleField::Func(func) => {
+                func.id = None;
+                func.name = None;
+                strip_type_use(&mut func.ty);
+                if let FuncKind::Inline { locals, .. } = &mut func.kind {
+                    for local in locals.iter_mut() {
+                        local.id = None;
+                        local.name = None;
+                    }
+                }
+            }
+            ModuleField::Table(table) => {
+                table.id = None;
+                table.name = None;
+            }
+            ModuleField::Memory(memory) => {
+                memory.id = None;
+                memory.name = None;
+            }
+            ModuleField::Global(global) => {
+                global.id = None;
+                global.name = None;
+            }
+            ModuleField::Elem(elem) => {
+                elem.id = None;
+                elem.name = None;
+            }
+            ModuleField::Data(data) => {
+                data.id = None;
+                data.name = None;
+            }
+            ModuleField::Tag(tag) => {
+                tag.id = None;
+                tag.name = None;
+                let TagType::Exception(ty) = &mut tag.ty;
+                strip_type_use(ty);
+            }
+            ModuleField::Export(..) | ModuleField::Start(..) | ModuleField::Custom(..) => {}
+        }
+    }
+    // Branches refer to blocks by depth once resolved, so labels are only removed.
+    label_depths(kind);
+}
+
+fn strip_type(ty: &mut Type) {
+    ty.id = None;
+    ty.name = None;
+    match &mut ty.def {
+        TypeDef::Func(functy) => strip_params(functy),
+        TypeDef::Struct(structty) => {
+            for field in structty.fields.iter_mut() {
+                field.id = None;
+            }
+        }
+        TypeDef::Array(..) => {}
+    }
+}
+
+fn strip_type_use(ty: &mut TypeUse<FunctionType>) {
+    if let Some(functy) = &mut ty.inline {
+        strip_params(functy);
+    }
+}
+
+fn strip_params(functy: &mut FunctionType) {
+    for (id, name, _) in functy.params.iter_mut() {
+        *id = None;
+        *name = None;
+    }
+}
+
+/// The ids of the items in each of the module's index spaces.
+#[derive(Default)]
+struct Names<'src> {
+    /// Whether locals are named, which is the case if any of the other index spaces are.
+    locals: bool,
+    types: Vec<Option<Id<'src>>>,
+    funcs: Vec<Option<Id<'src>>>,
+    tables: Vec<Option<Id<'src>>>,
+    memories: Vec<Option<Id<'src>>>,
+    globals: Vec<Option<Id<'src>>>,
+    data: Vec<Option<Id<'src>>>,
+    signatures: Signatures<'src>,
+}
+
+impl<'src> Names<'src> {
+    fn new(fields: &[ModuleField<'src>]) -> Self {
+        let mut names = Names {
+            locals: true,
+            signatures: Signatures::new(fields),
+            ..Names::default()
+        };
+        // Imported items precede defined items in each index space.
+        let mut defined = Names::default();
+        for field in fields {
+            match field {
+                ModuleField::Type(ty) => names.types.push(ty.id),
+                ModuleField::Rec(rec) => names.types.extend(rec.types.iter().map(|ty| ty.id)),
+                ModuleField::Import(import) => {
+                    let id = import.item.id;
+                    match import.item.kind {
+                        ItemKind::Func(..) => names.funcs.push(id),
+                        ItemKind::Table(..) => names.tables.push(id),
+                        ItemKind::Memory(..) => names.memories.push(id),
+                        ItemKind::Global(..) => names.globals.push(id),
+                        ItemKind::Tag(..) => {}
+                    }
+                }
+                ModuleField::Func(func) => match func.kind {
+                    FuncKind::Import(..) => names.funcs.push(func.id),
+                    FuncKind::Inline { .. } => defined.funcs.push(func.id),
+                },
+                ModuleField::Table(table) => match table.kind {
+                    TableKind::Import { .. } => names.tables.push(table.id),
+                    _ => defined.tables.push(table.id),
+                },
+                ModuleField::Memory(memory) => match memory.kind {
+                    MemoryKind::Import { .. } => names.memories.push(memory.id),
+                    MemoryKind::Normal(..) => defined.memories.push(memory.id),
+                    // Inline data defines a data segment without an id.
+                    MemoryKind::Inline { .. } => {
+                        defined.memories.push(memory.id);
+                        names.data.push(None);
+                    }
+                },
+                ModuleField::Global(global) => match global.kind {
+                    GlobalKind::Import(..) => names.globals.push(global.id),
+                    GlobalKind::Inline(..) => defined.globals.push(global.id),
+                },
+                ModuleField::Data(data) => names.data.push(data.id),
+                _ => {}
+            }
+        }
+        names.funcs.extend(defined.funcs);
+        names.tables.extend(defined.tables);
+        names.memories.extend(defined.memories);
+        names.globals.extend(defined.globals);
+        for ids in [
+            &mut names.types,
+            &mut names.funcs,
+            &mut names.tables,
+            &mut names.memories,
+            &mut names.globals,
+            &mut names.data,
+        ] {
+            forget_ambiguous(ids);
+        }
+        names
+    }
+
+    fn field(&self, field: &mut ModuleField<'src>) {
+        match field {
+            ModuleField::Import(import) => {
+                if let ItemKind::Func(ty) = &mut import.item.kind {
+                    self.type_use(ty);
+                }
+            }
+            ModuleField::Func(func) => {
+                self.type_use(&mut func.ty);
+                if let FuncKind::Inline { locals, expression } = &mut func.kind {
+                    let params = match &func.ty.inline {
+                        Some(functy) => Some(functy.params.iter().map(|(id, ..)| *id).collect()),
+                        // The parameters of a referenced type have no ids, but precede the locals.
+                        None => self.signatures.param_count(&func.ty).map(|n| vec![None; n]),
+                    };
+                    // Locals cannot be numbered if the number of parameters is unknown.
+                    let mut ids = match params.filter(|_| self.locals) {
+                        Some(params) => params
+                            .into_iter()
+                            .chain(locals.iter().map(|local| local.id))
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    forget_ambiguous(&mut ids);
+                    self.expression(expression, &ids);
+                }
+            }
+            ModuleField::Global(global) => {
+                if let GlobalKind::Inline(expression) = &mut global.kind {
+                    self.expression(expression, &[]);
+                }
+            }
+            ModuleField::Export(export) => {
+                let ids = match export.kind {
+                    ExportKind::Func => &self.funcs,
+                    ExportKind::Table => &self.tables,
+                    ExportKind::Memory => &self.memories,
+                    ExportKind::Global => &self.globals,
+                    ExportKind::Tag => return,
+                };
+                name(ids, &mut export.item);
+            }
+            ModuleField::Start(index) => name(&self.funcs, index),
+            ModuleField::Elem(elem) => {
+                // The table of an active segment is written without a keyword,
+                // where an id would be read as the id of the segment, so it is left as it is.
+                if let ElemKind::Active { offset, .. } = &mut elem.kind {
+                    self.expression(offset, &[]);
+                }
+                match &mut elem.payload {
+                    ElemPayload::Indices(indices) => {
+                        for index in indices {
+                            name(&self.funcs, index);
+                        }
+                    }
+                    ElemPayload::Exprs { exprs, .. } => {
+                        for expression in exprs {
+                            self.expression(expression, &[]);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn type_use(&self, ty: &mut TypeUse<'src, FunctionType<'src>>) {
+        if let Some(index) = &mut ty.index {
+            name(&self.types, index);
+        }
+    }
+
+    fn expression(&self, expression: &mut Expression<'src>, locals: &[Option<Id<'src>>]) {
+        // The labels of the enclosing blocks, innermost last.
+        let mut labels = Vec::new();
+        for instruction in expression.instrs.iter_mut() {
+            match instruction {
+                Instruction::Block(bt)
+                | Instruction::Loop(bt)
+                | Instruction::If(bt)
+                | Instruction::Try(bt) => {
+                    self.type_use(&mut bt.ty);
+                    labels.push(bt.label);
+                }
+                Instruction::End(..) => {
+                    labels.pop();
+                }
+                Instruction::Delegate(index) => {
+                    labels.pop();
+                    name_label(&labels, index);
+                }
+                Instruction::Br(index)
+                | Instruction::BrIf(index)
+                | Instruction::BrOnNull(index)
+                | Instruction::BrOnNonNull(index)
+                | Instruction::Rethrow(index) => name_label(&labels, index),
+                Instruction::BrTable(indices) => {
+                    for index in indices
+                        .labels
+                        .iter_mut()
+                        .chain(iter::once(&mut indices.default))
+                    {
+                        name_label(&labels, index);
+                    }
+                }
+                Instruction::Call(index)
+                | Instruction::ReturnCall(index)
+                | Instruction::RefFunc(index) => name(&self.funcs, index),
+                Instruction::CallIndirect(call) | Instruction::ReturnCallIndirect(call) => {
+                    name(&self.tables, &mut call.table);
+                    self.type_use(&mut call.ty);
+                }
+                Instruction::LocalGet(index)
+                | Instruction::LocalSet(index)
+                | Instruction::LocalTee(index) => name(locals, index),
+                Instruction::GlobalGet(index) | Instruction::GlobalSet(index) => {
+                    name(&self.globals, index)
+                }
+                Instruction::MemoryInit(init) => name(&self.data, &mut init.data),
+                Instruction::DataDrop(index) => name(&self.data, index),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Replace `index` with the id of the item it refers to, if that item has an id.
+fn name<'src>(ids: &[Option<Id<'src>>], index: &mut Index<'src>) {
+    if let Index::Num(n, ..) = index {
+        if let Some(Some(id)) = ids.get(*n as usize) {
+            *index = Index::Id(*id);
+        }
+    }
+}
+
+/// Replace the branch depth `index` with the label of the block it refers to,
+/// unless a block in between has the same label.
+fn name_label<'src>(labels: &[Option<Id<'src>>], index: &mut Index<'src>) {
+    let depth = match index {
+        Index::Num(depth, ..) => *depth as usize,
+        Index::Id(..) => return,
+    };
+    // The outermost label is the function body itself, which has no id.
+    let target = match labels.len().checked_sub(depth + 1) {
+        Some(target) => target,
+        None => return,
+    };
+    if let Some(id) = labels[target] {
+        let shadowed = labels[target + 1..]
+            .iter()
+            .flatten()
+            .any(|label| label.name() == id.name());
+        if !id_is_gensym(&id) && !shadowed {
+            *index = Index::Id(id);
+        }
+    }
+}
+
+/// Forget ids that cannot be used to refer to their item,
+/// because another item has the same id or because they were generated.
+fn forget_ambiguous(ids: &mut [Option<Id>]) {
+    let mut counts = HashMap::new();
+    for id in ids.iter().flatten() {
+        *counts.entry(id.name()).or_insert(0) += 1;
+    }
+    for id in ids.iter_mut() {
+        if id.is_some_and(|id| id_is_gensym(&id) || counts[id.name()] > 1) {
+            *id = None;
+        }
+    }
+}
+
+/// Write readable ids for the anonymous functions, types, parameters, locals and block labels,
+/// to be parsed and passed to [`assign_ids`].
+///
+/// Ids can only be created by parsing them, so the module borrows its new ids
+/// from a buffer that is parsed from the returned text.
+pub(crate) fn synthesize_ids(kind: &mut ModuleKind, naming: Naming) -> String {
+    let names = anonymous(kind, naming)
+        .into_iter()
+        .map(|(_, name)| format!("${}", name))
+        .collect::<Vec<_>>();
+    names.join(" ")
+}
+
+/// Give the anonymous items the ids parsed from the text written by [`synthesize_ids`].
+pub(crate) fn assign_ids<'src>(
+    kind: &mut ModuleKind<'src>,
+    buffer: &'src ParseBuffer<'src>,
+    naming: Naming,
+) -> Result<(), wast::Error> {
+    let Ids(ids) = parse::<Ids>(buffer)?;
+    for ((slot, _), id) in anonymous(kind, naming).into_iter().zip(ids) {
+        *slot = Some(id);
+    }
+    Ok(())
+}
+
+/// A list of ids separated by whitespace.
+struct Ids<'src>(Vec<Id<'src>>);
+
+impl<'src> Parse<'src> for Ids<'src> {
+    fn parse(parser: Parser<'src>) -> wast::parser::Result<Self> {
+        let mut ids = Vec::new();
+        while !parser.is_empty() {
+            ids.push(parser.parse()?);
+        }
+        Ok(Ids(ids))
+    }
+}
+
+type Slot<'a, 'src> = (&'a mut Option<Id<'src>>, String);
+
+/// The id of each anonymous item together with the name synthesized for it,
+/// always in the same order.
+fn anonymous<'a, 'src>(kind: &'a mut ModuleKind<'src>, naming: Naming) -> Vec<Slot<'a, 'src>> {
+    let fields = match kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return Vec::new(),
+    };
+    let signatures = Signatures::new(fields);
+    let func_names = func_names(fields);
+    let mut types = Scope::new(fields.iter().flat_map(|field| match field {
+        ModuleField::Type(ty) => vec![ty.id],
+        ModuleField::Rec(rec) => rec.types.iter().map(|ty| ty.id).collect(),
+        _ => Vec::new(),
+    }));
+    let mut funcs = Scope::new(fields.iter().map(|field| match field {
+        ModuleField::Import(import) => import.item.id,
+        ModuleField::Func(func) => func.id,
+        _ => None,
+    }));
+
+    let mut slots = Vec::new();
+    let mut type_index = 0;
+    let mut func_names = func_names.into_iter();
+    for field in fields.iter_mut() {
+        match field {
+            _ if naming == Naming::Labels => {
+                if let ModuleField::Func(func) = field {
+                    if let FuncKind::Inline { expression, .. } = &mut func.kind {
+                        slots.extend(label_slots(expression, naming));
+                    }
+                }
+            }
+            ModuleField::Type(ty) => {
+                slots.extend(type_slot(ty, type_index, &mut types));
+                type_index += 1;
+            }
+            ModuleField::Rec(rec) => {
+                for ty in rec.types.iter_mut() {
+                    slots.extend(type_slot(ty, type_index, &mut types));
+                    type_index += 1;
+                }
+            }
+            ModuleField::Import(import) => {
+                if let ItemKind::Func(..) = import.item.kind {
+                    let name = func_names.next().expect("function name");
+                    if is_anonymous(&import.item.id) {
+                        slots.push((&mut import.item.id, funcs.unique(name)));
+                    }
+                }
+            }
+            ModuleField::Func(func) => {
+                let name = func_names.next().expect("function name");
+                if is_anonymous(&func.id) {
+                    slots.push((&mut func.id, funcs.unique(name)));
+                }
+                if let FuncKind::Inline { locals, expression } = &mut func.kind {
+                    // Parameters only get an id in an inline signature,
+                    // so it is written out for functions that only refer to their type.
+                    if func.ty.inline.is_none() {
+                        func.ty.inline = signatures
+                            .referenced(&func.ty)
+                            .filter(|functy| !functy.params.is_empty());
+                    }
+                    let params = signatures.params(&func.ty);
+                    let params_ids = func.ty.inline.iter().flat_map(|ty| ty.params.iter());
+                    let mut scope = Scope::new(
+                        params_ids
+                            .map(|(id, ..)| *id)
+                            .chain(locals.iter().map(|local| local.id)),
+                    );
+                    if let Some(functy) = &mut func.ty.inline {
+                        for (i, (id, ..)) in functy.params.iter_mut().enumerate() {
+                            if is_anonymous(id) {
+                                slots.push((id, scope.unique(format!("p{}", i))));
+                            }
+                        }
+                    }
+                    for (i, local) in locals.iter_mut().enumerate() {
+                        if is_anonymous(&local.id) {
+                            slots.push((&mut local.id, scope.unique(format!("l{}", params + i))));
+                        }
+                    }
+                    slots.extend(label_slots(expression, naming));
+                }
+            }
+            _ => {}
+        }
+    }
+    slots
+}
+
+/// The labels of the anonymous blocks of a function, named after their kind and depth,
+/// such as `B0` for an outermost block and `L1` for a loop within it.
+fn label_slots<'a, 'src>(
+    expression: &'a mut Expression<'src>,
+    naming: Naming,
+) -> Vec<Slot<'a, 'src>> {
+    let targets = branch_targets(&expression.instrs);
+    let labels = Scope::new(
+        expression
+            .instrs
+            .iter()
+            .map(|instruction| match instruction {
+                Instruction::Block(bt)
+                | Instruction::Loop(bt)
+                | Instruction::If(bt)
+                | Instruction::Try(bt) => bt.label,
+                _ => None,
+            }),
+    );
+    let mut slots = Vec::new();
+    let mut depth = 0;
+    for (instruction, target) in expression.instrs.iter_mut().zip(targets) {
+        let (bt, kind) = match instruction {
+            Instruction::Block(bt) | Instruction::If(bt) | Instruction::Try(bt) => (bt, "B"),
+            Instruction::Loop(bt) => (bt, "L"),
+            Instruction::End(..) | Instruction::Delegate(..) => {
+                depth -= 1;
+                continue;
+            }
+            _ => continue,
+        };
+        if is_anonymous(&bt.label) && (naming == Naming::All || target) {
+            // Blocks at the same depth do not overlap, so only the labels in the source can clash.
+            slots.push((&mut bt.label, labels.fresh(format!("{}{}", kind, depth))));
+        }
+        depth += 1;
+    }
+    slots
+}
+
+fn type_slot<'a, 'src>(
+    ty: &'a mut Type<'src>,
+    index: usize,
+    types: &mut Scope,
+) -> Option<Slot<'a, 'src>> {
+    if !is_anonymous(&ty.id) {
+        return None;
+    }
+    let name = match &ty.def {
+        TypeDef::Func(functy) => signature_name(functy),
+        TypeDef::Struct(..) | TypeDef::Array(..) => format!("t{}", index),
+    };
+    Some((&mut ty.id, types.unique(name)))
+}
+
+fn is_anonymous(id: &Option<Id>) -> bool {
+    id.is_none_or(|id| id_is_gensym(&id))
+}
+
+/// The names of the functions in the order of the fields that define them,
+/// taken from their first export name, their import name, or their index.
+fn func_names(fields: &[ModuleField]) -> Vec<String> {
+    let mut indices = Vec::new();
+    let mut imported = 0;
+    let mut defined = Vec::new();
+    let mut ids = HashMap::new();
+    for (i, field) in fields.iter().enumerate() {
+        let (id, import) = match field {
+            ModuleField::Import(import) if matches!(import.item.kind, ItemKind::Func(..)) => {
+                (import.item.id, true)
+            }
+            ModuleField::Func(func) => (func.id, matches!(func.kind, FuncKind::Import(..))),
+            _ => continue,
+        };
+        if import {
+            indices.push((i, imported));
+            imported += 1;
+        } else {
+            defined.push(i);
+        }
+        if let Some(id) = id {
+            ids.insert(id.name(), i);
+        }
+    }
+    let imports = indices.len();
+    indices.extend(
+        defined
+            .into_iter()
+            .enumerate()
+            .map(|(n, i)| (i, imports + n)),
+    );
+    indices.sort_unstable();
+
+    let mut exports = HashMap::new();
+    for field in fields {
+        if let ModuleField::Export(export) = field {
+            if export.kind != ExportKind::Func {
+                continue;
+            }
+            let field = match export.item {
+                Index::Num(n, ..) => indices
+                    .iter()
+                    .find(|(_, index)| *index == n as usize)
+                    .map(|(i, _)| *i),
+                Index::Id(id) => ids.get(id.name()).copied(),
+            };
+            if let Some(field) = field {
+                exports.entry(field).or_insert(export.name);
+            }
+        }
+    }
+
+    indices
+        .into_iter()
+        .map(|(i, index)| {
+            let (export, import) = match &fields[i] {
+                ModuleField::Import(import) => (None, Some((import.module, import.field))),
+                ModuleField::Func(func) => {
+                    let import = match &func.kind {
+                        FuncKind::Import(import) => Some((import.module, import.field)),
+                        FuncKind::Inline { .. } => None,
+                    };
+                    (func.exports.names.first().copied(), import)
+                }
+                _ => unreachable!(),
+            };
+            let name = export
+                .or_else(|| exports.get(&i).copied())
+                .map(sanitize)
+                .or_else(|| {
+                    import.map(|(module, field)| sanitize(&format!("{}.{}", module, field)))
+                })
+                .filter(|name| !name.is_empty());
+            name.unwrap_or_else(|| format!("f{}", index))
+        })
+        .collect()
+}
+
+/// The function types of the module, used to number the locals that follow their parameters.
+#[derive(Default)]
+struct Signatures<'src> {
+    types: Vec<(Option<Id<'src>>, Option<FunctionType<'src>>)>,
+}
+
+impl<'src> Signatures<'src> {
+    fn new(fields: &[ModuleField<'src>]) -> Self {
+        let mut types = Vec::new();
+        for field in fields {
+            let defs = match field {
+                ModuleField::Type(ty) => vec![ty],
+                ModuleField::Rec(rec) => rec.types.iter().collect(),
+                _ => continue,
+            };
+            for ty in defs {
+                let functy = match &ty.def {
+                    TypeDef::Func(functy) => Some(functy.clone()),
+                    TypeDef::Struct(..) | TypeDef::Array(..) => None,
+                };
+                types.push((ty.id, functy));
+            }
+        }
+        Signatures { types }
+    }
+
+    fn params(&self, ty: &TypeUse<FunctionType>) -> usize {
+        self.param_count(ty).unwrap_or(0)
+    }
+
+    /// The number of parameters of `ty`, or `None` if it refers to a type that is not defined.
+    fn param_count(&self, ty: &TypeUse<FunctionType>) -> Option<usize> {
+        if let Some(functy) = &ty.inline {
+            return Some(functy.params.len());
+        }
+        self.entry(ty)
+            .map(|functy| functy.as_ref().map_or(0, |functy| functy.params.len()))
+    }
+
+    /// The function type that `ty` refers to, if it is defined.
+    fn referenced(&self, ty: &TypeUse<FunctionType>) -> Option<FunctionType<'src>> {
+        self.entry(ty).cloned().flatten()
+    }
+
+    fn entry(&self, ty: &TypeUse<FunctionType>) -> Option<&Option<FunctionType<'src>>> {
+        let entry = match &ty.index {
+            Some(Index::Num(n, ..)) => self.types.get(*n as usize),
+            Some(Index::Id(id)) => self
+                .types
+                .iter()
+                .find(|(other, _)| other.map(|other| other.name()) == Some(id.name())),
+            None => None,
+        };
+        entry.map(|(_, functy)| functy)
+    }
+}
+
+/// A name after a function signature, such as `i32_i32_to_i64`.
+fn signature_name(functy: &FunctionType) -> String {
+    let side = |types: &mut dyn Iterator<Item = &ValType>| {
+        let names = types.map(val_type_name).collect::<Vec<_>>();
+        if names.is_empty() {
+            "void".to_string()
+        } else {
+            names.join("_")
+        }
+    };
+    format!(
+        "{}_to_{}",
+        side(&mut functy.params.iter().map(|(_, _, ty)| ty)),
+        side(&mut functy.results.iter())
+    )
+}
+
+fn val_type_name(ty: &ValType) -> &'static str {
+    match ty {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        ValType::V128 => "v128",
+        ValType::Ref(RefType {
+            nullable: true,
+            heap: HeapType::Func,
+        }) => "funcref",
+        ValType::Ref(RefType {
+            nullable: true,
+            heap: HeapType::Extern,
+        }) => "externref",
+        ValType::Ref(..) => "ref",
+    }
+}
+
+/// Replace the characters that cannot appear in an id.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The ids in use in one index space, used to keep synthesized ids unique.
+struct Scope {
+    taken: HashSet<String>,
+}
+
+impl Scope {
+    fn new<'src>(ids: impl Iterator<Item = Option<Id<'src>>>) -> Self {
+        Scope {
+            taken: ids.flatten().map(|id| id.name().to_string()).collect(),
+        }
+    }
+
+    /// `name`, or `name` followed by a number if it is taken already.
+    fn fresh(&self, name: String) -> String {
+        let mut fresh = name.clone();
+        let mut n = 1;
+        while self.taken.contains(&fresh) {
+            fresh = format!("{}_{}", name, n);
+            n += 1;
+        }
+        fresh
+    }
+
+    /// Like `fresh`, also taking the returned name.
+    fn unique(&mut self, name: String) -> String {
+        let unique = self.fresh(name);
+        self.taken.insert(unique.clone());
+        unique
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fmt::fmt;
+    use crate::options::Options;
+    use pretty_assertions::assert_eq;
+
+    fn options() -> Options {
+        Options {
+            name_indices: true,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn name_indices_should_use_ids() {
+        let source = "(module
+  (import \"env\" \"log\" (func $log (param i32)))
+  (global $g (mut i32) (i32.const 0))
+  (func $f (param $x i32) (local i32) (local $y i32)
+    (call 0 (local.get 0))
+    (local.set 2 (global.get 0))
+    (call 1 (local.get 1)))
+  (export \"f\" (func 1)))";
+        let expected = "(module
+\t(import \"env\" \"log\" (func $log (param i32)))
+\t(global $g (mut i32) (i32.const 0))
+\t(func $f (param $x i32)
+\t\t(local i32)
+\t\t(local $y i32)
+\t\t(local.get $x)
+\t\t(call $log)
+\t\t(global.get $g)
+\t\t(local.set $y)
+\t\t(local.get 1)
+\t\t(call $f)
+\t)
+\t(export \"f\" (func $f))
+)
+";
+        assert_eq!(fmt(source, options()), expected);
+    }
+
+    #[test]
+    fn name_indices_should_count_the_params_of_referenced_types() {
+        let source = "(module
+  (type (func (param i32)))
+  (func (type 0) (local $x i32)
+    (local.set 1 (local.get 0)))
+  (func (type 1) (local $y i32)
+    (local.set 0 (i32.const 0))))";
+        let expected = "(module
+\t(type (func (param i32)))
+\t(func (type 0)
+\t\t(local $x i32)
+\t\t(local.get 0)
+\t\t(local.set $x)
+\t)
+\t(func (type 1)
+\t\t(local $y i32)
+\t\t(i32.const 0)
+\t\t(local.set 0)
+\t)
+)
+";
+        assert_eq!(fmt(source, options()), expected);
+    }
+
+    #[test]
+    fn name_indices_should_respect_shadowed_labels() {
+        let source = "(module
+  (func
+    (block $a
+      (block $b
+        (block $a
+          (br 0)
+          (br 1)
+          (br 2)
+          (br 3))))))";
+        let expected = "(module
+\t(func
+\t\tblock $a
+\t\t\tblock $b
+\t\t\t\tblock $a
+\t\t\t\t\t(br $a)
+\t\t\t\t\t(br $b)
+\t\t\t\t\t(br 2)
+\t\t\t\t\t(br 3)
+\t\t\t\tend
+\t\t\tend
+\t\tend
+\t)
+)
+";
+        assert_eq!(fmt(source, options()), expected);
+    }
+
+    #[test]
+    fn synthesize_names_should_name_anonymous_items() {
+        let source = "(module
+  (import \"env\" \"memcpy\" (func (param i32 i32 i32)))
+  (type (func (param i32 i32) (result i64)))
+  (func (export \"main\") (type 0) (param i32 i32) (result i64) (local i32)
+    (block
+      (loop
+        (br_if 1 (local.get 2))
+        (call 0 (local.get 0) (local.get 1) (local.get 2))
+        (br 0)))
+    (i64.const 0))
+  (func $f2 (call 3))
+  (func (call 2)))";
+        let expected = "(module
+\t(import \"env\" \"memcpy\" (func $env.memcpy (param i32 i32 i32)))
+\t(type $i32_i32_to_i64 (func (param i32 i32) (result i64)))
+\t(func $main (export \"main\") (type $i32_i32_to_i64) (param $p0 i32) (param $p1 i32) (result i64)
+\t\t(local $l2 i32)
+\t\tblock $B0
+\t\t\tloop $L1
+\t\t\t\t(local.get $l2)
+\t\t\t\t(br_if $B0)
+\t\t\t\t(local.get $p0)
+\t\t\t\t(local.get $p1)
+\t\t\t\t(local.get $l2)
+\t\t\t\t(call $env.memcpy)
+\t\t\t\t(br $L1)
+\t\t\tend
+\t\tend
+\t\t(i64.const 0)
+\t)
+\t(func $f2
+\t\t(call $f3)
+\t)
+\t(func $f3
+\t\t(call $f2)
+\t)
+)
+";
+        let options = Options {
+            synthesize_names: true,
+            ..Options::default()
+        };
+        assert_eq!(fmt(source, options.clone()), expected);
+        assert_eq!(fmt(expected, options), expected);
+    }
+
+    #[test]
+    fn synthesize_names_should_name_params_of_referenced_types() {
+        let source = "(module
+  (type (func (param i32 i64)))
+  (func (type 0) (local f32)
+    (drop (local.get 1))
+    (drop (local.get 2))))";
+        let expected = "(module
+\t(type $i32_i64_to_void (func (param i32 i64)))
+\t(func $f0 (type $i32_i64_to_void) (param $p0 i32) (param $p1 i64)
+\t\t(local $l2 f32)
+\t\t(local.get $p1)
+\t\tdrop
+\t\t(local.get $l2)
+\t\tdrop
+\t)
+)
+";
+        let options = Options {
+            synthesize_names: true,
+            ..Options::default()
+        };
+        assert_eq!(fmt(source, options.clone()), expected);
+        assert_eq!(fmt(expected, options), expected);
+    }
+}
@@ -0,0 +1,407 @@
+use crate::options::FieldOrder;
+use wast::core::{
+    Expression, FuncKind, GlobalKind, Instruction, ItemKind, MemoryKind, ModuleField, TableKind,
+    TagKind, TagType,
+};
+
+/// The order in which the fields of a module are written, as indices into `fields`.
+///
+/// Fields are moved into the order of the sections of the binary format,
+/// keeping the relative order of the fields in each section, so that no index changes.
+/// The fields that `pinned` returns `true` for, and custom sections, stay where they are.
+/// So do separate exports next to inline exports, which are exported where their item is.
+/// If moving the fields would change an index or the order of the exports after all,
+/// returns why, and the fields are to be written in their original order.
+pub(crate) fn field_order(
+    fields: &[ModuleField],
+    order: FieldOrder,
+    pinned: impl Fn(usize) -> bool,
+) -> Result<Vec<usize>, String> {
+    let original = (0..fields.len()).collect::<Vec<_>>();
+    if order == FieldOrder::Preserve {
+        return Ok(original);
+    }
+    // Sorting exports by name reorders them anyway.
+    let keeps_exports = order == FieldOrder::Sections;
+    let pins_exports = keeps_exports && fields.iter().any(has_inline_exports);
+    let pinned = |index: usize| {
+        pinned(index)
+            || match fields[index] {
+                ModuleField::Custom(..) => true,
+                ModuleField::Export(..) => pins_exports,
+                _ => false,
+            }
+    };
+    let mut movable = original
+        .iter()
+        .copied()
+        .filter(|index| !pinned(*index))
+        .collect::<Vec<_>>();
+    movable.sort_by_key(|index| section(&fields[*index]));
+    if order == FieldOrder::Names {
+        sort_imports(fields, &mut movable);
+        let exports = movable
+            .iter()
+            .position(|index| section(&fields[*index]) == Section::Export);
+        if let Some(start) = exports {
+            let end = movable[start..]
+                .iter()
+                .position(|index| section(&fields[*index]) != Section::Export)
+                .map_or(movable.len(), |len| start + len);
+            movable[start..end].sort_by_key(|index| match &fields[*index] {
+                ModuleField::Export(export) => export.name,
+                _ => unreachable!("export"),
+            });
+        }
+    }
+
+    let mut movable = movable.into_iter();
+    let order = original
+        .iter()
+        .map(|index| match pinned(*index) {
+            true => *index,
+            false => movable.next().expect("movable field"),
+        })
+        .collect::<Vec<_>>();
+    // Segments defined inline in tables and memories, and types that type uses define implicitly,
+    // are numbered along with fields of other sections.
+    let moved = if !keeps_order(&order, |index| defines_elem(&fields[index])) {
+        "element segments"
+    } else if !keeps_order(&order, |index| defines_data(&fields[index])) {
+        "data segments"
+    } else if !keeps_order(&order, |index| defines_types(&fields[index])) {
+        "types"
+    } else if keeps_exports && !keeps_order(&order, |index| defines_exports(&fields[index])) {
+        "exports"
+    } else {
+        return Ok(order);
+    };
+    Err(format!(
+        "the fields were kept in their order, since moving them would reorder the {}",
+        moved
+    ))
+}
+
+/// The sections of the binary format, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Section {
+    Type,
+    Import,
+    Func,
+    Table,
+    Memory,
+    Tag,
+    Global,
+    Export,
+    Start,
+    Elem,
+    Data,
+    Custom,
+}
+
+fn section(field: &ModuleField) -> Section {
+    match field {
+        ModuleField::Type(..) | ModuleField::Rec(..) => Section::Type,
+        ModuleField::Import(..) => Section::Import,
+        ModuleField::Func(func) => match func.kind {
+            FuncKind::Import(..) => Section::Import,
+            FuncKind::Inline { .. } => Section::Func,
+        },
+        ModuleField::Table(table) => match table.kind {
+            TableKind::Import { .. } => Section::Import,
+            _ => Section::Table,
+        },
+        ModuleField::Memory(memory) => match memory.kind {
+            MemoryKind::Import { .. } => Section::Import,
+            _ => Section::Memory,
+        },
+        ModuleField::Tag(tag) => match tag.kind {
+            TagKind::Import(..) => Section::Import,
+            TagKind::Inline() => Section::Tag,
+        },
+        ModuleField::Global(global) => match global.kind {
+            GlobalKind::Import(..) => Section::Import,
+            GlobalKind::Inline(..) => Section::Global,
+        },
+        ModuleField::Export(..) => Section::Export,
+        ModuleField::Start(..) => Section::Start,
+        ModuleField::Elem(..) => Section::Elem,
+        ModuleField::Data(..) => Section::Data,
+        ModuleField::Custom(..) => Section::Custom,
+    }
+}
+
+/// Sort the imports among `movable` by module and name,
+/// as far as this keeps the order of the imports of each kind, which determines their indices.
+fn sort_imports(fields: &[ModuleField], movable: &mut [usize]) {
+    let start = match movable
+        .iter()
+        .position(|index| section(&fields[*index]) == Section::Import)
+    {
+        Some(start) => start,
+        None => return,
+    };
+    let end = movable[start..]
+        .iter()
+        .position(|index| section(&fields[*index]) != Section::Import)
+        .map_or(movable.len(), |len| start + len);
+    // The imports of each kind, in their original order.
+    let mut kinds: Vec<Vec<usize>> = Vec::new();
+    for index in &movable[start..end] {
+        let kind = import_kind(&fields[*index]);
+        match kinds
+            .iter_mut()
+            .find(|imports| import_kind(&fields[imports[0]]) == kind)
+        {
+            Some(imports) => imports.push(*index),
+            None => kinds.push(vec![*index]),
+        }
+    }
+    // Repeatedly take the first remaining import of the kind whose next import sorts first.
+    let mut heads = vec![0; kinds.len()];
+    for slot in movable[start..end].iter_mut() {
+        let kind = (0..kinds.len())
+            .filter(|kind| heads[*kind] < kinds[*kind].len())
+            .min_by_key(|kind| import_name(&fields[kinds[*kind][heads[*kind]]]))
+            .expect("remaining import");
+        *slot = kinds[kind][heads[kind]];
+        heads[kind] += 1;
+    }
+}
+
+/// The index space that an import belongs to.
+fn import_kind(field: &ModuleField) -> u8 {
+    match field {
+        ModuleField::Import(import) => match import.item.kind {
+            ItemKind::Func(..) => 0,
+            ItemKind::Table(..) => 1,
+            ItemKind::Memory(..) => 2,
+            ItemKind::Global(..) => 3,
+            ItemKind::Tag(..) => 4,
+        },
+        ModuleField::Func(..) => 0,
+        ModuleField::Table(..) => 1,
+        ModuleField::Memory(..) => 2,
+        ModuleField::Global(..) => 3,
+        _ => 4,
+    }
+}
+
+/// The module and name that an import is imported from.
+fn import_name<'a>(field: &ModuleField<'a>) -> (&'a str, &'a str) {
+    let import = match field {
+        ModuleField::Import(import) => return (import.module, import.field),
+        ModuleField::Func(func) => match func.kind {
+            FuncKind::Import(import) => import,
+            FuncKind::Inline { .. } => unreachable!("import"),
+        },
+        ModuleField::Table(table) => match table.kind {
+            TableKind::Import { import, .. } => import,
+            _ => unreachable!("import"),
+        },
+        ModuleField::Memory(memory) => match memory.kind {
+            MemoryKind::Import { import, .. } => import,
+            _ => unreachable!("import"),
+        },
+        ModuleField::Global(global) => match global.kind {
+            GlobalKind::Import(import) => import,
+            GlobalKind::Inline(..) => unreachable!("import"),
+        },
+        ModuleField::Tag(tag) => match tag.kind {
+            TagKind::Import(import) => import,
+            TagKind::Inline() => unreachable!("import"),
+        },
+        _ => unreachable!("import"),
+    };
+    (import.module, import.field)
+}
+
+/// Whether the fields for which `included` returns `true` appear in `order`
+/// in the same order as in the source.
+fn keeps_order(order: &[usize], included: impl Fn(usize) -> bool) -> bool {
+    let included = order
+        .iter()
+        .copied()
+        .filter(|index| included(*index))
+        .collect::<Vec<_>>();
+    included.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+fn defines_elem(field: &ModuleField) -> bool {
+    match field {
+        ModuleField::Elem(..) => true,
+        ModuleField::Table(table) => matches!(table.kind, TableKind::Inline { .. }),
+        _ => false,
+    }
+}
+
+fn defines_data(field: &ModuleField) -> bool {
+    match field {
+        ModuleField::Data(..) => true,
+        ModuleField::Memory(memory) => matches!(memory.kind, MemoryKind::Inline { .. }),
+        _ => false,
+    }
+}
+
+fn has_inline_exports(field: &ModuleField) -> bool {
+    let exports = match field {
+        ModuleField::Func(func) => &func.exports,
+        ModuleField::Table(table) => &table.exports,
+        ModuleField::Memory(memory) => &memory.exports,
+        ModuleField::Global(global) => &global.exports,
+        ModuleField::Tag(tag) => &tag.exports,
+        _ => return false,
+    };
+    !exports.names.is_empty()
+}
+
+fn defines_exports(field: &ModuleField) -> bool {
+    matches!(field, ModuleField::Export(..)) || has_inline_exports(field)
+}
+
+/// Whether a field contains a type use without an index,
+/// which may add a type to the end of the type section.
+fn defines_types(field: &ModuleField) -> bool {
+    match field {
+        ModuleField::Import(import) => match &import.item.kind {
+            ItemKind::Func(ty) | ItemKind::Tag(TagType::Exception(ty)) => ty.index.is_none(),
+            _ => false,
+        },
+        ModuleField::Func(func) => {
+            func.ty.index.is_none()
+                || match &func.kind {
+                    FuncKind::Inline { expression, .. } => expression_defines_types(expression),
+                    FuncKind::Import(..) => false,
+                }
+        }
+        ModuleField::Tag(tag) => {
+            let TagType::Exception(ty) = &tag.ty;
+            ty.index.is_none()
+        }
+        _ => false,
+    }
+}
+
+fn expression_defines_types(expression: &Expression) -> bool {
+    expression
+        .instrs
+        .iter()
+        .any(|instruction| match instruction {
+            Instruction::Block(bt)
+            | Instruction::Loop(bt)
+            | Instruction::If(bt)
+            | Instruction::Try(bt) => {
+                bt.ty.index.is_none()
+                    && bt
+                        .ty
+                        .inline
+                        .as_ref()
+                        .is_some_and(|ty| !ty.params.is_empty() || ty.results.len() > 1)
+            }
+            Instruction::CallIndirect(call) | Instruction::ReturnCallIndirect(call) => {
+                call.ty.index.is_none()
+            }
+            _ => false,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fmt::{fmt, fmt_with_summary};
+    use crate::options::{FieldOrder, Options};
+    use crate::verify::verify;
+    use pretty_assertions::assert_eq;
+
+    fn options(field_order: FieldOrder) -> Options {
+        Options {
+            field_order,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn sections_should_order_fields() {
+        let source = "(module
+  (export \"b\" (func 0))
+  (func (type 0))
+  (global i32 (i32.const 0))
+  (export \"a\" (global 0))
+  (import \"env\" \"g\" (func (type 0)))
+  (type (func)))";
+        let expected = "(module
+\t(type (func))
+\t(import \"env\" \"g\" (func (type 0)))
+\t(func (type 0))
+\t(global i32 (i32.const 0))
+\t(export \"b\" (func 0))
+\t(export \"a\" (global 0))
+)
+";
+        assert_eq!(fmt(source, options(FieldOrder::Sections)), expected);
+    }
+
+    #[test]
+    fn names_should_sort_imports_of_different_kinds_and_exports() {
+        let source = "(module
+  (import \"env\" \"c\" (func (param i32)))
+  (import \"env\" \"b\" (global i32))
+  (import \"env\" \"a\" (func (param i32)))
+  (export \"z\" (func 0))
+  (export \"y\" (func 1)))";
+        let expected = "(module
+\t(import \"env\" \"b\" (global i32))
+\t(import \"env\" \"c\" (func (param i32)))
+\t(import \"env\" \"a\" (func (param i32)))
+\t(export \"y\" (func 1))
+\t(export \"z\" (func 0))
+)
+";
+        assert_eq!(fmt(source, options(FieldOrder::Names)), expected);
+    }
+
+    #[test]
+    fn order_should_be_kept_if_indices_would_change() {
+        let source = "(module
+  (data (i32.const 0) \"a\")
+  (memory (data \"b\"))
+  (func))";
+        let expected = fmt(source, Options::default());
+        let (formatted, summary) = fmt_with_summary(source, options(FieldOrder::Sections)).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            summary.warnings,
+            ["the fields were kept in their order, since moving them would reorder the data segments"]
+        );
+    }
+
+    #[test]
+    fn sections_should_keep_separate_exports_next_to_inline_exports() {
+        let source = "(module
+  (export \"a\" (func 0))
+  (global (export \"b\") i32 (i32.const 0))
+  (func)
+  (export \"c\" (global 0)))";
+        let expected = "(module
+\t(export \"a\" (func 0))
+\t(func)
+\t(global (export \"b\") i32 (i32.const 0))
+\t(export \"c\" (global 0))
+)
+";
+        let formatted = fmt(source, options(FieldOrder::Sections));
+        assert_eq!(formatted, expected);
+        verify(source, &formatted, &options(FieldOrder::Sections)).unwrap();
+    }
+
+    #[test]
+    fn sections_should_keep_the_order_of_exports() {
+        let source = include_str!("../../tests/data/input/exports.wat");
+        let (formatted, summary) = fmt_with_summary(source, options(FieldOrder::Sections)).unwrap();
+        assert_eq!(formatted, fmt(source, Options::default()));
+        assert_eq!(
+            summary.warnings,
+            ["the fields were kept in their order, since moving them would reorder the exports"]
+        );
+        verify(source, &formatted, &options(FieldOrder::Sections)).unwrap();
+    }
+}
@@ -0,0 +1,392 @@
+use super::utils::id_is_gensym;
+use crate::options::Placement;
+use std::collections::HashSet;
+use std::mem;
+use wast::core::{
+    Export, ExportKind, Func, FuncKind, Global, GlobalKind, Import, InlineExport, InlineImport,
+    ItemKind, ItemSig, Memory, MemoryKind, ModuleField, ModuleKind, Table, TableKind,
+};
+use wast::token::{Id, Index, Span};
+
+/// Write imports in the functions, tables, memories and globals they define,
+/// or as separate `import` fields, as `placement` asks.
+pub(crate) fn place_imports(kind: &mut ModuleKind, placement: Placement) {
+    if let ModuleKind::Text(fields) = kind {
+        match placement {
+            Placement::Preserve => {}
+            Placement::Inline => inline_imports(fields),
+            Placement::Separate => separate_imports(fields),
+        }
+    }
+}
+
+/// Write exports in the functions, tables, memories and globals they export,
+/// or as separate `export` fields, as `placement` asks.
+pub(crate) fn place_exports(kind: &mut ModuleKind, placement: Placement) {
+    if let ModuleKind::Text(fields) = kind {
+        match placement {
+            Placement::Preserve => {}
+            Placement::Inline => inline_exports(fields),
+            Placement::Separate => separate_exports(fields),
+        }
+    }
+}
+
+fn inline_imports(fields: &mut Vec<ModuleField>) {
+    for field in fields.iter_mut() {
+        let import = match field {
+            ModuleField::Import(import) => import,
+            _ => continue,
+        };
+        let Import {
+            span,
+            module,
+            field: name,
+            item,
+        } = import.clone();
+        let import = InlineImport {
+            module,
+            field: name,
+        };
+        let (id, name, exports) = (item.id, item.name, InlineExport::default());
+        *field = match item.kind {
+            ItemKind::Func(ty) => ModuleField::Func(Func {
+                span,
+                id,
+                name,
+                exports,
+                kind: FuncKind::Import(import),
+                ty,
+            }),
+            ItemKind::Table(ty) => ModuleField::Table(Table {
+                span,
+                id,
+                name,
+                exports,
+                kind: TableKind::Import { import, ty },
+            }),
+            ItemKind::Memory(ty) => ModuleField::Memory(Memory {
+                span,
+                id,
+                name,
+                exports,
+                kind: MemoryKind::Import { import, ty },
+            }),
+            ItemKind::Global(ty) => ModuleField::Global(Global {
+                span,
+                id,
+                name,
+                exports,
+                ty,
+                kind: GlobalKind::Import(import),
+            }),
+            ItemKind::Tag(..) => continue,
+        };
+    }
+}
+
+/// Replace inline imports with `import` fields.
+/// The exports of imported items are written as separate fields too,
+/// since `import` fields cannot contain them.
+fn separate_imports(fields: &mut Vec<ModuleField>) {
+    let mut items = Items::default();
+    for field in mem::take(fields) {
+        let index = items.next(&field);
+        let (module, field_name, exports, item) = match field {
+            ModuleField::Func(Func {
+                span,
+                id,
+                name,
+                exports,
+                kind: FuncKind::Import(import),
+                ty,
+            }) => {
+                let kind = ItemKind::Func(ty);
+                (import.module, import.field, exports, (span, id, name, kind))
+            }
+            ModuleField::Table(Table {
+                span,
+                id,
+                name,
+                exports,
+                kind: TableKind::Import { import, ty },
+            }) => {
+                let kind = ItemKind::Table(ty);
+                (import.module, import.field, exports, (span, id, name, kind))
+            }
+            ModuleField::Memory(Memory {
+                span,
+                id,
+                name,
+                exports,
+                kind: MemoryKind::Import { import, ty },
+            }) => {
+                let kind = ItemKind::Memory(ty);
+                (import.module, import.field, exports, (span, id, name, kind))
+            }
+            ModuleField::Global(Global {
+                span,
+                id,
+                name,
+                exports,
+                ty,
+                kind: GlobalKind::Import(import),
+            }) => {
+                let kind = ItemKind::Global(ty);
+                (import.module, import.field, exports, (span, id, name, kind))
+            }
+            field => {
+                fields.push(field);
+                continue;
+            }
+        };
+        let (span, id, name, kind) = item;
+        let export_kind = item_kind(&kind);
+        fields.push(ModuleField::Import(Import {
+            span,
+            module,
+            field: field_name,
+            item: ItemSig {
+                span,
+                id,
+                name,
+                kind,
+            },
+        }));
+        let item = reference(id, index.expect("item index"), span);
+        fields.extend(export_fields(exports, export_kind, item, span));
+    }
+}
+
+/// Replace inline exports with `export` fields that follow the item they export.
+fn separate_exports(fields: &mut Vec<ModuleField>) {
+    let mut items = Items::default();
+    for mut field in mem::take(fields) {
+        let index = items.next(&field);
+        let exported = match &mut field {
+            ModuleField::Func(func) => {
+                Some((ExportKind::Func, func.span, func.id, &mut func.exports))
+            }
+            ModuleField::Table(table) => {
+                Some((ExportKind::Table, table.span, table.id, &mut table.exports))
+            }
+            ModuleField::Memory(memory) => Some((
+                ExportKind::Memory,
+                memory.span,
+                memory.id,
+                &mut memory.exports,
+            )),
+            ModuleField::Global(global) => Some((
+                ExportKind::Global,
+                global.span,
+                global.id,
+                &mut global.exports,
+            )),
+            _ => None,
+        };
+        let exports =
+            exported.map(|(kind, span, id, exports)| (kind, span, id, mem::take(exports)));
+        fields.push(field);
+        if let (Some((kind, span, id, exports)), Some(index)) = (exports, index) {
+            let item = reference(id, index, span);
+            fields.extend(export_fields(exports, kind, item, span));
+        }
+    }
+}
+
+/// Move `export` fields into the functions, tables, memories and globals they export,
+/// where those are defined by such a field rather than by an `import` field.
+fn inline_exports(fields: &mut Vec<ModuleField>) {
+    // The position of the field defining each item, by kind and index.
+    let mut items: [Vec<usize>; 4] = Default::default();
+    for (position, field) in fields.iter().enumerate() {
+        if let Some(slot) = field_kind(field).and_then(slot) {
+            items[slot].push(position);
+        }
+    }
+    let mut moves = Vec::new();
+    for (position, field) in fields.iter().enumerate() {
+        let export = match field {
+            ModuleField::Export(export) => export,
+            _ => continue,
+        };
+        let positions = match slot(export.kind) {
+            Some(slot) => &items[slot],
+            None => continue,
+        };
+        let target = match export.item {
+            Index::Num(index, ..) => positions.get(index as usize).copied(),
+            Index::Id(id) => {
+                let mut matches = positions.iter().filter(|target| {
+                    field_id(&fields[**target]).map(|id| id.name()) == Some(id.name())
+                });
+                match (matches.next(), matches.next()) {
+                    (Some(target), None) => Some(*target),
+                    _ => None,
+                }
+            }
+        };
+        if let Some(target) =
+            target.filter(|target| !matches!(fields[*target], ModuleField::Import(..)))
+        {
+            moves.push((position, target, export.name));
+        }
+    }
+    for (_, target, name) in &moves {
+        if let Some(exports) = exports_mut(&mut fields[*target]) {
+            exports.names.push(name);
+        }
+    }
+    let moved = moves
+        .iter()
+        .map(|(position, ..)| *position)
+        .collect::<HashSet<_>>();
+    let mut position = 0;
+    fields.retain(|_| {
+        position += 1;
+        !moved.contains(&(position - 1))
+    });
+}
+
+/// Counts the items defined by each field, to find the index of the item a field defines.
+#[derive(Default)]
+struct Items([u32; 4]);
+
+impl Items {
+    /// The index of the item that `field` defines, if it defines one.
+    fn next(&mut self, field: &ModuleField) -> Option<u32> {
+        let slot = field_kind(field).and_then(slot)?;
+        let index = self.0[slot];
+        self.0[slot] += 1;
+        Some(index)
+    }
+}
+
+/// The kind of item a field defines, if any.
+fn field_kind(field: &ModuleField) -> Option<ExportKind> {
+    match field {
+        ModuleField::Import(import) => Some(item_kind(&import.item.kind)),
+        ModuleField::Func(..) => Some(ExportKind::Func),
+        ModuleField::Table(..) => Some(ExportKind::Table),
+        ModuleField::Memory(..) => Some(ExportKind::Memory),
+        ModuleField::Global(..) => Some(ExportKind::Global),
+        _ => None,
+    }
+}
+
+fn item_kind(kind: &ItemKind) -> ExportKind {
+    match kind {
+        ItemKind::Func(..) => ExportKind::Func,
+        ItemKind::Table(..) => ExportKind::Table,
+        ItemKind::Memory(..) => ExportKind::Memory,
+        ItemKind::Global(..) => ExportKind::Global,
+        ItemKind::Tag(..) => ExportKind::Tag,
+    }
+}
+
+/// The index space of each kind of item that can be exported inline.
+fn slot(kind: ExportKind) -> Option<usize> {
+    match kind {
+        ExportKind::Func => Some(0),
+        ExportKind::Table => Some(1),
+        ExportKind::Memory => Some(2),
+        ExportKind::Global => Some(3),
+        ExportKind::Tag => None,
+    }
+}
+
+fn field_id<'src>(field: &ModuleField<'src>) -> Option<Id<'src>> {
+    match field {
+        ModuleField::Import(import) => import.item.id,
+        ModuleField::Func(func) => func.id,
+        ModuleField::Table(table) => table.id,
+        ModuleField::Memory(memory) => memory.id,
+        ModuleField::Global(global) => global.id,
+        _ => None,
+    }
+}
+
+fn exports_mut<'a, 'src>(field: &'a mut ModuleField<'src>) -> Option<&'a mut InlineExport<'src>> {
+    match field {
+        ModuleField::Func(func) => Some(&mut func.exports),
+        ModuleField::Table(table) => Some(&mut table.exports),
+        ModuleField::Memory(memory) => Some(&mut memory.exports),
+        ModuleField::Global(global) => Some(&mut global.exports),
+        _ => None,
+    }
+}
+
+/// A reference to an item by its id, or by its index if it has none.
+fn reference<'src>(id: Option<Id<'src>>, index: u32, span: Span) -> Index<'src> {
+    match id {
+        Some(id) if !id_is_gensym(&id) => Index::Id(id),
+        _ => Index::Num(index, span),
+    }
+}
+
+fn export_fields<'src>(
+    exports: InlineExport<'src>,
+    kind: ExportKind,
+    item: Index<'src>,
+    span: Span,
+) -> impl Iterator<Item = ModuleField<'src>> {
+    exports.names.into_iter().map(move |name| {
+        ModuleField::Export(Export {
+            span,
+            name,
+            kind,
+            item,
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fmt::fmt;
+    use crate::options::{Options, Placement};
+    use pretty_assertions::assert_eq;
+
+    const SOURCE: &str = "(module
+  (import \"env\" \"g\" (global i32))
+  (func (export \"a\") (import \"env\" \"f\"))
+  (func $run (export \"run\"))
+  (memory 1)
+  (export \"memory\" (memory 0)))";
+
+    #[test]
+    fn separate_should_hoist_imports_and_exports() {
+        let options = Options {
+            imports: Placement::Separate,
+            exports: Placement::Separate,
+            ..Options::default()
+        };
+        let expected = "(module
+\t(import \"env\" \"g\" (global i32))
+\t(import \"env\" \"f\" (func))
+\t(export \"a\" (func 0))
+\t(func $run)
+\t(export \"run\" (func $run))
+\t(memory 1)
+\t(export \"memory\" (memory 0))
+)
+";
+        assert_eq!(fmt(SOURCE, options), expected);
+    }
+
+    #[test]
+    fn inline_should_fold_imports_and_exports_into_items() {
+        let options = Options {
+            imports: Placement::Inline,
+            exports: Placement::Inline,
+            ..Options::default()
+        };
+        let expected = "(module
+\t(global (import \"env\" \"g\") i32)
+\t(func (export \"a\") (import \"env\" \"f\"))
+\t(func $run (export \"run\"))
+\t(memory (export \"memory\") 1)
+)
+";
+        assert_eq!(fmt(SOURCE, options), expected);
+    }
+}
@@ -3,37 +3,49 @@ use wast::token::{Float32, Float64};
 
 impl Fmt for u32 {
     fn fmt(&self, formatter: &mut Formatter) {
-        formatter.write(&self.to_string());
+        let hex = || format!("0x{:x}", self);
+        formatter.write(&number(formatter, self.to_string(), hex));
     }
 }
 
 impl Fmt for u64 {
     fn fmt(&self, formatter: &mut Formatter) {
-        formatter.write(&self.to_string());
+        let hex = || format!("0x{:x}", self);
+        formatter.write(&number(formatter, self.to_string(), hex));
     }
 }
 
 impl Fmt for i32 {
     fn fmt(&self, formatter: &mut Formatter) {
-        formatter.write(&self.to_string());
+        let hex = || match self {
+            n if *n < 0 => format!("-0x{:x}", n.unsigned_abs()),
+            n => format!("0x{:x}", n),
+        };
+        formatter.write(&number(formatter, self.to_string(), hex));
     }
 }
 
 impl Fmt for i64 {
     fn fmt(&self, formatter: &mut Formatter) {
-        formatter.write(&self.to_string());
+        let hex = || match self {
+            n if *n < 0 => format!("-0x{:x}", n.unsigned_abs()),
+            n => format!("0x{:x}", n),
+        };
+        formatter.write(&number(formatter, self.to_string(), hex));
     }
 }
 
 impl Fmt for f32 {
     fn fmt(&self, formatter: &mut Formatter) {
-        formatter.write(&self.to_string());
+        let exponent = || format!("{:e}", self);
+        formatter.write(&number(formatter, self.to_string(), exponent));
     }
 }
 
 impl Fmt for f64 {
     fn fmt(&self, formatter: &mut Formatter) {
-        formatter.write(&self.to_string());
+        let exponent = || format!("{:e}", self);
+        formatter.write(&number(formatter, self.to_string(), exponent));
     }
 }
 
@@ -49,7 +61,21 @@ impl Fmt for &Float64 {
     }
 }
 
-impl<'src> Fmt for &'src str {
+/// The usual notation of a number, or the alternative notation
+/// if it is shorter and the output is minified.
+fn number(formatter: &Formatter, usual: String, alternative: impl FnOnce() -> String) -> String {
+    if !formatter.options.minify {
+        return usual;
+    }
+    let alternative = alternative();
+    if alternative.len() < usual.len() {
+        alternative
+    } else {
+        usual
+    }
+}
+
+impl Fmt for &str {
     fn fmt(&self, formatter: &mut Formatter) {
         formatter.write("\"");
         formatter.write(self);
@@ -57,7 +83,7 @@ impl<'src> Fmt for &'src str {
     }
 }
 
-impl<'src> Fmt for &Vec<&'src str> {
+impl Fmt for &Vec<&str> {
     fn fmt(&self, formatter: &mut Formatter) {
         let mut iter = self.iter();
         if let Some(name) = iter.next() {
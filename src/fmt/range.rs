@@ -0,0 +1,254 @@
+use super::context::ModuleContext;
+use super::ignore::{Directive, Directives};
+use super::scan::{Extent, Scan};
+use super::utils::matching_paren;
+use super::{parse_error, prepare, Formatter};
+use crate::error::Error;
+use crate::options::{ExpressionStyle, Options};
+use std::ops::Range;
+use wast::core::{ModuleField, ModuleKind};
+use wast::lexer::Token;
+use wast::parser::{parse, ParseBuffer};
+use wast::Wat;
+
+/// Format the module fields of `source` that overlap `range`,
+/// a range of byte offsets into `source`.
+///
+/// Everything outside of the overlapping fields is left as it is.
+/// Fields are the smallest unit that is formatted,
+/// so a function overlapping the range is formatted as a whole.
+pub fn fmt_range(source: &str, range: Range<usize>, options: Options) -> Result<String, Error> {
+    let (mut ids, mut ids_buffer) = (String::new(), None);
+    let buffer = ParseBuffer::new(source).map_err(|err| parse_error(err, source))?;
+    let wat = parse::<Wat>(&buffer).map_err(|err| parse_error(err, source))?;
+    let mut module = match wat {
+        Wat::Module(module) => module,
+        Wat::Component(..) => return Err(Error::Unsupported("components".to_string())),
+    };
+    let mut directives = Directives::new(source, &module.kind);
+    prepare(&mut module, &options, &mut ids, &mut ids_buffer)
+        .map_err(|err| parse_error(err, source))?;
+    let fields = match &module.kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return Err(Error::Unsupported("binary modules".to_string())),
+    };
+    let layout = match FieldLayout::scan(source) {
+        Some(layout) if layout.spans.len() == fields.len() => layout,
+        _ => {
+            return Err(Error::Unsupported(
+                "range formatting of this module".to_string(),
+            ))
+        }
+    };
+
+    let context = ModuleContext::new(fields);
+    directives.follow(&module.kind)?;
+    let mut output = source.to_string();
+    // Replace from the end, so that earlier offsets remain valid.
+    for (index, (field, span)) in fields.iter().zip(&layout.spans).enumerate().rev() {
+        if !overlaps(span, &range) || directives.get(index) != Directive::Format {
+            continue;
+        }
+        let mut formatter = Formatter::with_options(&options);
+        formatter.module = context.clone();
+        if options.expression_style == ExpressionStyle::Preserve {
+            formatter.source = source.to_string();
+        }
+        for _ in 0..layout.depth {
+            formatter.indent();
+        }
+        formatter.fmt(field);
+        if let Some(construct) = formatter.unsupported.take() {
+            return Err(Error::Unsupported(construct));
+        }
+        let formatted: String = formatter.into();
+        // The indentation of a field that starts its line is replaced along with the field,
+        // so that all of its lines are indented alike.
+        let (replaced, formatted) = match (whole_lines(source, span), line_start(source, span)) {
+            (Some(lines), _) => (lines, formatted.as_str()),
+            (None, Some(start)) => (start..span.end, formatted.trim_end()),
+            (None, None) => (span.clone(), formatted.trim()),
+        };
+        output.replace_range(replaced, formatted);
+    }
+    Ok(output)
+}
+
+/// Determine if a field overlaps a range, where an empty range
+/// overlaps the field it is positioned within.
+fn overlaps(span: &Range<usize>, range: &Range<usize>) -> bool {
+    span.start < range.end.max(range.start + 1) && range.start < span.end
+}
+
+/// The lines that a field spans, including the line ending,
+/// if the field is the only thing on those lines.
+fn whole_lines(source: &str, span: &Range<usize>) -> Option<Range<usize>> {
+    let start = line_start(source, span)?;
+    let rest = &source[span.end..];
+    let end = match rest.find('\n') {
+        Some(i) => span.end + i + 1,
+        None => source.len(),
+    };
+    if !source[span.end..end].trim().is_empty() {
+        return None;
+    }
+    Some(start..end)
+}
+
+/// The start of the line that a field starts on, if only whitespace precedes the field.
+fn line_start(source: &str, span: &Range<usize>) -> Option<usize> {
+    let start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    source[start..span.start].trim().is_empty().then_some(start)
+}
+
+/// The byte range of each module field in `source`,
+/// or `None` if the fields cannot be located.
+pub(crate) fn field_spans(source: &str) -> Option<Vec<Range<usize>>> {
+    FieldLayout::scan(source).map(|layout| layout.spans)
+}
+
+/// The index into `spans` of the source field that each of `fields` was parsed from,
+/// or `None` for fields that were added, such as the types that type uses define.
+///
+/// Fields that were moved or split out of another field, such as inline exports,
+/// are found by their position in the source, which lies within the field they came from.
+pub(crate) fn field_origins(spans: &[Range<usize>], fields: &[ModuleField]) -> Vec<Option<usize>> {
+    fields
+        .iter()
+        .map(|field| {
+            let offset = field_offset(field);
+            // Added fields are positioned at the start of the source, before any field keyword.
+            spans
+                .iter()
+                .position(|span| span.start < offset && offset < span.end)
+        })
+        .collect()
+}
+
+/// The offset of the keyword of `field` in the source.
+fn field_offset(field: &ModuleField) -> usize {
+    let span = match field {
+        ModuleField::Type(ty) => ty.span,
+        ModuleField::Rec(rec) => rec.span,
+        ModuleField::Import(import) => import.span,
+        ModuleField::Func(func) => func.span,
+        ModuleField::Table(table) => table.span,
+        ModuleField::Memory(memory) => memory.span,
+        ModuleField::Global(global) => global.span,
+        ModuleField::Export(export) => export.span,
+        ModuleField::Start(index) => index.span(),
+        ModuleField::Elem(elem) => elem.span,
+        ModuleField::Data(data) => data.span,
+        ModuleField::Tag(tag) => tag.span,
+        ModuleField::Custom(custom) => custom.span,
+    };
+    span.offset()
+}
+
+/// The positions of the module fields in the source code.
+struct FieldLayout {
+    /// The byte range of each field, from its opening to its closing parenthesis.
+    spans: Vec<Range<usize>>,
+    /// The number of levels that fields are indented by.
+    depth: usize,
+}
+
+impl FieldLayout {
+    fn scan(source: &str) -> Option<Self> {
+        let tokens = Scan::new(source, Extent::Tokens).complete()?;
+        let (mut i, depth) = match (tokens.first(), tokens.get(1)) {
+            (Some((Token::LParen(..), _)), Some((Token::Keyword("module"), _))) => (2, 1),
+            _ => (0, 0),
+        };
+        if depth == 1 {
+            if let Some((Token::Id(..), _)) = tokens.get(i) {
+                i += 1;
+            }
+        }
+        let mut spans = Vec::new();
+        while let Some((token, start)) = tokens.get(i) {
+            match token {
+                Token::LParen(..) => {
+                    let close = matching_paren(&tokens, i)?;
+                    // Annotations other than custom sections are not fields.
+                    let is_field = match tokens.get(i + 1) {
+                        Some((Token::Reserved(annotation), _)) => {
+                            !annotation.starts_with('@') || *annotation == "@custom"
+                        }
+                        _ => true,
+                    };
+                    if is_field {
+                        spans.push(*start..tokens[close].1 + 1);
+                    }
+                    i = close + 1;
+                }
+                Token::RParen(..) if depth == 1 => break,
+                _ => return None,
+            }
+        }
+        Some(FieldLayout { spans, depth })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fmt_range;
+    use crate::options::Options;
+    use pretty_assertions::assert_eq;
+
+    const SOURCE: &str = "(module
+  (func $a (result i32) (i32.add (i32.const 1) (i32.const 2)))
+  (func $b (result i32)   (i32.const 3))
+  (global i32 (i32.const 4))
+)
+";
+
+    #[test]
+    fn fmt_range_should_only_format_overlapping_fields() {
+        let start = SOURCE.find("(func $b").unwrap() + 3;
+        let actual = fmt_range(SOURCE, start..start + 4, Options::default()).unwrap();
+        let expected = "(module
+  (func $a (result i32) (i32.add (i32.const 1) (i32.const 2)))
+\t(func $b (result i32)
+\t\t(i32.const 3)
+\t)
+  (global i32 (i32.const 4))
+)
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn fmt_range_should_format_fields_sharing_a_line_in_place() {
+        let source = "(module (global i32 (i32.const   4)) (global i32 (i32.const 5)))";
+        let actual = fmt_range(source, 8..9, Options::default()).unwrap();
+        assert_eq!(
+            actual,
+            "(module (global i32 (i32.const 4)) (global i32 (i32.const 5)))"
+        );
+    }
+
+    #[test]
+    fn fmt_range_should_indent_every_line_of_a_field_alike() {
+        let source = "(module
+  (global i32 (i32.const 4))
+  (func $b (result i32)   (i32.const 3)))
+";
+        let start = source.find("(func $b").unwrap();
+        let actual = fmt_range(source, start..start + 1, Options::default()).unwrap();
+        let expected = "(module
+  (global i32 (i32.const 4))
+\t(func $b (result i32)
+\t\t(i32.const 3)
+\t))
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn fmt_range_should_leave_source_unchanged_outside_fields() {
+        let end = SOURCE.len();
+        let actual = fmt_range(SOURCE, end..end, Options::default()).unwrap();
+        assert_eq!(actual, SOURCE);
+    }
+}
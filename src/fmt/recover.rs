@@ -0,0 +1,311 @@
+use super::context::ModuleContext;
+use super::scan::{Extent, Scan};
+use super::utils::matching_paren;
+use super::{prepare, try_fmt, Formatter};
+use crate::options::{ExpressionStyle, Options};
+use std::fmt;
+use std::ops::Range;
+use wast::core::ModuleKind;
+use wast::lexer::Token;
+use wast::parser::{parse, ParseBuffer};
+use wast::Wat;
+
+/// The text that module fields are wrapped in to parse them on their own.
+const PREFIX: &str = "(module\n";
+const SUFFIX: &str = "\n)";
+
+/// A syntax error found while formatting source code that does not parse.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The byte offset of the error in the source.
+    pub offset: usize,
+    /// The line of the error, counting from 1.
+    pub line: usize,
+    /// The column of the error in characters, counting from 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(source: &str, offset: usize, message: String) -> Self {
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Diagnostic {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Format `.wat` source code that may contain syntax errors.
+///
+/// If `source` parses, this is the same as [`try_fmt`].
+/// Otherwise the module is split at its top-level parenthesized fields,
+/// every field that parses on its own is formatted, and the others are left as they are.
+/// Fields that cannot be formatted are left as they are too.
+/// Returns the formatted code together with the syntax errors.
+pub fn fmt_recover(source: &str, options: Options) -> (String, Vec<Diagnostic>) {
+    if let Ok(formatted) = try_fmt(source, options.clone()) {
+        return (formatted, Vec::new());
+    }
+    let layout = Layout::scan(source);
+    let mut diagnostics = Vec::new();
+    let mut parsed = Vec::new();
+    for chunk in &layout.chunks {
+        let text = &source[chunk.clone()];
+        if layout.unclosed == Some(chunk.start) {
+            let message = "unclosed parenthesis".to_string();
+            diagnostics.push(Diagnostic::new(source, chunk.start, message));
+            parsed.push(false);
+            continue;
+        }
+        match parse_fields(text) {
+            Ok(()) => parsed.push(true),
+            Err(err) => {
+                let offset = err
+                    .span()
+                    .offset()
+                    .saturating_sub(PREFIX.len())
+                    .min(text.len());
+                diagnostics.push(Diagnostic::new(source, chunk.start + offset, err.message()));
+                parsed.push(false);
+            }
+        }
+    }
+
+    // Parse the fields that parse on their own together, so that they can refer to each other.
+    let mut combined = String::from(PREFIX);
+    for (chunk, _) in layout
+        .chunks
+        .iter()
+        .zip(&parsed)
+        .filter(|(_, parsed)| **parsed)
+    {
+        combined.push_str(&source[chunk.clone()]);
+        combined.push('\n');
+    }
+    combined.push_str(SUFFIX);
+    let (mut ids, mut ids_buffer) = (String::new(), None);
+    let buffer = match ParseBuffer::new(&combined) {
+        Ok(buffer) => buffer,
+        Err(..) => return (source.to_string(), diagnostics),
+    };
+    let mut module = match parse::<Wat>(&buffer) {
+        Ok(Wat::Module(module)) => module,
+        _ => return (source.to_string(), diagnostics),
+    };
+    if prepare(&mut module, &options, &mut ids, &mut ids_buffer).is_err() {
+        return (source.to_string(), diagnostics);
+    }
+    let fields = match &module.kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return (source.to_string(), diagnostics),
+    };
+    if fields.len() != parsed.iter().filter(|parsed| **parsed).count() {
+        return (source.to_string(), diagnostics);
+    }
+
+    let mut formatter = Formatter::with_options(&options);
+    formatter.module = ModuleContext::new(fields);
+    if options.expression_style == ExpressionStyle::Preserve {
+        formatter.source = combined.clone();
+    }
+    if let Some(header) = &layout.header {
+        formatter.write_verbatim(&source[header.clone()]);
+        formatter.indent();
+    }
+    let mut fields = fields.iter();
+    for (chunk, parsed) in layout.chunks.iter().zip(parsed) {
+        let text = &source[chunk.clone()];
+        if !parsed {
+            formatter.write_verbatim(text);
+            continue;
+        }
+        let start = formatter.docs.len();
+        formatter.fmt(fields.next().expect("parsed field"));
+        if formatter.unsupported.take().is_some() {
+            formatter.docs.truncate(start);
+            formatter.write_verbatim(text);
+        }
+    }
+    if layout.header.is_some() {
+        formatter.deindent();
+        if layout.closed {
+            formatter.write_line(")");
+        }
+    }
+    (formatter.into(), diagnostics)
+}
+
+/// Parse the module fields in `text` without the rest of the module.
+fn parse_fields(text: &str) -> Result<(), wast::Error> {
+    let wrapped = format!("{}{}{}", PREFIX, text, SUFFIX);
+    let buffer = ParseBuffer::new(&wrapped)?;
+    match parse::<Wat>(&buffer)? {
+        Wat::Module(..) => Ok(()),
+        Wat::Component(..) => Err(wast::Error::new(
+            wast::token::Span::from_offset(PREFIX.len()),
+            "expected module fields".to_string(),
+        )),
+    }
+}
+
+/// The top-level fields of a module that may not parse.
+struct Layout {
+    /// The byte range of `(module` and the id of the module,
+    /// if the fields are wrapped in `(module ...)`.
+    header: Option<Range<usize>>,
+    /// Whether the parenthesis that closes the module is present.
+    closed: bool,
+    /// The byte ranges of the fields, and of any text between them that is not a field.
+    chunks: Vec<Range<usize>>,
+    /// The offset of a parenthesis that is never closed, if any.
+    unclosed: Option<usize>,
+}
+
+impl Layout {
+    fn scan(source: &str) -> Self {
+        // Everything following a token that cannot be read is left as it is.
+        let Scan { tokens, error, .. } = Scan::new(source, Extent::Tokens);
+        let wrapped = matches!(
+            (tokens.first(), tokens.get(1)),
+            (
+                Some((Token::LParen(..), _)),
+                Some((Token::Keyword("module"), _))
+            )
+        );
+        let mut i = 0;
+        let mut header = None;
+        if wrapped {
+            i = 2;
+            if let Some((Token::Id(..), _)) = tokens.get(i) {
+                i += 1;
+            }
+            let (last, end) = &tokens[i - 1];
+            header = Some(tokens[0].1..end + last.src().len());
+        }
+        let mut layout = Layout {
+            header,
+            closed: false,
+            chunks: Vec::new(),
+            unclosed: None,
+        };
+        while let Some((token, start)) = tokens.get(i) {
+            let end = match token {
+                Token::RParen(..) if wrapped => {
+                    layout.closed = true;
+                    break;
+                }
+                Token::LParen(..) => match matching_paren(&tokens, i) {
+                    Some(close) => {
+                        i = close + 1;
+                        tokens[close].1 + 1
+                    }
+                    None => {
+                        layout.unclosed = Some(*start);
+                        i = tokens.len();
+                        source.len()
+                    }
+                },
+                // Text that is not a field extends to the next parenthesis.
+                _ => {
+                    i += 1;
+                    while let Some((token, _)) = tokens.get(i) {
+                        if let Token::LParen(..) | Token::RParen(..) = token {
+                            break;
+                        }
+                        i += 1;
+                    }
+                    match tokens.get(i) {
+                        Some((_, offset)) => *offset,
+                        None => source.len(),
+                    }
+                }
+            };
+            layout.chunks.push(*start..source[..end].trim_end().len());
+        }
+        if let (Some(error), false) = (error, layout.closed) {
+            if layout.chunks.last().is_none_or(|chunk| chunk.end <= error) {
+                layout.chunks.push(error..source.trim_end().len());
+            }
+        }
+        layout
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fmt_recover;
+    use crate::options::Options;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn recover_should_format_fields_that_parse() {
+        let source = "(module
+  (global $a i32   (i32.const 1))
+  (func $f (result i32) (i32.add (i32.const 1)))
+  (func $g   (i32.const))
+  (global $b i32   (i32.const 2)))";
+        let (formatted, diagnostics) = fmt_recover(source, Options::default());
+        let expected = "(module
+\t(global $a i32 (i32.const 1))
+\t(func $f (result i32)
+\t\t(i32.const 1)
+\t\ti32.add
+\t)
+\t(func $g   (i32.const))
+\t(global $b i32 (i32.const 2))
+)
+";
+        assert_eq!(formatted, expected);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 4);
+        assert_eq!(diagnostics[0].column, 24);
+    }
+
+    #[test]
+    fn recover_should_keep_unclosed_fields() {
+        let source = "(module\n  (global $a i32   (i32.const 1))\n  (func $f (result i32)\n";
+        let (formatted, diagnostics) = fmt_recover(source, Options::default());
+        assert_eq!(
+            formatted,
+            "(module\n\t(global $a i32 (i32.const 1))\n\t(func $f (result i32)\n"
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].to_string(), "3:3: unclosed parenthesis");
+    }
+
+    #[test]
+    fn recover_should_format_valid_source_normally() {
+        let (formatted, diagnostics) = fmt_recover("(module)", Options::default());
+        assert_eq!(formatted, "(module\n)\n");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn recover_should_keep_the_module_id() {
+        let source = "(module $m
+  (global $a i32   (i32.const 1))
+  (func (i32.const)))";
+        let expected = "(module $m
+\t(global $a i32 (i32.const 1))
+\t(func (i32.const))
+)
+";
+        assert_eq!(fmt_recover(source, Options::default()).0, expected);
+        let source = "(module $m (global $a i32   (i32.const 1)))";
+        let expected = "(module $m
+\t(global $a i32 (i32.const 1))
+)
+";
+        assert_eq!(fmt_recover(source, Options::default()).0, expected);
+    }
+}
@@ -0,0 +1,107 @@
+use wast::lexer::{Lexer, Token};
+
+/// Which tokens of a source are scanned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Extent {
+    /// The tokens that are not whitespace or comments.
+    Tokens,
+    /// Every token, including whitespace and comments.
+    Trivia,
+    /// The tokens that are not whitespace or comments, up to the parenthesis
+    /// that closes the one that the source starts within.
+    Enclosed,
+}
+
+/// The tokens of a source paired with their byte offsets, as far as they can be read.
+pub(crate) struct Scan<'a> {
+    pub(crate) tokens: Vec<(Token<'a>, usize)>,
+    /// The offset of the first text that cannot be read as a token, where the scan stopped.
+    pub(crate) error: Option<usize>,
+    /// Whether the scan ended at the end of its extent.
+    complete: bool,
+}
+
+impl<'a> Scan<'a> {
+    pub(crate) fn new(source: &'a str, extent: Extent) -> Self {
+        let mut tokens = Vec::new();
+        let mut depth = 1usize;
+        for token in Lexer::new(source) {
+            let token = match token {
+                Ok(token) => token,
+                Err(err) => {
+                    return Scan {
+                        tokens,
+                        error: Some(err.span().offset()),
+                        complete: false,
+                    }
+                }
+            };
+            match token {
+                Token::Whitespace(..) | Token::LineComment(..) | Token::BlockComment(..)
+                    if extent != Extent::Trivia =>
+                {
+                    continue
+                }
+                Token::LParen(..) => depth += 1,
+                Token::RParen(..) => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            let offset = token.src().as_ptr() as usize - source.as_ptr() as usize;
+            tokens.push((token, offset));
+            if extent == Extent::Enclosed && depth == 0 {
+                return Scan {
+                    tokens,
+                    error: None,
+                    complete: true,
+                };
+            }
+        }
+        Scan {
+            tokens,
+            error: None,
+            complete: extent != Extent::Enclosed,
+        }
+    }
+
+    /// The tokens, or `None` if not all of them could be read.
+    pub(crate) fn complete(self) -> Option<Vec<(Token<'a>, usize)>> {
+        match self.complete {
+            true => Some(self.tokens),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Extent, Scan};
+    use pretty_assertions::assert_eq;
+
+    fn offsets(scan: Scan) -> Vec<usize> {
+        scan.tokens.iter().map(|(_, offset)| *offset).collect()
+    }
+
+    #[test]
+    fn enclosed_should_end_at_the_closing_parenthesis() {
+        let source = "func (nop) ;; a\n) (func)";
+        assert_eq!(
+            offsets(Scan::new(source, Extent::Enclosed)),
+            [0, 5, 6, 9, 16]
+        );
+        assert_eq!(Scan::new("func (nop)", Extent::Enclosed).complete(), None);
+    }
+
+    #[test]
+    fn trivia_should_include_whitespace_and_comments() {
+        let source = "(; a ;) nop";
+        assert_eq!(offsets(Scan::new(source, Extent::Trivia)), [0, 7, 8]);
+        assert_eq!(offsets(Scan::new(source, Extent::Tokens)), [8]);
+    }
+
+    #[test]
+    fn scan_should_stop_at_text_that_cannot_be_read() {
+        let scan = Scan::new("(nop) \"a", Extent::Tokens);
+        assert_eq!(scan.error, Some(8));
+        assert_eq!(offsets(scan), [0, 1, 4]);
+    }
+}
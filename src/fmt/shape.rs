@@ -0,0 +1,415 @@
+use super::scan::{Extent, Scan};
+use super::utils::matching_paren;
+use std::ops::Range;
+use wast::core::Expression;
+use wast::lexer::Token;
+use wast::parser::{parse, ParseBuffer};
+
+/// A run of consecutive instructions in a function body
+/// that were either all written folded or all written flat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub folded: bool,
+    /// The number of instructions in the run, once unfolded.
+    pub len: usize,
+}
+
+/// The fields that may precede the body of a function.
+const FUNC_HEADER_KEYWORDS: [&str; 6] = ["export", "import", "type", "param", "result", "local"];
+
+/// The parenthesized immediates of flat instructions.
+const IMMEDIATE_KEYWORDS: [&str; 3] = ["type", "param", "result"];
+
+/// Split the body of the function whose `func` keyword is at `offset` in `source`
+/// into runs of folded and flat instructions.
+///
+/// Returns `None` if the source cannot be read back.
+pub fn body_segments(source: &str, offset: usize) -> Option<Vec<Segment>> {
+    let rest = source.get(offset..)?;
+    let tokens = tokens(rest)?;
+    let mut i = body_start(&tokens)?;
+
+    let mut segments = Vec::new();
+    // The byte range of the current run of flat instructions.
+    let mut flat: Option<(usize, usize)> = None;
+    loop {
+        let (token, start) = tokens.get(i)?;
+        let end = match token {
+            Token::RParen(..) => break,
+            Token::LParen(..) => {
+                let close = matching_paren(&tokens, i)?;
+                let end = tokens[close].1 + 1;
+                let is_immediate = match tokens.get(i + 1) {
+                    Some((Token::Keyword(keyword), _)) => IMMEDIATE_KEYWORDS.contains(keyword),
+                    _ => false,
+                };
+                i = close + 1;
+                if !is_immediate {
+                    if let Some((start, end)) = flat.take() {
+                        segments.push(segment(&rest[start..end], false)?);
+                    }
+                    segments.push(segment(&rest[*start..end], true)?);
+                    continue;
+                }
+                end
+            }
+            token => {
+                i += 1;
+                start + token.src().len()
+            }
+        };
+        flat = Some((flat.map_or(*start, |(start, _)| start), end));
+    }
+    if let Some((start, end)) = flat {
+        segments.push(segment(&rest[start..end], false)?);
+    }
+    Some(segments)
+}
+
+/// The byte offset of the name of each instruction in the body of the function
+/// whose `func` keyword is at `offset` in `source`, in the order that the instructions are parsed.
+/// Instructions that are implied by folded expressions,
+/// such as the `end` of a folded block, have no offset.
+///
+/// Returns `None` if the source cannot be read back.
+pub fn instruction_offsets(source: &str, offset: usize) -> Option<Vec<Option<usize>>> {
+    let rest = source.get(offset..)?;
+    let tokens = tokens(rest)?;
+    let names = instruction_names(&tokens)?;
+    Some(
+        names
+            .into_iter()
+            .map(|name| Some(offset + tokens[name?].1))
+            .collect(),
+    )
+}
+
+/// The byte range of the name and immediates of each instruction in the body of the function
+/// whose `func` keyword is at `offset` in `source`, like [`instruction_offsets`].
+/// The operands of folded instructions are not part of the range.
+pub fn instruction_spans(source: &str, offset: usize) -> Option<Vec<Option<Range<usize>>>> {
+    let rest = source.get(offset..)?;
+    let tokens = tokens(rest)?;
+    let names = instruction_names(&tokens)?;
+    Some(
+        names
+            .into_iter()
+            .map(|name| {
+                let name = name?;
+                let (last, start) = (immediates_end(&tokens, name)? - 1, tokens[name].1);
+                let end = tokens[last].1 + tokens[last].0.src().len();
+                Some(offset + start..offset + end)
+            })
+            .collect(),
+    )
+}
+
+/// The index of the token that names each instruction of the function body in `tokens`.
+fn instruction_names(tokens: &[(Token<'_>, usize)]) -> Option<Vec<Option<usize>>> {
+    let mut walker = Walker {
+        tokens,
+        i: body_start(tokens)?,
+        names: Vec::new(),
+    };
+    walker.instructions()?;
+    Some(walker.names)
+}
+
+/// The index of the token after the immediates of the instruction named at `name`.
+fn immediates_end(tokens: &[(Token<'_>, usize)], name: usize) -> Option<usize> {
+    let mut i = name + 1;
+    loop {
+        match tokens.get(i)? {
+            (Token::RParen(..), _) => return Some(i),
+            (Token::LParen(..), _) => match tokens.get(i + 1)? {
+                (Token::Keyword(keyword), _) if IMMEDIATE_KEYWORDS.contains(keyword) => {
+                    i = matching_paren(tokens, i)? + 1
+                }
+                _ => return Some(i),
+            },
+            (Token::Keyword(keyword), _) if is_instruction(keyword) => return Some(i),
+            _ => i += 1,
+        }
+    }
+}
+
+fn is_instruction(keyword: &str) -> bool {
+    keyword.contains('.') || PLAIN_INSTRUCTIONS.contains(&keyword)
+}
+
+/// The index of the first token of the body of a function,
+/// after its identifier, exports, imports and type.
+fn body_start(tokens: &[(Token<'_>, usize)]) -> Option<usize> {
+    let mut i = match tokens.first() {
+        Some((Token::Keyword("func"), _)) => 1,
+        _ => return None,
+    };
+    if let Some((Token::Id(..), _)) = tokens.get(i) {
+        i += 1;
+    }
+    while let (Some((Token::LParen(..), _)), Some((head, _))) = (tokens.get(i), tokens.get(i + 1)) {
+        let is_header = match head {
+            Token::Keyword(keyword) => FUNC_HEADER_KEYWORDS.contains(keyword),
+            Token::Reserved(annotation) => annotation.starts_with('@'),
+            _ => false,
+        };
+        if !is_header {
+            break;
+        }
+        i = matching_paren(tokens, i)? + 1;
+    }
+    Some(i)
+}
+
+/// The names of instructions that do not contain a `.`.
+/// Other keywords without one are immediates, such as `func` in `ref.null func`.
+const PLAIN_INSTRUCTIONS: [&str; 21] = [
+    "unreachable",
+    "nop",
+    "block",
+    "loop",
+    "if",
+    "else",
+    "end",
+    "br",
+    "br_if",
+    "br_table",
+    "return",
+    "call",
+    "call_indirect",
+    "return_call",
+    "return_call_indirect",
+    "drop",
+    "select",
+    "try",
+    "catch",
+    "catch_all",
+    "delegate",
+];
+
+/// Walks the tokens of a function body in the order that `wast` unfolds them.
+struct Walker<'t, 'a> {
+    tokens: &'t [(Token<'a>, usize)],
+    i: usize,
+    /// The index of the token that names each instruction.
+    names: Vec<Option<usize>>,
+}
+
+impl<'t, 'a> Walker<'t, 'a> {
+    fn name(&self, i: usize) -> Option<usize> {
+        self.tokens.get(i).map(|_| i)
+    }
+
+    fn head(&self) -> Option<&'a str> {
+        match self.tokens.get(self.i + 1)? {
+            (Token::Keyword(keyword), _) => Some(keyword),
+            _ => None,
+        }
+    }
+
+    fn is_immediate(&self) -> bool {
+        matches!(self.tokens.get(self.i), Some((Token::LParen(..), _)))
+            && self
+                .head()
+                .is_some_and(|head| IMMEDIATE_KEYWORDS.contains(&head))
+    }
+
+    fn skip_group(&mut self) -> Option<()> {
+        self.i = matching_paren(self.tokens, self.i)? + 1;
+        Some(())
+    }
+
+    fn close(&mut self) -> Option<()> {
+        match self.tokens.get(self.i)? {
+            (Token::RParen(..), _) => {
+                self.i += 1;
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    /// Walk instructions up to the parenthesis that closes the enclosing expression.
+    fn instructions(&mut self) -> Option<()> {
+        loop {
+            match self.tokens.get(self.i)? {
+                (Token::RParen(..), _) => return Some(()),
+                (Token::LParen(..), _) if self.is_immediate() => self.skip_group()?,
+                (Token::LParen(..), _) => self.folded()?,
+                (Token::Keyword(keyword), _) if is_instruction(keyword) => {
+                    self.names.push(Some(self.i));
+                    self.i += 1;
+                }
+                _ => self.i += 1,
+            }
+        }
+    }
+
+    /// Walk a folded expression.
+    fn folded(&mut self) -> Option<()> {
+        let head = self.head()?;
+        let name = self.name(self.i + 1);
+        self.i += 2;
+        match head {
+            "block" | "loop" => {
+                self.names.push(name);
+                self.instructions()?;
+                self.close()?;
+                self.names.push(None);
+            }
+            "if" => {
+                self.block_type();
+                if self.head() != Some("then") {
+                    self.folded()?;
+                }
+                self.names.push(name);
+                self.arm("then")?;
+                if let Some((Token::LParen(..), _)) = self.tokens.get(self.i) {
+                    self.names.push(self.name(self.i + 1));
+                    if self.head() == Some("else")
+                        && matches!(self.tokens.get(self.i + 2)?, (Token::RParen(..), _))
+                    {
+                        self.names.pop();
+                    }
+                    self.arm("else")?;
+                }
+                self.close()?;
+                self.names.push(None);
+            }
+            "try" | "let" => return None,
+            _ => {
+                self.instructions()?;
+                self.close()?;
+                self.names.push(name);
+            }
+        }
+        Some(())
+    }
+
+    /// Skip the label and block type of a folded block.
+    fn block_type(&mut self) {
+        if let Some((Token::Id(..), _)) = self.tokens.get(self.i) {
+            self.i += 1;
+        }
+        while self.is_immediate() {
+            if self.skip_group().is_none() {
+                return;
+            }
+        }
+    }
+
+    /// Walk the `then` or `else` arm of a folded `if`,
+    /// which is a single folded expression if the keyword is omitted.
+    fn arm(&mut self, keyword: &str) -> Option<()> {
+        if self.head() == Some(keyword) {
+            self.i += 2;
+            self.instructions()?;
+            self.close()
+        } else {
+            self.folded()
+        }
+    }
+}
+
+/// The tokens of `source` that are not whitespace or comments,
+/// paired with their byte offsets.
+/// `source` starts within the parentheses of the function,
+/// and ends with the parenthesis that closes it.
+fn tokens(source: &str) -> Option<Vec<(Token<'_>, usize)>> {
+    Scan::new(source, Extent::Enclosed).complete()
+}
+
+/// Count the instructions that `text` unfolds to.
+fn segment(text: &str, folded: bool) -> Option<Segment> {
+    let buffer = ParseBuffer::new(text).ok()?;
+    let expression = parse::<Expression>(&buffer).ok()?;
+    Some(Segment {
+        folded,
+        len: expression.instrs.len(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{body_segments, instruction_offsets};
+
+    fn segments(source: &str) -> Option<Vec<(bool, usize)>> {
+        let offset = source.find("func")?;
+        let segments = body_segments(source, offset)?;
+        Some(segments.iter().map(|s| (s.folded, s.len)).collect())
+    }
+
+    #[test]
+    fn body_segments_should_split_folded_and_flat_runs() {
+        let source = "(module (func $f (param i32) (result i32) (local i32)
+            (local.set 1 (i32.add (local.get 0) (i32.const 1)))
+            block (result i32)
+                local.get 1
+            end
+            (i32.mul (local.get 0))))";
+        assert_eq!(
+            segments(source),
+            Some(vec![(true, 4), (false, 3), (true, 2)])
+        );
+    }
+
+    #[test]
+    fn body_segments_should_skip_header_and_comments() {
+        let source = "(func $f (export \"f\") (type 0) (local i32) ;; comment
+            (; block comment ;) nop)";
+        assert_eq!(segments(source), Some(vec![(false, 1)]));
+    }
+
+    #[test]
+    fn body_segments_should_keep_immediates_with_flat_instructions() {
+        let source = "(func call_indirect (type 0) (i32.const 0) select (result i32))";
+        assert_eq!(
+            segments(source),
+            Some(vec![(false, 1), (true, 1), (false, 1)])
+        );
+    }
+
+    #[test]
+    fn instruction_offsets_should_follow_unfolded_order() {
+        let source = "(func (result i32) (i32.add (i32.const 1) (block (result i32) i32.const 2)))";
+        let names: Vec<_> = instruction_offsets(source, 1)
+            .unwrap()
+            .into_iter()
+            .map(|offset| offset.map(|offset| source[offset..].split(' ').next().unwrap()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("i32.const"),
+                Some("block"),
+                Some("i32.const"),
+                None,
+                Some("i32.add")
+            ]
+        );
+    }
+
+    #[test]
+    fn instruction_offsets_should_unfold_if() {
+        let source = "(func (if (local.get 0) (then nop) (else)) if nop else nop end)";
+        let offsets = instruction_offsets(source, 1).unwrap();
+        let names: Vec<_> = offsets
+            .iter()
+            .map(|offset| offset.map(|offset| &source[offset..offset + 2]))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("lo"),
+                Some("if"),
+                Some("no"),
+                None,
+                Some("if"),
+                Some("no"),
+                Some("el"),
+                Some("no"),
+                Some("en"),
+            ]
+        );
+    }
+}
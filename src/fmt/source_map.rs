@@ -0,0 +1,144 @@
+use super::doc::render_marked;
+use super::fallback::Mode;
+use super::format;
+use super::range::field_spans;
+use super::shape::instruction_offsets;
+use crate::error::Error;
+use crate::options::Options;
+use std::collections::HashMap;
+use wast::core::{FuncKind, Instruction, ModuleField, ModuleKind};
+
+/// A mapping from byte offsets in source code to byte offsets in its formatted code.
+///
+/// The mapping is made up of anchors: the start of each module field
+/// and the name of each instruction, paired with their position in the formatted code.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    anchors: Vec<(usize, usize)>,
+    len: usize,
+}
+
+impl SourceMap {
+    /// The pairs of source and formatted offsets, in order of source offset.
+    pub fn anchors(&self) -> &[(usize, usize)] {
+        &self.anchors
+    }
+
+    /// Map an offset in the source to the corresponding offset in the formatted code.
+    ///
+    /// An offset between anchors keeps its distance to the closest preceding anchor,
+    /// so that a position within a token stays within that token.
+    pub fn map(&self, offset: usize) -> usize {
+        let index = self
+            .anchors
+            .partition_point(|(source, _)| *source <= offset);
+        match index.checked_sub(1).map(|index| self.anchors[index]) {
+            Some((source, formatted)) => (formatted + offset - source).min(self.len),
+            None => offset.min(self.len),
+        }
+    }
+}
+
+/// Format `.wat` source code like [`try_fmt`](super::try_fmt),
+/// also returning a mapping from offsets in `source` to offsets in the formatted code.
+pub fn fmt_with_source_map(source: &str, options: Options) -> Result<(String, SourceMap), Error> {
+    let (formatter, _) = format(source, options, Mode::Anchored)?;
+    let (formatted, mut anchors) = render_marked(&formatter.docs, &formatter.options);
+    anchors.sort_unstable();
+    anchors.dedup_by_key(|(source, _)| *source);
+    let len = formatted.len();
+    Ok((formatted, SourceMap { anchors, len }))
+}
+
+/// The source offsets of module fields and instructions.
+#[derive(Default)]
+pub(crate) struct Anchors {
+    /// The offset of the opening parenthesis of each module field.
+    fields: Vec<usize>,
+    /// The offset of the name of each instruction,
+    /// keyed by the address of the instruction in the parsed module.
+    instructions: HashMap<usize, usize>,
+}
+
+impl Anchors {
+    pub(crate) fn new(source: &str, kind: &ModuleKind) -> Self {
+        let fields = match kind {
+            ModuleKind::Text(fields) => fields,
+            ModuleKind::Binary(..) => return Anchors::default(),
+        };
+        let spans = field_spans(source).filter(|spans| spans.len() == fields.len());
+        let mut instructions = HashMap::new();
+        for field in fields {
+            if let ModuleField::Func(func) = field {
+                if let FuncKind::Inline { expression, .. } = &func.kind {
+                    let offsets = instruction_offsets(source, func.span.offset())
+                        .filter(|offsets| offsets.len() == expression.instrs.len());
+                    for (instruction, offset) in
+                        expression.instrs.iter().zip(offsets.iter().flatten())
+                    {
+                        if let Some(offset) = offset {
+                            instructions.insert(address(instruction), *offset);
+                        }
+                    }
+                }
+            }
+        }
+        Anchors {
+            fields: spans
+                .map(|spans| spans.iter().map(|span| span.start).collect())
+                .unwrap_or_default(),
+            instructions,
+        }
+    }
+
+    pub(crate) fn field(&self, index: usize) -> Option<usize> {
+        self.fields.get(index).copied()
+    }
+
+    pub(crate) fn instruction(&self, instruction: &Instruction) -> Option<usize> {
+        self.instructions.get(&address(instruction)).copied()
+    }
+}
+
+fn address(instruction: &Instruction) -> usize {
+    instruction as *const Instruction as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::fmt_with_source_map;
+    use crate::options::{ExpressionStyle, Options};
+
+    const SOURCE: &str = "(module (func $f (result i32) (i32.add (i32.const 1) (i32.const 2))) (global i32 (i32.const 3)))";
+
+    fn token_at(text: &str, offset: usize) -> &str {
+        text[offset..]
+            .split(|c: char| c.is_whitespace() || c == ')')
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn source_map_should_map_instructions() {
+        let (formatted, map) = fmt_with_source_map(SOURCE, Options::default()).unwrap();
+        for needle in ["i32.add", "i32.const 2", "(global"] {
+            let offset = SOURCE.find(needle).unwrap();
+            assert_eq!(
+                token_at(&formatted, map.map(offset)),
+                token_at(SOURCE, offset)
+            );
+        }
+    }
+
+    #[test]
+    fn source_map_should_keep_offsets_within_tokens() {
+        let options = Options {
+            expression_style: ExpressionStyle::Folded,
+            ..Options::default()
+        };
+        let (formatted, map) = fmt_with_source_map(SOURCE, options).unwrap();
+        let offset = SOURCE.find("add").unwrap();
+        assert_eq!(&formatted[map.map(offset)..map.map(offset) + 3], "add");
+        assert_eq!(map.map(0), 0);
+    }
+}
@@ -7,6 +7,7 @@ use wast::core::{
 impl<'src> Fmt for &Table<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         formatter.start_line();
+        formatter.group();
         formatter.write("(table ");
         if let Some(id) = &self.id {
             if !id_is_gensym(id) {
@@ -16,9 +17,10 @@ impl<'src> Fmt for &Table<'src> {
         }
         if !self.exports.names.is_empty() {
             formatter.fmt(&self.exports);
-            formatter.write(" ");
+            formatter.soft_break();
         }
         formatter.fmt(&self.kind);
+        formatter.end_group();
         formatter.write(")");
         formatter.end_line();
     }
@@ -70,13 +72,13 @@ impl<'src> Fmt for &RefType<'src> {
             HeapType::Func => {
                 formatter.write("funcref");
             }
-            HeapType::Extern => unimplemented!(),
-            HeapType::Any => unimplemented!(),
-            HeapType::Eq => unimplemented!(),
-            HeapType::I31 => unimplemented!(),
-            HeapType::Index(..) => unimplemented!(),
-            HeapType::Array => unimplemented!(),
-            HeapType::Data => unimplemented!(),
+            HeapType::Extern
+            | HeapType::Any
+            | HeapType::Eq
+            | HeapType::I31
+            | HeapType::Index(..)
+            | HeapType::Array
+            | HeapType::Data => formatter.unsupported("reference types other than `funcref`"),
         }
     }
 }
@@ -104,8 +106,8 @@ impl<'src> Fmt for &Elem<'src> {
 impl<'src> Fmt for &ElemKind<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         match self {
-            ElemKind::Passive => todo!(),
-            ElemKind::Declared => todo!(),
+            ElemKind::Passive => formatter.unsupported("passive element segments"),
+            ElemKind::Declared => formatter.unsupported("declared element segments"),
             ElemKind::Active { table, offset } => {
                 if !index_is_default(table) {
                     formatter.fmt(table);
@@ -129,7 +131,7 @@ impl<'src> Fmt for &ElemPayload<'src> {
             ElemPayload::Indices(refs) => {
                 formatter.fmt(refs);
             }
-            ElemPayload::Exprs { .. } => unimplemented!(),
+            ElemPayload::Exprs { .. } => formatter.unsupported("element expressions"),
         }
     }
 }
@@ -22,23 +22,25 @@ impl<'src> Fmt for &TypeDef<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
         match self {
             TypeDef::Func(functy) => functy.fmt(formatter),
-            TypeDef::Struct(..) => unimplemented!(),
-            TypeDef::Array(..) => unimplemented!(),
+            TypeDef::Struct(..) => formatter.unsupported("struct types"),
+            TypeDef::Array(..) => formatter.unsupported("array types"),
         };
     }
 }
 
 impl<'src> Fmt for &FunctionType<'src> {
     fn fmt(&self, formatter: &mut Formatter) {
+        formatter.group();
         formatter.write("(func");
         if !self.params.is_empty() {
-            formatter.write(" ");
+            formatter.soft_break();
         }
         formatter.fmt(&*self.params);
         if !self.results.is_empty() {
-            formatter.write(" ");
+            formatter.soft_break();
         }
         formatter.fmt(&*self.results);
+        formatter.end_group();
         formatter.write(")");
     }
 }
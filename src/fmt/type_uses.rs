@@ -0,0 +1,340 @@
+use super::utils::{func_ty_is_empty, func_ty_signature_eq, id_is_gensym};
+use crate::options::TypeUseStyle;
+use wast::core::{
+    DataKind, ElemKind, ElemPayload, Expression, FuncKind, FunctionType, GlobalKind, Instruction,
+    ItemKind, LetType, ModuleField, ModuleKind, TagType, Type, TypeDef, TypeUse,
+};
+use wast::token::{Id, Index, Span};
+
+/// Write type uses with a `(type ...)` reference, their parameters and results, or both,
+/// as `style` asks.
+///
+/// A type use without a reference refers to the first type with the same signature,
+/// or to a type that is added to the end of the type section if there is none,
+/// so references are only added or removed where they refer to that type.
+/// Parameters with ids are kept, since the body of a function may refer to them.
+pub(crate) fn normalize_type_uses(kind: &mut ModuleKind, style: TypeUseStyle) {
+    let fields = match kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return,
+    };
+    if style == TypeUseStyle::Preserve {
+        return;
+    }
+    let mut types = Types::new(fields);
+    for field in fields.iter_mut() {
+        field_type_uses(field, &mut |ty, block| types.normalize(ty, style, block));
+    }
+    // Types that type uses define are numbered after those of the type section,
+    // so their definitions follow the last type definition.
+    let position = fields
+        .iter()
+        .rposition(|field| matches!(field, ModuleField::Type(..) | ModuleField::Rec(..)))
+        .map_or(0, |position| position + 1);
+    let span = Span::from_offset(0);
+    let added = types.entries.drain(types.defined..).map(|(_, functy)| {
+        ModuleField::Type(Type {
+            span,
+            id: None,
+            name: None,
+            def: TypeDef::Func(functy.expect("function type")),
+            parent: None,
+        })
+    });
+    fields.splice(position..position, added);
+}
+
+/// The number of types that the fields of a module define,
+/// which precede those added for type uses without a matching definition.
+pub(crate) fn defined_types(fields: &[ModuleField]) -> usize {
+    fields
+        .iter()
+        .map(|field| match field {
+            ModuleField::Type(..) => 1,
+            ModuleField::Rec(rec) => rec.types.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The module's types, by index.
+struct Types<'src> {
+    /// The id of each type, and its signature if it is a function type.
+    entries: Vec<(Option<Id<'src>>, Option<FunctionType<'src>>)>,
+    /// The number of [`defined_types`].
+    defined: usize,
+}
+
+impl<'src> Types<'src> {
+    fn new(fields: &[ModuleField<'src>]) -> Self {
+        let mut entries = Vec::new();
+        for field in fields {
+            match field {
+                ModuleField::Type(ty) => {
+                    let functy = match &ty.def {
+                        TypeDef::Func(functy) => Some(functy.clone()),
+                        TypeDef::Struct(..) | TypeDef::Array(..) => None,
+                    };
+                    entries.push((ty.id, functy));
+                }
+                // Type uses without a reference never refer to types in a recursion group.
+                ModuleField::Rec(rec) => entries.extend(rec.types.iter().map(|ty| (ty.id, None))),
+                _ => {}
+            }
+        }
+        let defined = defined_types(fields);
+        Types { entries, defined }
+    }
+
+    fn normalize(
+        &mut self,
+        ty: &mut TypeUse<'src, FunctionType<'src>>,
+        style: TypeUseStyle,
+        block: bool,
+    ) {
+        let target = match &ty.index {
+            Some(index) => self.resolve(index),
+            // Blocks without parameters and with at most one result are encoded without a type.
+            None if block && ty.inline.as_ref().is_none_or(is_value_type) => return,
+            None => {
+                let functy = ty.inline.clone().unwrap_or_default();
+                match (self.lookup(&functy), style) {
+                    (Some(target), _) => Some(target),
+                    (None, TypeUseStyle::Reference) => {
+                        self.entries.push((None, Some(without_ids(&functy))));
+                        Some(self.entries.len() - 1)
+                    }
+                    (None, _) => None,
+                }
+            }
+        };
+        let (target, functy) =
+            match target.and_then(|target| Some((target, self.entries[target].1.as_ref()?))) {
+                Some(target) => target,
+                None => return,
+            };
+        match style {
+            TypeUseStyle::Preserve => {}
+            TypeUseStyle::Explicit => {
+                ty.index.get_or_insert_with(|| self.reference(target));
+                if ty.inline.as_ref().is_none_or(func_ty_is_empty) {
+                    ty.inline = Some(without_ids(functy));
+                }
+            }
+            TypeUseStyle::Inline => {
+                if self.lookup(functy) == Some(target) && !(block && is_value_type(functy)) {
+                    if ty.inline.as_ref().is_none_or(func_ty_is_empty) {
+                        ty.inline = Some(without_ids(functy));
+                    }
+                    ty.index = None;
+                }
+            }
+            TypeUseStyle::Reference => {
+                ty.index.get_or_insert_with(|| self.reference(target));
+                if ty.inline.as_ref().is_some_and(|functy| !has_ids(functy)) {
+                    ty.inline = None;
+                }
+            }
+        }
+    }
+
+    /// The position of the type that `index` refers to.
+    fn resolve(&self, index: &Index) -> Option<usize> {
+        match index {
+            Index::Num(n, ..) => Some(*n as usize).filter(|n| *n < self.entries.len()),
+            Index::Id(id) => self
+                .entries
+                .iter()
+                .position(|(other, _)| other.as_ref() == Some(id)),
+        }
+    }
+
+    /// The position of the first function type with the signature of `functy`.
+    fn lookup(&self, functy: &FunctionType) -> Option<usize> {
+        self.entries.iter().position(|(_, other)| {
+            other
+                .as_ref()
+                .is_some_and(|other| func_ty_signature_eq(other, functy))
+        })
+    }
+
+    /// A reference to the type at `position`, by its id or by its index if it has none.
+    fn reference(&self, position: usize) -> Index<'src> {
+        match self.entries[position].0 {
+            Some(id) if !id_is_gensym(&id) => Index::Id(id),
+            _ => Index::Num(position as u32, Span::from_offset(0)),
+        }
+    }
+}
+
+/// Call `normalize` with each type use of `field`, and whether it is the type of a block,
+/// in the order in which the types they define are numbered.
+pub(crate) fn field_type_uses<'src>(
+    field: &mut ModuleField<'src>,
+    normalize: &mut impl FnMut(&mut TypeUse<'src, FunctionType<'src>>, bool),
+) {
+    match field {
+        ModuleField::Import(import) => {
+            if let ItemKind::Func(ty) | ItemKind::Tag(TagType::Exception(ty)) =
+                &mut import.item.kind
+            {
+                normalize(ty, false);
+            }
+        }
+        ModuleField::Func(func) => {
+            normalize(&mut func.ty, false);
+            if let FuncKind::Inline { expression, .. } = &mut func.kind {
+                expression_type_uses(expression, normalize);
+            }
+        }
+        ModuleField::Global(global) => {
+            if let GlobalKind::Inline(expression) = &mut global.kind {
+                expression_type_uses(expression, normalize);
+            }
+        }
+        ModuleField::Data(data) => {
+            if let DataKind::Active { offset, .. } = &mut data.kind {
+                expression_type_uses(offset, normalize);
+            }
+        }
+        ModuleField::Elem(elem) => {
+            if let ElemKind::Active { offset, .. } = &mut elem.kind {
+                expression_type_uses(offset, normalize);
+            }
+            if let ElemPayload::Exprs { exprs, .. } = &mut elem.payload {
+                for expression in exprs {
+                    expression_type_uses(expression, normalize);
+                }
+            }
+        }
+        ModuleField::Tag(tag) => {
+            let TagType::Exception(ty) = &mut tag.ty;
+            normalize(ty, false);
+        }
+        _ => {}
+    }
+}
+
+fn expression_type_uses<'src>(
+    expression: &mut Expression<'src>,
+    normalize: &mut impl FnMut(&mut TypeUse<'src, FunctionType<'src>>, bool),
+) {
+    for instruction in expression.instrs.iter_mut() {
+        match instruction {
+            Instruction::Block(bt)
+            | Instruction::Loop(bt)
+            | Instruction::If(bt)
+            | Instruction::Let(LetType { block: bt, .. })
+            | Instruction::Try(bt) => normalize(&mut bt.ty, true),
+            Instruction::FuncBind(bind) => normalize(&mut bind.ty, false),
+            Instruction::CallIndirect(call) | Instruction::ReturnCallIndirect(call) => {
+                normalize(&mut call.ty, false)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether the signature of a block is encoded as a value type rather than as a type index.
+fn is_value_type(functy: &FunctionType) -> bool {
+    functy.params.is_empty() && functy.results.len() <= 1
+}
+
+fn has_ids(functy: &FunctionType) -> bool {
+    functy
+        .params
+        .iter()
+        .any(|(id, name, _)| id.is_some() || name.is_some())
+}
+
+/// The signature of `functy`, without the ids of its parameters,
+/// which would otherwise name the parameters of every function that uses it.
+fn without_ids<'src>(functy: &FunctionType<'src>) -> FunctionType<'src> {
+    FunctionType {
+        params: functy
+            .params
+            .iter()
+            .map(|(_, _, ty)| (None, None, *ty))
+            .collect(),
+        results: functy.results.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fmt::fmt;
+    use crate::options::{Options, TypeUseStyle};
+    use pretty_assertions::assert_eq;
+
+    const SOURCE: &str = "(module
+  (type $t (func (param i32) (result i32)))
+  (func $a (type $t) (local.get 0))
+  (func $b (param $p i32) (result i32) (local.get $p))
+  (func $c (param i64)))";
+
+    fn options(type_uses: TypeUseStyle) -> Options {
+        Options {
+            type_uses,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn explicit_should_write_references_and_signatures() {
+        let expected = "(module
+\t(type $t (func (param i32) (result i32)))
+\t(func $a (type $t) (param i32) (result i32)
+\t\t(local.get 0)
+\t)
+\t(func $b (type $t) (param $p i32) (result i32)
+\t\t(local.get $p)
+\t)
+\t(func $c (param i64))
+)
+";
+        assert_eq!(fmt(SOURCE, options(TypeUseStyle::Explicit)), expected);
+    }
+
+    #[test]
+    fn inline_should_write_signatures() {
+        let expected = "(module
+\t(type $t (func (param i32) (result i32)))
+\t(func $a (param i32) (result i32)
+\t\t(local.get 0)
+\t)
+\t(func $b (param $p i32) (result i32)
+\t\t(local.get $p)
+\t)
+\t(func $c (param i64))
+)
+";
+        assert_eq!(fmt(SOURCE, options(TypeUseStyle::Inline)), expected);
+    }
+
+    #[test]
+    fn reference_should_define_missing_types() {
+        let expected = "(module
+\t(type $t (func (param i32) (result i32)))
+\t(type (func (param i64)))
+\t(func $a (type $t)
+\t\t(local.get 0)
+\t)
+\t(func $b (type $t) (param $p i32) (result i32)
+\t\t(local.get $p)
+\t)
+\t(func $c (type 1))
+)
+";
+        assert_eq!(fmt(SOURCE, options(TypeUseStyle::Reference)), expected);
+    }
+
+    #[test]
+    fn inline_should_keep_references_to_duplicate_types() {
+        let source = "(module
+  (type (func))
+  (type (func))
+  (func (type 1)))";
+        let expected = fmt(source, Options::default());
+        assert_eq!(fmt(source, options(TypeUseStyle::Inline)), expected);
+    }
+}
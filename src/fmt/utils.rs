@@ -2,6 +2,7 @@ use super::Formatter;
 use wast::core::{
     BlockType, Expression, FunctionType, InlineExport, Instruction, MemoryArg, TypeUse,
 };
+use wast::lexer::Token;
 use wast::token::{Id, Index};
 
 pub fn expr_is_const(expression: &Expression) -> bool {
@@ -24,6 +25,17 @@ pub fn func_ty_is_empty(func_ty: &FunctionType) -> bool {
     func_ty.params.is_empty() && func_ty.results.is_empty()
 }
 
+/// Whether two function types have the same parameter and result types,
+/// regardless of the ids of their parameters.
+pub fn func_ty_signature_eq(a: &FunctionType, b: &FunctionType) -> bool {
+    a.params.len() == b.params.len()
+        && a.params
+            .iter()
+            .zip(b.params.iter())
+            .all(|(a, b)| a.2 == b.2)
+        && a.results == b.results
+}
+
 pub fn ty_use_is_empty<'a>(ty_use: &TypeUse<'a, FunctionType<'a>>) -> bool {
     ty_use.index.is_none() && ty_use.inline.as_ref().map(func_ty_is_empty).unwrap_or(true)
 }
@@ -33,7 +45,11 @@ pub fn id_is_gensym(id: &Id) -> bool {
 }
 
 pub fn fmt_long_expression<'src>(expression: &Expression<'src>, formatter: &mut Formatter) {
-    for instruction in expression.instrs.iter() {
+    fmt_instructions(&expression.instrs, formatter);
+}
+
+pub fn fmt_instructions<'src>(instrs: &[Instruction<'src>], formatter: &mut Formatter) {
+    for instruction in instrs.iter() {
         if instr_is_block_end(instruction) {
             formatter.deindent();
         }
@@ -71,3 +87,21 @@ pub fn memory_index_is_valid(index: &Index) -> bool {
 pub fn bt_is_empty(block_type: &BlockType) -> bool {
     block_type.label.is_none() && ty_use_is_empty(&block_type.ty)
 }
+
+/// The index of the parenthesis that closes the one at `open`.
+pub fn matching_paren(tokens: &[(Token<'_>, usize)], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, (token, _)) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::LParen(..) => depth += 1,
+            Token::RParen(..) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
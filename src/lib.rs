@@ -1,7 +1,25 @@
+mod config;
 mod diff;
+mod editorconfig;
 mod error;
 mod fmt;
+mod lsp;
+mod options;
+mod report;
+mod verify;
 
-pub use diff::Diff;
+pub use config::{Config, CONFIG_FILE_NAMES};
+pub use diff::{Diff, Hunk};
+pub use editorconfig::EDITORCONFIG_FILE_NAME;
 pub use error::Error;
-pub use fmt::{fmt, Fmt, Formatter, Options};
+pub use fmt::{
+    fmt, fmt_best_effort, fmt_range, fmt_recover, fmt_with_source_map, fmt_with_summary, try_fmt,
+    Diagnostic, Fallback, Fmt, Formatter, SourceMap, Summary,
+};
+pub use lsp::serve_lsp;
+pub use options::{
+    BranchStyle, ExpressionStyle, FieldOrder, IndentStyle, LineEnding, Options, Placement,
+    TypeUseStyle, DEFAULT_INDENT_WIDTH, DEFAULT_MAX_WIDTH,
+};
+pub use report::{FileReport, MessageFormat};
+pub use verify::{verify, verify_idempotent};
@@ -0,0 +1,600 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::fmt::range::field_spans;
+use crate::fmt::utils::id_is_gensym;
+use crate::fmt::{fmt_range, fmt_recover};
+use crate::options::{IndentStyle, Options};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{
+    DocumentSymbolRequest, Formatting, OnTypeFormatting, RangeFormatting, Request as _,
+};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentFormattingParams, DocumentOnTypeFormattingOptions,
+    DocumentOnTypeFormattingParams, DocumentRangeFormattingParams, DocumentSymbol,
+    DocumentSymbolParams, DocumentSymbolResponse, FormattingOptions, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url,
+};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::io;
+use wast::core::{ItemKind, ModuleField, ModuleKind};
+use wast::parser::{parse, ParseBuffer};
+use wast::token::{Id, Span};
+use wast::Wat;
+
+/// Serve the language server protocol over `connection` until the client exits.
+///
+/// Documents are formatted with the settings that apply to their path,
+/// with `overrides` taking precedence, as for the command line.
+/// Settings that are not configured anywhere are taken from the
+/// formatting options of the client.
+pub fn serve_lsp(connection: Connection, overrides: Config) -> Result<(), Error> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: ")".to_string(),
+            more_trigger_character: Some(vec!["\n".to_string()]),
+        }),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let capabilities = serde_json::to_value(capabilities).expect("capabilities");
+    connection
+        .initialize(capabilities)
+        .map_err(protocol_error)?;
+    let mut server = Server {
+        connection: &connection,
+        overrides,
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection
+                    .handle_shutdown(&request)
+                    .map_err(protocol_error)?
+                {
+                    return Ok(());
+                }
+                server.handle_request(request)?;
+            }
+            Message::Notification(notification) => server.handle_notification(notification)?,
+            Message::Response(..) => {}
+        }
+    }
+    Ok(())
+}
+
+fn protocol_error(err: impl ToString) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    overrides: Config,
+    /// The contents of the open documents.
+    documents: HashMap<Url, String>,
+}
+
+impl<'a> Server<'a> {
+    fn send(&self, message: impl Into<Message>) -> Result<(), Error> {
+        self.connection
+            .sender
+            .send(message.into())
+            .map_err(|err| Error::Io(io::Error::new(io::ErrorKind::BrokenPipe, err.to_string())))
+    }
+
+    fn handle_request(&mut self, request: Request) -> Result<(), Error> {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            Formatting::METHOD => params(request).map(|params: DocumentFormattingParams| {
+                self.edits(
+                    &params.text_document.uri,
+                    &params.options,
+                    |source, options| Ok(fmt_recover(source, options).0),
+                )
+            }),
+            RangeFormatting::METHOD => {
+                params(request).map(|params: DocumentRangeFormattingParams| {
+                    let range = params.range;
+                    self.edits(
+                        &params.text_document.uri,
+                        &params.options,
+                        |source, options| {
+                            let start = offset(source, range.start);
+                            let end = offset(source, range.end);
+                            fmt_range(source, start..end, options)
+                        },
+                    )
+                })
+            }
+            OnTypeFormatting::METHOD => {
+                params(request).map(|params: DocumentOnTypeFormattingParams| {
+                    let position = &params.text_document_position;
+                    let cursor = position.position;
+                    self.edits(
+                        &position.text_document.uri,
+                        &params.options,
+                        |source, options| {
+                            let offset = offset(source, cursor);
+                            fmt_range(source, offset..offset, options)
+                        },
+                    )
+                })
+            }
+            DocumentSymbolRequest::METHOD => params(request).map(|params: DocumentSymbolParams| {
+                let symbols = self
+                    .documents
+                    .get(&params.text_document.uri)
+                    .and_then(|source| symbols(source));
+                serde_json::to_value(symbols.map(DocumentSymbolResponse::Nested)).expect("symbols")
+            }),
+            method => Err(Response::new_err(
+                id.clone(),
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request `{}`", method),
+            )),
+        };
+        let response = match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(response) => response,
+        };
+        self.send(response)
+    }
+
+    /// The edits that format a document, or `null` if it cannot be formatted.
+    fn edits(
+        &self,
+        uri: &Url,
+        formatting: &FormattingOptions,
+        format: impl FnOnce(&str, Options) -> Result<String, Error>,
+    ) -> serde_json::Value {
+        let source = match self.documents.get(uri) {
+            Some(source) => source,
+            None => return serde_json::Value::Null,
+        };
+        let edits = format(source, self.options(uri, formatting))
+            .ok()
+            .map(|formatted| edits(source, &formatted));
+        serde_json::to_value(edits).expect("edits")
+    }
+
+    /// The options for formatting a document.
+    fn options(&self, uri: &Url, formatting: &FormattingOptions) -> Options {
+        let client = Config {
+            indent_style: Some(if formatting.insert_spaces {
+                IndentStyle::Spaces
+            } else {
+                IndentStyle::Tabs
+            }),
+            indent_width: Some(formatting.tab_size as usize).filter(|width| *width > 0),
+            ..Config::default()
+        };
+        let resolved = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| Config::resolve(&path).ok())
+            .map(|(_, config)| config)
+            .unwrap_or_default();
+        client
+            .merge(resolved)
+            .merge(self.overrides.clone())
+            .to_options()
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<(), Error> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                if let Ok(params) =
+                    notification.extract::<DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                {
+                    let document = params.text_document;
+                    self.open(document.uri, document.text)?;
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                if let Ok(params) = notification
+                    .extract::<DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)
+                {
+                    // Documents are synchronized in full, so the last change is the new text.
+                    if let Some(change) = params.content_changes.into_iter().last() {
+                        self.open(params.text_document.uri, change.text)?;
+                    }
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                if let Ok(params) =
+                    notification.extract::<DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                {
+                    let uri = params.text_document.uri;
+                    self.documents.remove(&uri);
+                    self.publish_diagnostics(uri, Vec::new())?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, uri: Url, text: String) -> Result<(), Error> {
+        let diagnostics = diagnostics(&text);
+        self.documents.insert(uri.clone(), text);
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> Result<(), Error> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        self.send(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            params,
+        ))
+    }
+}
+
+/// Deserialize the parameters of a request, or respond with an error.
+fn params<P: DeserializeOwned>(request: Request) -> Result<P, Response> {
+    let id = request.id;
+    serde_json::from_value(request.params)
+        .map_err(|err| Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()))
+}
+
+/// The syntax errors in `source`.
+fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    let (_, diagnostics) = fmt_recover(source, Options::default());
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let start = position(source, diagnostic.offset);
+            Diagnostic {
+                range: Range { start, end: start },
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("wasmfmt".to_string()),
+                message: diagnostic.message,
+                ..Diagnostic::default()
+            }
+        })
+        .collect()
+}
+
+/// The single edit that turns `source` into `formatted`,
+/// replacing only the lines that differ.
+fn edits(source: &str, formatted: &str) -> Vec<TextEdit> {
+    if source == formatted {
+        return Vec::new();
+    }
+    let before: Vec<&str> = source.split_inclusive('\n').collect();
+    let after: Vec<&str> = formatted.split_inclusive('\n').collect();
+    let prefix = before
+        .iter()
+        .zip(&after)
+        .take_while(|(before, after)| before == after)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(before, after)| before == after)
+        .count();
+    let start: usize = before[..prefix].iter().map(|line| line.len()).sum();
+    let end: usize = source.len()
+        - before[before.len() - suffix..]
+            .iter()
+            .map(|line| line.len())
+            .sum::<usize>();
+    vec![TextEdit {
+        range: Range {
+            start: position(source, start),
+            end: position(source, end),
+        },
+        new_text: after[prefix..after.len() - suffix].concat(),
+    }]
+}
+
+/// The position of a byte offset, with characters counted in UTF-16 code units.
+fn position(source: &str, offset: usize) -> Position {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// The byte offset of a position, clamped to the end of its line.
+fn offset(source: &str, position: Position) -> usize {
+    let line_start = if position.line == 0 {
+        Some(0)
+    } else {
+        source
+            .match_indices('\n')
+            .nth(position.line as usize - 1)
+            .map(|(i, _)| i + 1)
+    };
+    let line_start = match line_start {
+        Some(line_start) => line_start,
+        None => return source.len(),
+    };
+    let mut units = 0;
+    for (i, c) in source[line_start..].char_indices() {
+        if units >= position.character as usize || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    source.len()
+}
+
+/// The types, functions, tables, memories and globals defined in `source`.
+#[allow(deprecated)]
+fn symbols(source: &str) -> Option<Vec<DocumentSymbol>> {
+    let buffer = ParseBuffer::new(source).ok()?;
+    let module = match parse::<Wat>(&buffer).ok()? {
+        Wat::Module(module) => module,
+        Wat::Component(..) => return None,
+    };
+    let fields = match &module.kind {
+        ModuleKind::Text(fields) => fields,
+        ModuleKind::Binary(..) => return None,
+    };
+    let spans = field_spans(source).filter(|spans| spans.len() == fields.len());
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    let mut symbols = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let (kind, span, id) = match field {
+            ModuleField::Type(ty) => ("type", ty.span, ty.id),
+            ModuleField::Rec(rec) => {
+                *indices.entry("type").or_default() += rec.types.len();
+                continue;
+            }
+            ModuleField::Import(import) => {
+                let kind = match import.item.kind {
+                    ItemKind::Func(..) => "func",
+                    ItemKind::Table(..) => "table",
+                    ItemKind::Memory(..) => "memory",
+                    ItemKind::Global(..) => "global",
+                    ItemKind::Tag(..) => "tag",
+                };
+                *indices.entry(kind).or_default() += 1;
+                continue;
+            }
+            ModuleField::Func(func) => ("func", func.span, func.id),
+            ModuleField::Table(table) => ("table", table.span, table.id),
+            ModuleField::Memory(memory) => ("memory", memory.span, memory.id),
+            ModuleField::Global(global) => ("global", global.span, global.id),
+            _ => continue,
+        };
+        let entry = indices.entry(kind).or_default();
+        let name = match id {
+            Some(id) if !id_is_gensym(&id) => format!("${}", id.name()),
+            _ => format!("{} {}", kind, entry),
+        };
+        *entry += 1;
+        let selection = selection_range(source, kind, span, id);
+        let range = match &spans {
+            Some(spans) => Range {
+                start: position(source, spans[index].start),
+                end: position(source, spans[index].end),
+            },
+            None => selection,
+        };
+        symbols.push(DocumentSymbol {
+            name,
+            detail: Some(kind.to_string()),
+            kind: match kind {
+                "type" => SymbolKind::INTERFACE,
+                "func" => SymbolKind::FUNCTION,
+                "table" => SymbolKind::ARRAY,
+                "memory" => SymbolKind::OBJECT,
+                _ => SymbolKind::VARIABLE,
+            },
+            tags: None,
+            deprecated: None,
+            range,
+            selection_range: selection,
+            children: None,
+        });
+    }
+    Some(symbols)
+}
+
+/// The range of the identifier of a field, or of its keyword if it has none.
+fn selection_range(source: &str, keyword: &str, span: Span, id: Option<Id>) -> Range {
+    let (start, len) = match id {
+        Some(id) if !id_is_gensym(&id) => (id.span().offset(), id.name().len() + 1),
+        _ => (span.offset(), keyword.len()),
+    };
+    Range {
+        start: position(source, start),
+        end: position(source, start + len),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{edits, offset, position, serve_lsp};
+    use crate::config::Config;
+    use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+    use lsp_types::Position;
+    use serde_json::{json, Value};
+    use std::thread;
+
+    /// A client talking to a server running on another thread.
+    struct Client {
+        connection: Connection,
+        server: thread::JoinHandle<()>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (server, connection) = Connection::memory();
+            let server = thread::spawn(move || serve_lsp(server, Config::default()).unwrap());
+            let mut client = Client {
+                connection,
+                server,
+                next_id: 0,
+            };
+            client.request("initialize", json!({ "capabilities": {} }));
+            client.notify("initialized", json!({}));
+            client
+        }
+
+        fn request(&mut self, method: &str, params: Value) -> Response {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            let request = Request::new(id.clone(), method.to_string(), params);
+            self.connection.sender.send(request.into()).unwrap();
+            loop {
+                if let Message::Response(response) = self.connection.receiver.recv().unwrap() {
+                    assert_eq!(response.id, id);
+                    return response;
+                }
+            }
+        }
+
+        fn notify(&self, method: &str, params: Value) {
+            let notification = Notification::new(method.to_string(), params);
+            self.connection.sender.send(notification.into()).unwrap();
+        }
+
+        fn open(&self, text: &str) -> Value {
+            self.notify(
+                "textDocument/didOpen",
+                json!({
+                    "textDocument": {
+                        "uri": "untitled:test.wat",
+                        "languageId": "wat",
+                        "version": 1,
+                        "text": text,
+                    }
+                }),
+            );
+            loop {
+                if let Message::Notification(notification) =
+                    self.connection.receiver.recv().unwrap()
+                {
+                    assert_eq!(notification.method, "textDocument/publishDiagnostics");
+                    return notification.params;
+                }
+            }
+        }
+
+        fn shutdown(mut self) {
+            self.request("shutdown", Value::Null);
+            self.notify("exit", Value::Null);
+            self.server.join().unwrap();
+        }
+    }
+
+    const SOURCE: &str =
+        "(module\n(func $f (result i32) (i32.const 1))\n(global i32   (i32.const 2))\n)\n";
+
+    #[test]
+    fn formatting_should_return_edits() {
+        let mut client = Client::start();
+        client.open(SOURCE);
+        let response = client.request(
+            "textDocument/formatting",
+            json!({
+                "textDocument": { "uri": "untitled:test.wat" },
+                "options": { "tabSize": 4, "insertSpaces": false },
+            }),
+        );
+        client.shutdown();
+        assert_eq!(
+            response.result.unwrap()[0]["newText"],
+            "\t(func $f (result i32)\n\t\t(i32.const 1)\n\t)\n\t(global i32 (i32.const 2))\n"
+        );
+    }
+
+    #[test]
+    fn range_formatting_should_only_change_overlapping_fields() {
+        let mut client = Client::start();
+        client.open(SOURCE);
+        let response = client.request(
+            "textDocument/rangeFormatting",
+            json!({
+                "textDocument": { "uri": "untitled:test.wat" },
+                "range": {
+                    "start": { "line": 2, "character": 0 },
+                    "end": { "line": 2, "character": 1 },
+                },
+                "options": { "tabSize": 2, "insertSpaces": true },
+            }),
+        );
+        client.shutdown();
+        assert_eq!(
+            response.result.unwrap(),
+            json!([{
+                "range": {
+                    "start": { "line": 2, "character": 0 },
+                    "end": { "line": 3, "character": 0 },
+                },
+                "newText": "  (global i32 (i32.const 2))\n",
+            }])
+        );
+    }
+
+    #[test]
+    fn diagnostics_should_report_parse_errors() {
+        let client = Client::start();
+        let diagnostics = client.open("(module (func (i32.const)))");
+        client.shutdown();
+        let diagnostic = &diagnostics["diagnostics"][0];
+        assert_eq!(
+            diagnostic["range"]["start"],
+            json!({ "line": 0, "character": 24 })
+        );
+        assert_eq!(diagnostic["severity"], 1);
+    }
+
+    #[test]
+    fn document_symbols_should_list_fields() {
+        let mut client = Client::start();
+        client.open(SOURCE);
+        let response = client.request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": "untitled:test.wat" } }),
+        );
+        client.shutdown();
+        let symbols = response.result.unwrap();
+        assert_eq!(symbols[0]["name"], "$f");
+        assert_eq!(symbols[0]["kind"], 12);
+        assert_eq!(
+            symbols[0]["selectionRange"]["start"],
+            json!({ "line": 1, "character": 6 })
+        );
+        assert_eq!(symbols[1]["name"], "global 0");
+        assert_eq!(symbols[1]["kind"], 13);
+    }
+
+    #[test]
+    fn positions_should_count_utf16_code_units() {
+        let source = ";; \u{1F600}\n(module)";
+        assert_eq!(position(source, 7), Position::new(0, 5));
+        assert_eq!(offset(source, Position::new(0, 5)), 7);
+        assert_eq!(offset(source, Position::new(1, 1)), 9);
+        assert_eq!(offset(source, Position::new(0, 99)), 7);
+    }
+
+    #[test]
+    fn edits_should_replace_changed_lines() {
+        let edits = edits("a\nb\nc\n", "a\nB\nc\n");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start, Position::new(1, 0));
+        assert_eq!(edits[0].range.end, Position::new(2, 0));
+        assert_eq!(edits[0].new_text, "B\n");
+    }
+}
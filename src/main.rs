@@ -1,18 +1,53 @@
+use lsp_server::Connection;
 use std::fs;
 use std::io::{self, Write as _};
-use std::path::PathBuf;
+use std::iter;
+use std::ops::Range;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
 use structopt::StructOpt;
-use wasmfmt::{fmt, Diff, Error, Options};
+use wasmfmt::{
+    fmt_best_effort, fmt_range, fmt_recover, fmt_with_summary, serve_lsp, try_fmt, verify,
+    verify_idempotent, BranchStyle, Config, Diff, Error, ExpressionStyle, FieldOrder, FileReport,
+    IndentStyle, MessageFormat, Placement, TypeUseStyle,
+};
 
 /// Format WebAssembly code.
+///
+/// Settings are read from the nearest `wasmfmt.toml` or `.wasmfmt.toml`
+/// in the directory of the input file or any of its ancestors,
+/// and from `.editorconfig` files for settings it does not declare.
+/// Command-line flags take precedence over both.
+///
+/// Exits with 0 on success, 1 if a file is not formatted correctly,
+/// 2 if the input, configuration or arguments cannot be parsed,
+/// 3 if the input contains constructs that cannot be formatted,
+/// and 4 if a file cannot be read or written.
 #[derive(StructOpt)]
 enum Cli {
     /// Format the input file in-place.
-    Fix(Input),
-    /// Check if the input file is formatted correctly.
-    Check(Input),
+    ///
+    /// The file is replaced atomically, keeping its permissions.
+    /// It is left unchanged if it is already formatted,
+    /// or if the formatted code would not encode to the same module as the input.
+    Fix(FixInput),
+    /// Check if the input file is formatted correctly,
+    /// and that formatting it would not change the module.
+    Check(CheckInput),
     /// Print the formatted code to `stdout`.
     Print(Input),
+    /// Inspect the configuration that applies to a path.
+    Config(ConfigInput),
+    /// Check that formatting every `.wat` file in a directory tree
+    /// is idempotent and does not change the module.
+    Verify(VerifyInput),
+    /// Run a language server over `stdin` and `stdout`.
+    ///
+    /// It provides formatting, range formatting and on-type formatting,
+    /// document symbols and diagnostics for parse errors.
+    Lsp(Flags),
 }
 
 #[derive(StructOpt)]
@@ -21,6 +56,108 @@ struct Input {
     #[structopt(parse(from_os_str))]
     file: PathBuf,
 
+    /// Only format the module fields overlapping a range of lines,
+    /// such as `40-80`, leaving the rest of the file unchanged.
+    #[structopt(long)]
+    lines: Option<LineRange>,
+
+    /// Copy instructions and module fields that cannot be formatted as they are,
+    /// with a warning, instead of failing.
+    /// Cannot be combined with `--minify` or `--dedupe-types`.
+    #[structopt(long, conflicts_with = "lines")]
+    best_effort: bool,
+
+    /// Format the fields of a file with syntax errors that parse on their own,
+    /// leaving the others as they are and reporting the errors as warnings.
+    /// `check` still fails if there are syntax errors.
+    #[structopt(long, conflicts_with_all = &["lines", "best-effort"])]
+    recover: bool,
+
+    #[structopt(flatten)]
+    flags: Flags,
+}
+
+/// A range of lines, 1-based and inclusive at both ends.
+#[derive(Clone, Copy, Debug)]
+struct LineRange {
+    start: usize,
+    end: usize,
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid line range `{}`, expected `<start>-<end>`", s);
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let start: usize = start.trim().parse().map_err(|_| invalid())?;
+        let end: usize = end.trim().parse().map_err(|_| invalid())?;
+        if start == 0 || end < start {
+            return Err(invalid());
+        }
+        Ok(LineRange { start, end })
+    }
+}
+
+impl LineRange {
+    /// The byte range of `source` covered by these lines.
+    fn to_bytes(self, source: &str) -> Range<usize> {
+        let starts: Vec<usize> = iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        let line_start = |line: usize| starts.get(line - 1).copied().unwrap_or(source.len());
+        line_start(self.start)..line_start(self.end + 1)
+    }
+}
+
+#[derive(StructOpt)]
+struct FixInput {
+    #[structopt(flatten)]
+    input: Input,
+
+    /// Keep a copy of the original file as `<file>.orig`.
+    #[structopt(long)]
+    backup: bool,
+}
+
+#[derive(StructOpt)]
+struct CheckInput {
+    #[structopt(flatten)]
+    input: Input,
+
+    /// Report results as `human`, `json`, `checkstyle`, `sarif` or `github` [default: human].
+    #[structopt(long)]
+    message_format: Option<MessageFormat>,
+
+    /// Print nothing; report the result through the exit code only.
+    #[structopt(short, long, conflicts_with_all = &["list-different", "message-format"])]
+    quiet: bool,
+
+    /// Print the path of the input file if it is not formatted correctly.
+    #[structopt(short, long, conflicts_with = "message-format")]
+    list_different: bool,
+}
+
+#[derive(StructOpt)]
+struct ConfigInput {
+    /// Print the effective settings.
+    #[structopt(long)]
+    print: bool,
+
+    /// The file or directory to resolve the configuration for.
+    #[structopt(parse(from_os_str), default_value = ".")]
+    path: PathBuf,
+
+    #[structopt(flatten)]
+    flags: Flags,
+}
+
+#[derive(StructOpt)]
+struct VerifyInput {
+    /// The file or directory to verify.
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
     #[structopt(flatten)]
     flags: Flags,
 }
@@ -28,54 +165,435 @@ struct Input {
 #[derive(StructOpt, Debug)]
 struct Flags {
     /// Perform name resolution.
-    #[structopt(short, long)]
+    #[structopt(short, long, overrides_with = "no-resolve-names")]
     resolve_names: bool,
+
+    /// Do not perform name resolution, even if the configuration asks for it.
+    #[structopt(long, overrides_with = "resolve-names")]
+    no_resolve_names: bool,
+
+    /// Replace numeric indices with the ids of the items they refer to.
+    #[structopt(long, overrides_with = "no-name-indices")]
+    name_indices: bool,
+
+    /// Keep numeric indices, even if the configuration asks to replace them.
+    #[structopt(long, overrides_with = "name-indices")]
+    no_name_indices: bool,
+
+    /// Give anonymous functions, types, parameters, locals and block labels
+    /// generated ids, and refer to them by those ids.
+    #[structopt(long, overrides_with = "no-synthesize-names")]
+    synthesize_names: bool,
+
+    /// Do not generate ids, even if the configuration asks for them.
+    #[structopt(long, overrides_with = "synthesize-names")]
+    no_synthesize_names: bool,
+
+    /// The maximum line width [default: 100].
+    #[structopt(short, long)]
+    max_width: Option<usize>,
+
+    /// Indent with `tabs` or `spaces` [default: tabs].
+    #[structopt(long)]
+    indent_style: Option<IndentStyle>,
+
+    /// The number of spaces per indentation level,
+    /// or the width of a tab when measuring lines [default: 4].
+    #[structopt(long)]
+    indent_width: Option<usize>,
+
+    /// Write instructions `flat`, `folded`, or `preserve` the style
+    /// of the source [default: flat].
+    #[structopt(long)]
+    expression_style: Option<ExpressionStyle>,
+
+    /// Make branches refer to blocks by `labels` or by `depths`,
+    /// or `preserve` the references of the source [default: preserve].
+    #[structopt(long)]
+    branch_style: Option<BranchStyle>,
+
+    /// Write the label of a block after its `else` and `end`.
+    #[structopt(long, overrides_with = "no-label-ends")]
+    label_ends: bool,
+
+    /// Do not write labels after `else` and `end`, even if the configuration asks for them.
+    #[structopt(long, overrides_with = "label-ends")]
+    no_label_ends: bool,
+
+    /// Write the module on as few lines as possible, without ids or annotations.
+    #[structopt(long, overrides_with = "no-minify")]
+    minify: bool,
+
+    /// Do not minify the module, even if the configuration asks for it.
+    #[structopt(long, overrides_with = "minify")]
+    no_minify: bool,
+
+    /// Order module fields by `sections` of the binary format, by section and then
+    /// import and export `names`, or `preserve` the order of the source [default: preserve].
+    /// `names` changes the order in which a host sees the exports,
+    /// and only sorts imports as far as imports of the same kind keep their order.
+    #[structopt(long)]
+    field_order: Option<FieldOrder>,
+
+    /// Write imports `inline` in the items they import, as `separate` fields,
+    /// or `preserve` their placement in the source [default: preserve].
+    #[structopt(long)]
+    imports: Option<Placement>,
+
+    /// Write exports `inline` in the items they export, as `separate` fields,
+    /// or `preserve` their placement in the source [default: preserve].
+    #[structopt(long)]
+    exports: Option<Placement>,
+
+    /// Write type uses with an `explicit` type reference and signature, only the `inline`
+    /// signature, only a type `reference`, or `preserve` them [default: preserve].
+    #[structopt(long)]
+    type_uses: Option<TypeUseStyle>,
+
+    /// Remove function types that are identical to an earlier one.
+    #[structopt(long, overrides_with = "no-dedupe-types")]
+    dedupe_types: bool,
+
+    /// Keep identical function types, even if the configuration asks to remove them.
+    #[structopt(long, overrides_with = "dedupe-types")]
+    no_dedupe_types: bool,
+}
+
+impl From<&Flags> for Config {
+    fn from(flags: &Flags) -> Config {
+        Config {
+            resolve_names: switch(flags.resolve_names, flags.no_resolve_names),
+            name_indices: switch(flags.name_indices, flags.no_name_indices),
+            synthesize_names: switch(flags.synthesize_names, flags.no_synthesize_names),
+            max_width: flags.max_width,
+            indent_style: flags.indent_style,
+            indent_width: flags.indent_width,
+            expression_style: flags.expression_style,
+            branch_style: flags.branch_style,
+            label_ends: switch(flags.label_ends, flags.no_label_ends),
+            minify: switch(flags.minify, flags.no_minify),
+            field_order: flags.field_order,
+            imports: flags.imports,
+            exports: flags.exports,
+            type_uses: flags.type_uses,
+            dedupe_types: switch(flags.dedupe_types, flags.no_dedupe_types),
+            ..Config::default()
+        }
+    }
+}
+
+/// The value of a setting that can be turned on with one flag and off with another,
+/// or `None` to leave it to the configuration.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        (false, false) => None,
+    }
+}
+
+/// How the result of `check` is reported.
+enum Report {
+    Quiet,
+    ListDifferent,
+    Messages(MessageFormat),
 }
 
 enum Command {
-    Fix,
-    Check,
+    Fix { backup: bool },
+    Check { report: Report },
     Print,
 }
 
-impl From<Cli> for (Command, Input) {
-    fn from(cli: Cli) -> (Command, Input) {
-        match cli {
-            Cli::Print(input) => (Command::Print, input),
-            Cli::Fix(input) => (Command::Fix, input),
-            Cli::Check(input) => (Command::Check, input),
+/// The configuration that applies to an input file.
+struct Resolved {
+    /// The files the settings were read from, in order of precedence.
+    files: Vec<PathBuf>,
+    config: Config,
+}
+
+/// Resolve the settings for `path` from `flags`, the nearest `wasmfmt.toml`
+/// and any `.editorconfig` files, in that order of precedence.
+fn resolve_config(path: &Path, flags: &Flags) -> Result<Resolved, Error> {
+    let (files, config) = Config::resolve(path)?;
+    Ok(Resolved {
+        files,
+        config: config.merge(flags.into()),
+    })
+}
+
+/// The file is not formatted correctly.
+const EXIT_UNFORMATTED: i32 = 1;
+/// The input, a configuration file or the command line cannot be parsed.
+const EXIT_INVALID: i32 = 2;
+/// The input contains constructs that cannot be formatted,
+/// or formatting it would change the module.
+const EXIT_UNSUPPORTED: i32 = 3;
+/// A file cannot be read or written.
+const EXIT_IO: i32 = 4;
+
+fn main() {
+    let cli = Cli::from_args_safe().unwrap_or_else(|err| {
+        if err.use_stderr() {
+            eprintln!("{}", err.message);
+            process::exit(EXIT_INVALID);
         }
+        err.exit()
+    });
+    if let Err(err) = run(cli) {
+        eprintln!("error: {}", err);
+        process::exit(exit_code(&err));
+    }
+}
+
+fn exit_code(err: &Error) -> i32 {
+    match err {
+        Error::Io(..) => EXIT_IO,
+        Error::Config { .. } | Error::Parse(..) => EXIT_INVALID,
+        Error::Unsupported(..) | Error::Verify(..) | Error::Idempotency(..) => EXIT_UNSUPPORTED,
     }
 }
 
-fn main() -> Result<(), Error> {
-    let (command, input) = Cli::from_args().into();
+fn run(cli: Cli) -> Result<(), Error> {
+    let (command, input) = match cli {
+        Cli::Print(input) => (Command::Print, input),
+        Cli::Fix(FixInput { input, backup }) => (Command::Fix { backup }, input),
+        Cli::Check(CheckInput {
+            input,
+            message_format,
+            quiet,
+            list_different,
+        }) => {
+            let report = if quiet {
+                Report::Quiet
+            } else if list_different {
+                Report::ListDifferent
+            } else {
+                Report::Messages(message_format.unwrap_or_default())
+            };
+            (Command::Check { report }, input)
+        }
+        Cli::Config(input) => return config(input),
+        Cli::Verify(input) => return verify_files(input),
+        Cli::Lsp(flags) => {
+            let (connection, io_threads) = Connection::stdio();
+            serve_lsp(connection, (&flags).into())?;
+            return Ok(io_threads.join()?);
+        }
+    };
 
     let source = fs::read_to_string(input.file.as_path())?;
+    let resolved = resolve_config(&input.file, &input.flags)?;
 
-    let formatted = fmt(
-        &source,
-        Options {
-            resolve_names: input.flags.resolve_names,
-        },
-    );
+    let options = resolved.config.to_options();
+    let mut invalid = false;
+    let mut removed_types = 0;
+    let formatted = match input.lines {
+        Some(lines) => fmt_range(&source, lines.to_bytes(&source), options.clone())?,
+        None if input.best_effort => {
+            let (formatted, fallbacks) = fmt_best_effort(&source, options.clone())?;
+            for fallback in fallbacks {
+                eprintln!("warning: {}: {}", input.file.display(), fallback);
+            }
+            formatted
+        }
+        None if input.recover => {
+            let (formatted, diagnostics) = fmt_recover(&source, options.clone());
+            for diagnostic in &diagnostics {
+                eprintln!("warning: {}:{}", input.file.display(), diagnostic);
+            }
+            invalid = !diagnostics.is_empty();
+            formatted
+        }
+        None => {
+            let (formatted, summary) = fmt_with_summary(&source, options.clone())?;
+            for warning in summary.warnings {
+                eprintln!("warning: {}: {}", input.file.display(), warning);
+            }
+            removed_types = summary.removed_types;
+            formatted
+        }
+    };
 
+    // Code with syntax errors cannot be encoded.
+    if let (Command::Fix { .. } | Command::Check { .. }, false) = (&command, invalid) {
+        verify(&source, &formatted, &options)?;
+    }
     match command {
-        Command::Fix => {
-            fs::write(input.file, formatted)?;
+        Command::Fix { backup } => {
+            if formatted != source {
+                write_in_place(&input.file, &formatted, backup)?;
+            }
+            report_removed_types(&input.file, removed_types);
             Ok(())
         }
-        Command::Check => {
-            if let Some(diff) = Diff::from(&source, &formatted) {
-                io::stdout().write_fmt(format_args!("{}", diff))?;
-                io::stdout().flush().unwrap();
-                std::process::exit(1);
-            };
+        Command::Check { report } => {
+            let diff = Diff::from(&source, &formatted);
+            let unformatted = diff.is_some();
+            let mut stdout = io::stdout();
+            match report {
+                Report::Quiet => {}
+                Report::ListDifferent => {
+                    if unformatted {
+                        writeln!(stdout, "{}", input.file.display())?;
+                    }
+                }
+                Report::Messages(message_format) => {
+                    let report = FileReport {
+                        path: &input.file,
+                        diff,
+                    };
+                    stdout.write_all(message_format.render(&[report]).as_bytes())?;
+                }
+            }
+            stdout.flush()?;
+            if invalid {
+                process::exit(EXIT_INVALID);
+            }
+            if unformatted {
+                process::exit(EXIT_UNFORMATTED);
+            }
             Ok(())
         }
         Command::Print => {
             io::stdout().write_all(formatted.as_bytes())?;
+            report_removed_types(&input.file, removed_types);
             Ok(())
         }
     }
 }
+
+/// Report the duplicate types that `--dedupe-types` removed, if there were any.
+fn report_removed_types(file: &Path, removed: usize) {
+    if removed > 0 {
+        let types = if removed == 1 { "type" } else { "types" };
+        eprintln!(
+            "{}: removed {} duplicate {}",
+            file.display(),
+            removed,
+            types
+        );
+    }
+}
+
+/// Replace the contents of `path` without leaving a partially written file behind.
+///
+/// The contents are written to a temporary file in the same directory,
+/// which is flushed to disk and then renamed over the original.
+/// Symbolic links are followed, so the link itself is kept,
+/// and the permissions of the original file are preserved.
+fn write_in_place(path: &Path, contents: &str, backup: bool) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let permissions = fs::metadata(&path)?.permissions();
+    let file_name = path.file_name().expect("file name").to_string_lossy();
+    let dir = path.parent().expect("parent directory");
+    if backup {
+        fs::copy(&path, dir.join(format!("{}.orig", file_name)))?;
+    }
+    let temp = dir.join(format!(".{}.{}.tmp", file_name, process::id()));
+    let result = (|| {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+        file.write_all(contents.as_bytes())?;
+        file.set_permissions(permissions)?;
+        file.sync_all()?;
+        fs::rename(&temp, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+    // Persist the rename itself.
+    #[cfg(unix)]
+    fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn config(input: ConfigInput) -> Result<(), Error> {
+    if !input.print {
+        eprintln!("nothing to do; use `wasmfmt config --print` to print the effective settings");
+        return Ok(());
+    }
+    let resolved = resolve_config(&input.path, &input.flags)?;
+    let effective = Config::from(&resolved.config.to_options());
+    let mut stdout = io::stdout();
+    if resolved.files.is_empty() {
+        writeln!(stdout, "# no configuration file found")?;
+    }
+    for file in resolved.files {
+        writeln!(stdout, "# {}", file.display())?;
+    }
+    write!(stdout, "{}", effective)?;
+    Ok(())
+}
+
+fn verify_files(input: VerifyInput) -> Result<(), Error> {
+    let files = wat_files(&input.path)?;
+    let mut failures = 0;
+    let mut stdout = io::stdout();
+    for file in &files {
+        if let Err(message) = verify_file(file, &input.flags)? {
+            failures += 1;
+            writeln!(stdout, "{}: {}", file.display(), message)?;
+        }
+    }
+    writeln!(
+        stdout,
+        "verified {} files, {} failed",
+        files.len(),
+        failures
+    )?;
+    if failures > 0 {
+        stdout.flush()?;
+        process::exit(EXIT_UNFORMATTED);
+    }
+    Ok(())
+}
+
+/// Verify a single file, returning a description of the problem if it fails.
+fn verify_file(file: &Path, flags: &Flags) -> Result<Result<(), String>, Error> {
+    let source = fs::read_to_string(file)?;
+    let options = resolve_config(file, flags)?.config.to_options();
+    // Report panics as failures of the file being verified rather than aborting the run.
+    let result = panic::catch_unwind(|| {
+        let formatted = try_fmt(&source, options.clone())?;
+        verify(&source, &formatted, &options)?;
+        verify_idempotent(&source, &options)
+    });
+    Ok(match result {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(format!("the formatter panicked: {}", message))
+        }
+    })
+}
+
+/// The `.wat` files in a directory tree, in a stable order,
+/// or `path` itself if it is a file.
+///
+/// Symbolic links to directories are not followed, since they may lead back up the tree.
+fn wat_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            files.extend(wat_files(&path)?);
+        } else if path.is_file() && path.extension().is_some_and(|extension| extension == "wat") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,371 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The default maximum line width.
+pub const DEFAULT_MAX_WIDTH: usize = 100;
+
+/// The default number of columns per level of indentation.
+pub const DEFAULT_INDENT_WIDTH: usize = 4;
+
+/// Options that control how source code is formatted.
+///
+/// Options are built from [`Options::default`], so that more of them can be added.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Options {
+    /// Whether ids are replaced with the numeric indices of the items they refer to.
+    pub resolve_names: bool,
+    /// Whether numeric indices are replaced with the ids of the items they refer to.
+    pub name_indices: bool,
+    /// Whether anonymous functions, types, parameters, locals and block labels are given ids,
+    /// which numeric indices are then replaced with.
+    pub synthesize_names: bool,
+    /// The column at which long constructs are wrapped onto multiple lines.
+    pub max_width: usize,
+    /// The characters used for indentation.
+    pub indent_style: IndentStyle,
+    /// The number of spaces per level of indentation,
+    /// or the number of columns a tab counts as when measuring line widths.
+    pub indent_width: usize,
+    /// The character sequence that ends each line.
+    pub end_of_line: LineEnding,
+    /// Whether the output ends with a line ending.
+    pub insert_final_newline: bool,
+    /// How instructions in function bodies are written.
+    pub expression_style: ExpressionStyle,
+    /// How branch instructions refer to the blocks they target.
+    pub branch_style: BranchStyle,
+    /// Whether `else` and `end` are followed by the label of their block.
+    pub label_ends: bool,
+    /// Whether the module is written on as few lines as possible,
+    /// without ids or annotations and with the shortest form of each number.
+    pub minify: bool,
+    /// The order in which the fields of a module are written.
+    pub field_order: FieldOrder,
+    /// Whether imports are written in the items they import or as separate fields.
+    pub imports: Placement,
+    /// Whether exports are written in the items they export or as separate fields.
+    pub exports: Placement,
+    /// Whether type uses are written with a type reference, a signature or both.
+    pub type_uses: TypeUseStyle,
+    /// Whether function types with the same parameters and results as an earlier one are removed.
+    pub dedupe_types: bool,
+}
+
+impl Options {
+    /// The default options, usable in constants.
+    pub(crate) const DEFAULT: Options = Options {
+        resolve_names: false,
+        name_indices: false,
+        synthesize_names: false,
+        max_width: DEFAULT_MAX_WIDTH,
+        indent_style: IndentStyle::Tabs,
+        indent_width: DEFAULT_INDENT_WIDTH,
+        end_of_line: LineEnding::Lf,
+        insert_final_newline: true,
+        expression_style: ExpressionStyle::Flat,
+        branch_style: BranchStyle::Preserve,
+        label_ends: false,
+        minify: false,
+        field_order: FieldOrder::Preserve,
+        imports: Placement::Preserve,
+        exports: Placement::Preserve,
+        type_uses: TypeUseStyle::Preserve,
+        dedupe_types: false,
+    };
+
+    /// The string used for a single level of indentation.
+    pub(crate) fn indent_unit(&self) -> String {
+        match self.indent_style {
+            IndentStyle::Tabs => "\t".to_string(),
+            IndentStyle::Spaces => " ".repeat(self.indent_width),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The characters used for indentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    Tabs,
+    Spaces,
+}
+
+impl FromStr for IndentStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tabs" | "tab" => Ok(IndentStyle::Tabs),
+            "spaces" | "space" => Ok(IndentStyle::Spaces),
+            _ => Err(format!(
+                "invalid indent style `{}`, expected `tabs` or `spaces`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for IndentStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndentStyle::Tabs => f.write_str("tabs"),
+            IndentStyle::Spaces => f.write_str("spaces"),
+        }
+    }
+}
+
+/// The character sequence that ends each line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            "cr" => Ok(LineEnding::Cr),
+            _ => Err(format!(
+                "invalid line ending `{}`, expected `lf`, `crlf` or `cr`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineEnding::Lf => f.write_str("lf"),
+            LineEnding::Crlf => f.write_str("crlf"),
+            LineEnding::Cr => f.write_str("cr"),
+        }
+    }
+}
+
+/// How instructions in function bodies are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpressionStyle {
+    /// One instruction per line, in stack order.
+    Flat,
+    /// Nested S-expressions, where instruction operands are written as children.
+    Folded,
+    /// Folded where the source was folded, and flat elsewhere.
+    Preserve,
+}
+
+impl FromStr for ExpressionStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(ExpressionStyle::Flat),
+            "folded" => Ok(ExpressionStyle::Folded),
+            "preserve" => Ok(ExpressionStyle::Preserve),
+            _ => Err(format!(
+                "invalid expression style `{}`, expected `flat`, `folded` or `preserve`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExpressionStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionStyle::Flat => f.write_str("flat"),
+            ExpressionStyle::Folded => f.write_str("folded"),
+            ExpressionStyle::Preserve => f.write_str("preserve"),
+        }
+    }
+}
+
+/// How branch instructions refer to the blocks they target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BranchStyle {
+    /// Labels and depths are written as they are in the source.
+    Preserve,
+    /// Every block that is the target of a branch is labelled, and branches refer to labels.
+    Labels,
+    /// Branches refer to relative depths, and blocks are not labelled.
+    Depths,
+}
+
+impl FromStr for BranchStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(BranchStyle::Preserve),
+            "labels" => Ok(BranchStyle::Labels),
+            "depths" => Ok(BranchStyle::Depths),
+            _ => Err(format!(
+                "invalid branch style `{}`, expected `preserve`, `labels` or `depths`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BranchStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BranchStyle::Preserve => f.write_str("preserve"),
+            BranchStyle::Labels => f.write_str("labels"),
+            BranchStyle::Depths => f.write_str("depths"),
+        }
+    }
+}
+
+/// The order in which the fields of a module are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldOrder {
+    /// Fields are written in the order of the source.
+    Preserve,
+    /// Fields are written in the order of the sections of the binary format:
+    /// types, imports, functions, tables, memories, globals, exports, start, elements and data.
+    Sections,
+    /// Fields are written in the order of the sections,
+    /// with imports and exports sorted by name.
+    /// This changes the order in which a host sees the exports.
+    /// Imports of the same kind keep their order, since it determines their indices,
+    /// so imports are only sorted as far as that allows.
+    Names,
+}
+
+impl FromStr for FieldOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(FieldOrder::Preserve),
+            "sections" => Ok(FieldOrder::Sections),
+            "names" => Ok(FieldOrder::Names),
+            _ => Err(format!(
+                "invalid field order `{}`, expected `preserve`, `sections` or `names`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FieldOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldOrder::Preserve => f.write_str("preserve"),
+            FieldOrder::Sections => f.write_str("sections"),
+            FieldOrder::Names => f.write_str("names"),
+        }
+    }
+}
+
+/// Where imports or exports are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Placement {
+    /// Imports and exports are written where they are in the source.
+    Preserve,
+    /// Imports and exports are written in the functions, tables, memories and globals
+    /// that they define or export.
+    Inline,
+    /// Imports and exports are written as separate module fields.
+    Separate,
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(Placement::Preserve),
+            "inline" => Ok(Placement::Inline),
+            "separate" => Ok(Placement::Separate),
+            _ => Err(format!(
+                "invalid placement `{}`, expected `preserve`, `inline` or `separate`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Placement::Preserve => f.write_str("preserve"),
+            Placement::Inline => f.write_str("inline"),
+            Placement::Separate => f.write_str("separate"),
+        }
+    }
+}
+
+/// How type uses, such as the signature of a function, are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeUseStyle {
+    /// Type uses are written as in the source.
+    Preserve,
+    /// Type uses are written with both a `(type ...)` reference and their parameters and results,
+    /// where the module defines a matching type.
+    Explicit,
+    /// Type uses are written with only their parameters and results,
+    /// where this refers to the same type.
+    Inline,
+    /// Type uses are written with only a `(type ...)` reference,
+    /// adding a type definition where the module has none.
+    Reference,
+}
+
+impl FromStr for TypeUseStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(TypeUseStyle::Preserve),
+            "explicit" => Ok(TypeUseStyle::Explicit),
+            "inline" => Ok(TypeUseStyle::Inline),
+            "reference" => Ok(TypeUseStyle::Reference),
+            _ => Err(format!(
+                "invalid type use style `{}`, expected `preserve`, `explicit`, `inline` or `reference`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TypeUseStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeUseStyle::Preserve => f.write_str("preserve"),
+            TypeUseStyle::Explicit => f.write_str("explicit"),
+            TypeUseStyle::Inline => f.write_str("inline"),
+            TypeUseStyle::Reference => f.write_str("reference"),
+        }
+    }
+}
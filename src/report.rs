@@ -0,0 +1,275 @@
+use crate::diff::{Diff, Hunk};
+use serde_json::json;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The message reported for every hunk of unformatted code.
+const MESSAGE: &str = "Code is not formatted correctly";
+
+/// How the results of checking files are reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// The source and the formatted code, for people.
+    #[default]
+    Human,
+    /// One JSON object per line for each file.
+    Json,
+    /// A Checkstyle XML report.
+    Checkstyle,
+    /// A SARIF 2.1.0 log.
+    Sarif,
+    /// GitHub Actions workflow commands that annotate the changed lines.
+    Github,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            "checkstyle" => Ok(MessageFormat::Checkstyle),
+            "sarif" => Ok(MessageFormat::Sarif),
+            "github" => Ok(MessageFormat::Github),
+            _ => Err(format!(
+                "invalid message format `{}`, expected `human`, `json`, `checkstyle`, `sarif` or `github`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MessageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageFormat::Human => f.write_str("human"),
+            MessageFormat::Json => f.write_str("json"),
+            MessageFormat::Checkstyle => f.write_str("checkstyle"),
+            MessageFormat::Sarif => f.write_str("sarif"),
+            MessageFormat::Github => f.write_str("github"),
+        }
+    }
+}
+
+/// The result of checking a single file.
+pub struct FileReport<'a> {
+    pub path: &'a Path,
+    /// The difference between the file and its formatted code,
+    /// or `None` if the file is formatted correctly.
+    pub diff: Option<Diff<'a, 'a>>,
+}
+
+impl<'a> FileReport<'a> {
+    fn status(&self) -> &'static str {
+        match self.diff {
+            Some(..) => "unformatted",
+            None => "formatted",
+        }
+    }
+
+    fn hunks(&self) -> Vec<Hunk> {
+        self.diff.as_ref().map(Diff::hunks).unwrap_or_default()
+    }
+}
+
+/// The lines of the source that a hunk applies to, inclusive at both ends.
+/// Hunks that only insert lines are reported at the line they precede.
+fn source_lines(hunk: &Hunk) -> (usize, usize) {
+    let start = hunk.source.start;
+    (start, (hunk.source.end - 1).max(start))
+}
+
+impl MessageFormat {
+    /// Render the results of checking files.
+    pub fn render(&self, reports: &[FileReport]) -> String {
+        match self {
+            MessageFormat::Human => reports
+                .iter()
+                .filter_map(|report| report.diff.as_ref())
+                .map(|diff| diff.to_string())
+                .collect(),
+            MessageFormat::Json => reports.iter().map(render_json).collect(),
+            MessageFormat::Checkstyle => render_checkstyle(reports),
+            MessageFormat::Sarif => render_sarif(reports),
+            MessageFormat::Github => reports.iter().map(render_github).collect(),
+        }
+    }
+}
+
+fn render_json(report: &FileReport) -> String {
+    let hunks: Vec<_> = report
+        .hunks()
+        .iter()
+        .map(|hunk| {
+            json!({
+                "source": { "start": hunk.source.start, "end": hunk.source.end },
+                "formatted": { "start": hunk.formatted.start, "end": hunk.formatted.end },
+            })
+        })
+        .collect();
+    let record = json!({
+        "path": report.path.display().to_string(),
+        "status": report.status(),
+        "hunks": hunks,
+    });
+    format!("{}\n", record)
+}
+
+fn render_checkstyle(reports: &[FileReport]) -> String {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str("<checkstyle version=\"4.3\">\n");
+    for report in reports {
+        let path = escape_xml(&report.path.display().to_string());
+        output.push_str(&format!("  <file name=\"{}\">\n", path));
+        for hunk in report.hunks() {
+            let (line, _) = source_lines(&hunk);
+            output.push_str(&format!(
+                "    <error line=\"{}\" severity=\"warning\" message=\"{}\" source=\"wasmfmt\"/>\n",
+                line, MESSAGE
+            ));
+        }
+        output.push_str("  </file>\n");
+    }
+    output.push_str("</checkstyle>\n");
+    output
+}
+
+fn render_sarif(reports: &[FileReport]) -> String {
+    let results: Vec<_> = reports
+        .iter()
+        .flat_map(|report| {
+            let uri = report.path.display().to_string().replace('\\', "/");
+            report
+                .hunks()
+                .iter()
+                .map(|hunk| {
+                    let (start, end) = source_lines(hunk);
+                    json!({
+                        "ruleId": "formatting",
+                        "level": "warning",
+                        "message": { "text": MESSAGE },
+                        "locations": [{
+                            "physicalLocation": {
+                                "artifactLocation": { "uri": uri },
+                                "region": { "startLine": start, "endLine": end },
+                            },
+                        }],
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "wasmfmt",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": [{
+                        "id": "formatting",
+                        "shortDescription": { "text": MESSAGE },
+                    }],
+                },
+            },
+            "results": results,
+        }],
+    });
+    format!("{:#}\n", log)
+}
+
+fn render_github(report: &FileReport) -> String {
+    let path = escape_github_property(&report.path.display().to_string());
+    report
+        .hunks()
+        .iter()
+        .map(|hunk| {
+            let (start, end) = source_lines(hunk);
+            format!(
+                "::warning file={},line={},endLine={}::{}\n",
+                path, start, end, MESSAGE
+            )
+        })
+        .collect()
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn escape_github_property(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FileReport, MessageFormat};
+    use crate::diff::Diff;
+    use std::path::Path;
+
+    fn reports<'a>(source: &'a str, formatted: &'a str) -> Vec<FileReport<'a>> {
+        vec![
+            FileReport {
+                path: Path::new("a.wat"),
+                diff: Diff::from(source, formatted),
+            },
+            FileReport {
+                path: Path::new("b.wat"),
+                diff: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn json_should_emit_one_record_per_file() {
+        let output = MessageFormat::Json.render(&reports("a\nb\nc\n", "a\nB\nc\n"));
+        assert_eq!(
+            output,
+            concat!(
+                r#"{"hunks":[{"formatted":{"end":3,"start":2},"source":{"end":3,"start":2}}],"path":"a.wat","status":"unformatted"}"#,
+                "\n",
+                r#"{"hunks":[],"path":"b.wat","status":"formatted"}"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn github_should_annotate_changed_lines() {
+        let output = MessageFormat::Github.render(&reports("a\nb\nc\nd\n", "a\nB\nC\nd\n"));
+        assert_eq!(
+            output,
+            "::warning file=a.wat,line=2,endLine=3::Code is not formatted correctly\n"
+        );
+    }
+
+    #[test]
+    fn checkstyle_should_list_every_file() {
+        let output = MessageFormat::Checkstyle.render(&reports("a\n", "b\n"));
+        assert!(output.contains("<file name=\"a.wat\">\n    <error line=\"1\""));
+        assert!(output.contains("<file name=\"b.wat\">\n  </file>"));
+    }
+
+    #[test]
+    fn sarif_should_contain_a_result_per_hunk() {
+        let output = MessageFormat::Sarif.render(&reports("a\nb\n", "a\n"));
+        let log: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        let region = &results[0]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startLine"], 2);
+        assert_eq!(region["endLine"], 2);
+    }
+}
@@ -0,0 +1,488 @@
+use crate::error::Error;
+use crate::fmt::dedupe::dedupe_types;
+use crate::fmt::{describe_field, fmt_fields, try_fmt};
+use crate::options::{FieldOrder, Options, Placement};
+use wast::core::{FuncKind, ModuleField, ModuleKind};
+use wast::parser::{parse, ParseBuffer};
+use wast::Wat;
+
+/// The id of the import section.
+const IMPORT_SECTION: u8 = 2;
+
+/// The id of the export section.
+const EXPORT_SECTION: u8 = 7;
+
+/// The id of the code section.
+const CODE_SECTION: u8 = 10;
+
+/// The id of custom sections.
+const CUSTOM_SECTION: u8 = 0;
+
+/// Check that `formatted` describes the same module as `source`.
+///
+/// Both are encoded to the binary format and the results are compared,
+/// ignoring the `name` section, which only records identifiers,
+/// and, if `options` remove duplicate types, which of several identical function types
+/// is referred to.
+/// The order of exports and of imports of different kinds, which does not change any index,
+/// is only ignored where `options` move them.
+/// If `source` cannot be encoded, for example because it refers to
+/// an undefined identifier, it is only checked that `formatted` parses.
+pub fn verify(source: &str, formatted: &str, options: &Options) -> Result<(), Error> {
+    let moved = Moved::new(options);
+    let before = match encode(source, false) {
+        Ok(before) => before,
+        Err(..) => {
+            return match parses(formatted) {
+                Ok(()) => Ok(()),
+                Err(message) => Err(invalid(message)),
+            }
+        }
+    };
+    let after = encode(formatted, false).map_err(invalid)?;
+    let result = compare(&before, &after, &moved);
+    if result.is_err() && options.dedupe_types {
+        // The formatted code may refer to one of several identical types instead of another.
+        if let (Ok(before), Ok(after)) = (encode(source, true), encode(formatted, true)) {
+            if compare(&before, &after, &moved).is_ok() {
+                return Ok(());
+            }
+        }
+    }
+    result
+}
+
+fn invalid(message: String) -> Error {
+    Error::Verify(format!("the formatted code is invalid: {}", message))
+}
+
+/// Check that `source` parses, without resolving or encoding it.
+fn parses(source: &str) -> Result<(), String> {
+    let buffer = ParseBuffer::new(source).map_err(|err| err.to_string())?;
+    parse::<Wat>(&buffer).map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+fn compare(before: &Encoded, after: &Encoded, moved: &Moved) -> Result<(), Error> {
+    let (before_sections, after_sections) = match (sections(&before.bytes), sections(&after.bytes))
+    {
+        (Some(before), Some(after)) => (before, after),
+        _ => return Err(Error::Verify("the module could not be decoded".to_string())),
+    };
+    for (old, new) in before_sections.iter().zip(&after_sections) {
+        if old == new || same_entries(old, new, moved) {
+            continue;
+        }
+        if old.0 == CODE_SECTION && new.0 == CODE_SECTION {
+            if let Some(function) = first_different_body(old.1, new.1) {
+                return Err(Error::Verify(format!(
+                    "the body of {} is different",
+                    before.function_name(function)
+                )));
+            }
+        }
+        return Err(Error::Verify(format!(
+            "the {} section is different",
+            section_name(old.0)
+        )));
+    }
+    if before_sections.len() != after_sections.len() {
+        return Err(Error::Verify(
+            "the number of sections is different".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that formatting `source` is idempotent,
+/// that is, that formatting the formatted code again does not change it.
+pub fn verify_idempotent(source: &str, options: &Options) -> Result<(), Error> {
+    let formatted = try_fmt(source, options.clone())?;
+    if try_fmt(&formatted, options.clone())? == formatted {
+        return Ok(());
+    }
+    let before = fmt_fields(source, options)?;
+    let after = fmt_fields(&formatted, options)?;
+    let message = before
+        .iter()
+        .zip(&after)
+        .enumerate()
+        .find(|(_, (before, after))| before.1 != after.1)
+        .map(|(index, (field, _))| format!("module field {} (`{}`) changes", index, field.0))
+        .unwrap_or_else(|| "the module changes".to_string());
+    Err(Error::Idempotency(message))
+}
+
+/// A module encoded to the binary format.
+struct Encoded {
+    bytes: Vec<u8>,
+    /// The index of the module field defining each function in the code section,
+    /// together with a description of the field.
+    functions: Vec<(usize, String)>,
+}
+
+impl Encoded {
+    /// A description of the function at `index` in the code section.
+    fn function_name(&self, index: usize) -> String {
+        match self.functions.get(index) {
+            Some((field, description)) => format!("module field {} (`{}`)", field, description),
+            None => format!("function {}", index),
+        }
+    }
+}
+
+/// Encode `source`, removing duplicate function types first if `dedupe` is set.
+fn encode(source: &str, dedupe: bool) -> Result<Encoded, String> {
+    let buffer = ParseBuffer::new(source).map_err(|err| err.to_string())?;
+    let mut wat = parse::<Wat>(&buffer).map_err(|err| err.to_string())?;
+    let mut functions = Vec::new();
+    if let Wat::Module(module) = &mut wat {
+        if let ModuleKind::Text(fields) = &module.kind {
+            for (index, field) in fields.iter().enumerate() {
+                if let ModuleField::Func(func) = field {
+                    if let FuncKind::Inline { .. } = func.kind {
+                        functions.push((index, describe_field(field)));
+                    }
+                }
+            }
+        }
+        if dedupe {
+            dedupe_types(&mut module.kind);
+        }
+    }
+    let bytes = wat.encode().map_err(|err| err.to_string())?;
+    Ok(Encoded { bytes, functions })
+}
+
+/// Split a binary module into its sections, without the `name` section.
+fn sections(bytes: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut rest = bytes.get(8..)?;
+    let mut sections = Vec::new();
+    while let Some((&id, tail)) = rest.split_first() {
+        let (size, tail) = read_u32(tail)?;
+        let payload = tail.get(..size as usize)?;
+        rest = &tail[size as usize..];
+        if id == CUSTOM_SECTION && custom_section_name(payload) == Some(b"name") {
+            continue;
+        }
+        sections.push((id, payload));
+    }
+    Some(sections)
+}
+
+/// The name of a section, as used in error messages.
+fn section_name(id: u8) -> &'static str {
+    match id {
+        0 => "custom",
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data count",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+fn custom_section_name(payload: &[u8]) -> Option<&[u8]> {
+    let (len, rest) = read_u32(payload)?;
+    rest.get(..len as usize)
+}
+
+/// The entries that the options may move to another position in their section.
+struct Moved {
+    imports: bool,
+    exports: bool,
+}
+
+impl Moved {
+    fn new(options: &Options) -> Self {
+        let names = options.field_order == FieldOrder::Names;
+        Moved {
+            imports: names,
+            // Exports written inline follow the order of the items they export.
+            exports: names || options.exports == Placement::Inline,
+        }
+    }
+}
+
+/// Whether two import or export sections only differ in the order of their entries,
+/// such that every imported item keeps its index.
+fn same_entries(old: &(u8, &[u8]), new: &(u8, &[u8]), moved: &Moved) -> bool {
+    if old.0 != new.0 {
+        return false;
+    }
+    let entries = match old.0 {
+        IMPORT_SECTION if moved.imports => imports,
+        EXPORT_SECTION if moved.exports => exports,
+        _ => return false,
+    };
+    let (mut old_entries, mut new_entries) = match (entries(old.1), entries(new.1)) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return false,
+    };
+    if old.0 == IMPORT_SECTION {
+        // Imports are numbered in order within each kind.
+        old_entries.sort_by_key(|(kind, _)| *kind);
+        new_entries.sort_by_key(|(kind, _)| *kind);
+    } else {
+        old_entries.sort();
+        new_entries.sort();
+    }
+    old_entries == new_entries
+}
+
+/// The kind and encoding of each entry of an import section.
+fn imports(payload: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let (count, mut rest) = read_u32(payload)?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let tail = skip_name(skip_name(rest)?)?;
+        let (&kind, tail) = tail.split_first()?;
+        let tail = match kind {
+            // A function or a tag, whose attribute precedes its type.
+            0 => read_u32(tail)?.1,
+            4 => read_u32(tail.get(1..)?)?.1,
+            1 => skip_limits(skip_val_type(tail)?)?,
+            2 => skip_limits(tail)?,
+            // A global, whose mutability follows its type.
+            3 => skip_val_type(tail)?.get(1..)?,
+            _ => return None,
+        };
+        entries.push((kind, &rest[..rest.len() - tail.len()]));
+        rest = tail;
+    }
+    Some(entries)
+}
+
+/// The kind and encoding of each entry of an export section.
+fn exports(payload: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let (count, mut rest) = read_u32(payload)?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let tail = skip_name(rest)?;
+        let (&kind, tail) = tail.split_first()?;
+        let tail = read_u32(tail)?.1;
+        entries.push((kind, &rest[..rest.len() - tail.len()]));
+        rest = tail;
+    }
+    Some(entries)
+}
+
+fn skip_name(bytes: &[u8]) -> Option<&[u8]> {
+    let (len, rest) = read_u32(bytes)?;
+    rest.get(len as usize..)
+}
+
+fn skip_val_type(bytes: &[u8]) -> Option<&[u8]> {
+    let (&ty, rest) = bytes.split_first()?;
+    match ty {
+        // A reference type followed by its heap type.
+        0x63 | 0x64 | 0x6b | 0x6c => skip_leb(rest),
+        _ => Some(rest),
+    }
+}
+
+fn skip_limits(bytes: &[u8]) -> Option<&[u8]> {
+    let (&flags, rest) = bytes.split_first()?;
+    let rest = skip_leb(rest)?;
+    if flags & 1 == 0 {
+        Some(rest)
+    } else {
+        skip_leb(rest)
+    }
+}
+
+/// Skip a LEB128 integer of any size.
+fn skip_leb(bytes: &[u8]) -> Option<&[u8]> {
+    let len = bytes.iter().take(10).position(|byte| byte & 0x80 == 0)?;
+    Some(&bytes[len + 1..])
+}
+
+/// The index of the first function body that differs between two code sections.
+fn first_different_body(before: &[u8], after: &[u8]) -> Option<usize> {
+    let (before, after) = (bodies(before)?, bodies(after)?);
+    before
+        .iter()
+        .zip(&after)
+        .position(|(before, after)| before != after)
+        .or_else(|| {
+            if before.len() == after.len() {
+                None
+            } else {
+                Some(before.len().min(after.len()))
+            }
+        })
+}
+
+fn bodies(payload: &[u8]) -> Option<Vec<&[u8]>> {
+    let (count, mut rest) = read_u32(payload)?;
+    let mut bodies = Vec::new();
+    for _ in 0..count {
+        let (size, tail) = read_u32(rest)?;
+        bodies.push(tail.get(..size as usize)?);
+        rest = &tail[size as usize..];
+    }
+    Some(bodies)
+}
+
+/// Read an unsigned LEB128 integer.
+fn read_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let mut result = 0u32;
+    for (i, byte) in bytes.iter().enumerate().take(5) {
+        result |= u32::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((result, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{verify, verify_idempotent};
+    use crate::error::Error;
+    use crate::options::{ExpressionStyle, FieldOrder, Options};
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn verify_should_accept_equivalent_modules() {
+        let source =
+            "(module (func $f (export \"f\") (result i32) (i32.add (i32.const 1) (i32.const 2))))";
+        let formatted = "(module\n\t(func $f (export \"f\") (result i32)\n\t\t(i32.const 1)\n\t\t(i32.const 2)\n\t\ti32.add\n\t)\n)\n";
+        assert_matches!(verify(source, formatted, &Options::default()), Ok(()));
+    }
+
+    #[test]
+    fn verify_should_ignore_names() {
+        let source = "(module (func $f (param $x i32)))";
+        let formatted = "(module (func (param i32)))";
+        assert_matches!(verify(source, formatted, &Options::default()), Ok(()));
+    }
+
+    #[test]
+    fn verify_should_reject_reordered_exports() {
+        let source = "(module (func) (export \"b\" (func 0)) (export \"a\" (func 0)))";
+        let formatted = "(module (func) (export \"a\" (func 0)) (export \"b\" (func 0)))";
+        assert_matches!(
+            verify(source, formatted, &Options::default()),
+            Err(Error::Verify(message)) if message == "the export section is different"
+        );
+    }
+
+    #[test]
+    fn verify_should_ignore_the_order_of_exports_and_imports_of_different_kinds() {
+        let source = "(module
+            (import \"env\" \"f\" (func))
+            (import \"env\" \"g\" (global i32))
+            (export \"b\" (func 0))
+            (export \"a\" (global 0)))";
+        let formatted = "(module
+            (import \"env\" \"g\" (global i32))
+            (import \"env\" \"f\" (func))
+            (export \"a\" (global 0))
+            (export \"b\" (func 0)))";
+        let options = Options {
+            field_order: FieldOrder::Names,
+            ..Options::default()
+        };
+        assert_matches!(verify(source, formatted, &options), Ok(()));
+    }
+
+    #[test]
+    fn verify_should_reject_reordered_imports_of_the_same_kind() {
+        let source = "(module (import \"env\" \"f\" (func)) (import \"env\" \"g\" (func)))";
+        let formatted = "(module (import \"env\" \"g\" (func)) (import \"env\" \"f\" (func)))";
+        let options = Options {
+            field_order: FieldOrder::Names,
+            ..Options::default()
+        };
+        assert_matches!(
+            verify(source, formatted, &options),
+            Err(Error::Verify(message)) if message == "the import section is different"
+        );
+    }
+
+    #[test]
+    fn verify_should_accept_merged_duplicate_types() {
+        let source = "(module
+            (type (func))
+            (type (func))
+            (func (type 1)))";
+        let formatted = "(module (type (func)) (func (type 0)))";
+        let options = Options {
+            dedupe_types: true,
+            ..Options::default()
+        };
+        assert_matches!(verify(source, formatted, &options), Ok(()));
+        assert_matches!(
+            verify(source, formatted, &Options::default()),
+            Err(Error::Verify(..))
+        );
+    }
+
+    #[test]
+    fn verify_should_name_the_first_different_function() {
+        let source = "(module
+            (import \"env\" \"g\" (func))
+            (func $a (param f64) (result i32) (i32.trunc_f64_u (local.get 0)))
+            (func $b (param f64) (result i32) (i32.trunc_f64_u (local.get 0))))";
+        let formatted = "(module
+            (import \"env\" \"g\" (func))
+            (func $a (param f64) (result i32) (i32.trunc_f64_u (local.get 0)))
+            (func $b (param f64) (result i32) (i32.trunc_f64_s (local.get 0))))";
+        assert_matches!(
+            verify(source, formatted, &Options::default()),
+            Err(Error::Verify(message)) if message == "the body of module field 2 (`func $b`) is different"
+        );
+    }
+
+    #[test]
+    fn verify_should_only_parse_output_of_sources_that_cannot_be_encoded() {
+        let source = "(module (func call $nope))";
+        let formatted = "(module\n\t(func\n\t\tcall $nope\n\t)\n)\n";
+        assert_matches!(verify(source, formatted, &Options::default()), Ok(()));
+        assert_matches!(
+            verify(source, "(module (func call $nope)", &Options::default()),
+            Err(Error::Verify(..))
+        );
+    }
+
+    #[test]
+    fn verify_should_reject_invalid_output() {
+        let source = "(module (func (param f32) (result i64) (i64.trunc_f32_u (local.get 0))))";
+        let formatted = "(module (func (param f32) (result i64) (i64.trunc_f_32_u (local.get 0))))";
+        assert_matches!(
+            verify(source, formatted, &Options::default()),
+            Err(Error::Verify(..))
+        );
+    }
+
+    #[test]
+    fn verify_idempotent_should_accept_fixtures() {
+        let source = include_str!("../tests/data/input/fac.wat");
+        for expression_style in [ExpressionStyle::Flat, ExpressionStyle::Folded] {
+            let options = Options {
+                expression_style,
+                ..Options::default()
+            };
+            assert_matches!(verify_idempotent(source, &options), Ok(()));
+        }
+    }
+
+    #[test]
+    fn verify_idempotent_should_reject_unsupported_input() {
+        let source = "(module (func (param v128)))";
+        assert_matches!(
+            verify_idempotent(source, &Options::default()),
+            Err(Error::Unsupported(..))
+        );
+    }
+}
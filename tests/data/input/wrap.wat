@@ -0,0 +1,16 @@
+(module
+	(func $a_function_with_a_rather_long_name (export "a_function_with_a_rather_long_name") (param $first i32) (param $second i32) (param $third i64) (result i64)
+		(local.get $third)
+	)
+	(func (export "many") (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)
+		(block $a
+			(block $b
+				(block $c
+					(br_table 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 (local.get 0))
+				)
+			)
+		)
+	)
+	(memory (export "memory_export_number_one") (export "memory_export_number_two") (export "memory_export_number_three") 1)
+	(type (func (param i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64) (result i64)))
+)
@@ -0,0 +1,134 @@
+(module
+	(func (export "i32.wrap_i64") (param $x i64) (result i32)
+		(local.get $x)
+		i32.wrap_i64
+	)
+	(func (export "i32.trunc_f32_s") (param $x f32) (result i32)
+		(local.get $x)
+		i32.trunc_f32_s
+	)
+	(func (export "i32.trunc_f32_u") (param $x f32) (result i32)
+		(local.get $x)
+		i32.trunc_f32_u
+	)
+	(func (export "i32.trunc_f64_s") (param $x f64) (result i32)
+		(local.get $x)
+		i32.trunc_f64_s
+	)
+	(func (export "i32.trunc_f64_u") (param $x f64) (result i32)
+		(local.get $x)
+		i32.trunc_f64_u
+	)
+	(func (export "i32.trunc_sat_f32_s") (param $x f32) (result i32)
+		(local.get $x)
+		i32.trunc_sat_f32_s
+	)
+	(func (export "i32.trunc_sat_f32_u") (param $x f32) (result i32)
+		(local.get $x)
+		i32.trunc_sat_f32_u
+	)
+	(func (export "i32.trunc_sat_f64_s") (param $x f64) (result i32)
+		(local.get $x)
+		i32.trunc_sat_f64_s
+	)
+	(func (export "i32.trunc_sat_f64_u") (param $x f64) (result i32)
+		(local.get $x)
+		i32.trunc_sat_f64_u
+	)
+	(func (export "i64.extend_i32_s") (param $x i32) (result i64)
+		(local.get $x)
+		i64.extend_i32_s
+	)
+	(func (export "i64.extend_i32_u") (param $x i32) (result i64)
+		(local.get $x)
+		i64.extend_i32_u
+	)
+	(func (export "i64.trunc_f32_s") (param $x f32) (result i64)
+		(local.get $x)
+		i64.trunc_f32_s
+	)
+	(func (export "i64.trunc_f32_u") (param $x f32) (result i64)
+		(local.get $x)
+		i64.trunc_f32_u
+	)
+	(func (export "i64.trunc_f64_s") (param $x f64) (result i64)
+		(local.get $x)
+		i64.trunc_f64_s
+	)
+	(func (export "i64.trunc_f64_u") (param $x f64) (result i64)
+		(local.get $x)
+		i64.trunc_f64_u
+	)
+	(func (export "i64.trunc_sat_f32_s") (param $x f32) (result i64)
+		(local.get $x)
+		i64.trunc_sat_f32_s
+	)
+	(func (export "i64.trunc_sat_f32_u") (param $x f32) (result i64)
+		(local.get $x)
+		i64.trunc_sat_f32_u
+	)
+	(func (export "i64.trunc_sat_f64_s") (param $x f64) (result i64)
+		(local.get $x)
+		i64.trunc_sat_f64_s
+	)
+	(func (export "i64.trunc_sat_f64_u") (param $x f64) (result i64)
+		(local.get $x)
+		i64.trunc_sat_f64_u
+	)
+	(func (export "f32.convert_i32_s") (param $x i32) (result f32)
+		(local.get $x)
+		f32.convert_i32_s
+	)
+	(func (export "f32.convert_i32_u") (param $x i32) (result f32)
+		(local.get $x)
+		f32.convert_i32_u
+	)
+	(func (export "f32.convert_i64_s") (param $x i64) (result f32)
+		(local.get $x)
+		f32.convert_i64_s
+	)
+	(func (export "f32.convert_i64_u") (param $x i64) (result f32)
+		(local.get $x)
+		f32.convert_i64_u
+	)
+	(func (export "f32.demote_f64") (param $x f64) (result f32)
+		(local.get $x)
+		f32.demote_f64
+	)
+	(func (export "f64.convert_i32_s") (param $x i32) (result f64)
+		(local.get $x)
+		f64.convert_i32_s
+	)
+	(func (export "f64.convert_i32_u") (param $x i32) (result f64)
+		(local.get $x)
+		f64.convert_i32_u
+	)
+	(func (export "f64.convert_i64_s") (param $x i64) (result f64)
+		(local.get $x)
+		f64.convert_i64_s
+	)
+	(func (export "f64.convert_i64_u") (param $x i64) (result f64)
+		(local.get $x)
+		f64.convert_i64_u
+	)
+	(func (export "f64.promote_f32") (param $x f32) (result f64)
+		(local.get $x)
+		f64.promote_f32
+	)
+	(func (export "i32.reinterpret_f32") (param $x f32) (result i32)
+		(local.get $x)
+		i32.reinterpret_f32
+	)
+	(func (export "i64.reinterpret_f64") (param $x f64) (result i64)
+		(local.get $x)
+		i64.reinterpret_f64
+	)
+	(func (export "f32.reinterpret_i32") (param $x i32) (result f32)
+		(local.get $x)
+		f32.reinterpret_i32
+	)
+	(func (export "f64.reinterpret_i64") (param $x i64) (result f64)
+		(local.get $x)
+		f64.reinterpret_i64
+	)
+)
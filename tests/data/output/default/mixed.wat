@@ -0,0 +1,41 @@
+(module
+	(func $sum (export "sum") (param $n i32) (result i32)
+		(local $i i32)
+		(local $acc i32)
+		block $done
+			loop $loop
+				(local.get $i)
+				(local.get $n)
+				i32.ge_u
+				(br_if $done)
+				(local.get $acc)
+				(local.get $i)
+				i32.add
+				(local.set $acc)
+				(local.get $i)
+				(i32.const 1)
+				i32.add
+				(local.set $i)
+				(br $loop)
+			end
+		end
+		(local.get $acc)
+	)
+	(func $max (param $a i32) (param $b i32) (result i32)
+		(local.get $a)
+		(local.get $b)
+		i32.gt_s
+		if (result i32)
+			(local.get $a)
+		else
+			(local.get $b)
+		end
+	)
+	(func $mixed (param $x i32) (result i32)
+		(local.get $x)
+		(i32.const 2)
+		i32.mul
+		(i32.const 1)
+		i32.add
+	)
+)
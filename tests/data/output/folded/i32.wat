@@ -0,0 +1,95 @@
+(module
+	(func (export "add") (param $x i32) (param $y i32) (result i32)
+		(i32.add (local.get $x) (local.get $y))
+	)
+	(func (export "sub") (param $x i32) (param $y i32) (result i32)
+		(i32.sub (local.get $x) (local.get $y))
+	)
+	(func (export "mul") (param $x i32) (param $y i32) (result i32)
+		(i32.mul (local.get $x) (local.get $y))
+	)
+	(func (export "div_s") (param $x i32) (param $y i32) (result i32)
+		(i32.div_s (local.get $x) (local.get $y))
+	)
+	(func (export "div_u") (param $x i32) (param $y i32) (result i32)
+		(i32.div_u (local.get $x) (local.get $y))
+	)
+	(func (export "rem_s") (param $x i32) (param $y i32) (result i32)
+		(i32.rem_s (local.get $x) (local.get $y))
+	)
+	(func (export "rem_u") (param $x i32) (param $y i32) (result i32)
+		(i32.rem_u (local.get $x) (local.get $y))
+	)
+	(func (export "and") (param $x i32) (param $y i32) (result i32)
+		(i32.and (local.get $x) (local.get $y))
+	)
+	(func (export "or") (param $x i32) (param $y i32) (result i32)
+		(i32.or (local.get $x) (local.get $y))
+	)
+	(func (export "xor") (param $x i32) (param $y i32) (result i32)
+		(i32.xor (local.get $x) (local.get $y))
+	)
+	(func (export "shl") (param $x i32) (param $y i32) (result i32)
+		(i32.shl (local.get $x) (local.get $y))
+	)
+	(func (export "shr_s") (param $x i32) (param $y i32) (result i32)
+		(i32.shr_s (local.get $x) (local.get $y))
+	)
+	(func (export "shr_u") (param $x i32) (param $y i32) (result i32)
+		(i32.shr_u (local.get $x) (local.get $y))
+	)
+	(func (export "rotl") (param $x i32) (param $y i32) (result i32)
+		(i32.rotl (local.get $x) (local.get $y))
+	)
+	(func (export "rotr") (param $x i32) (param $y i32) (result i32)
+		(i32.rotr (local.get $x) (local.get $y))
+	)
+	(func (export "clz") (param $x i32) (result i32)
+		(i32.clz (local.get $x))
+	)
+	(func (export "ctz") (param $x i32) (result i32)
+		(i32.ctz (local.get $x))
+	)
+	(func (export "popcnt") (param $x i32) (result i32)
+		(i32.popcnt (local.get $x))
+	)
+	(func (export "extend8_s") (param $x i32) (result i32)
+		(i32.extend8_s (local.get $x))
+	)
+	(func (export "extend16_s") (param $x i32) (result i32)
+		(i32.extend16_s (local.get $x))
+	)
+	(func (export "eqz") (param $x i32) (result i32)
+		(i32.eqz (local.get $x))
+	)
+	(func (export "eq") (param $x i32) (param $y i32) (result i32)
+		(i32.eq (local.get $x) (local.get $y))
+	)
+	(func (export "ne") (param $x i32) (param $y i32) (result i32)
+		(i32.ne (local.get $x) (local.get $y))
+	)
+	(func (export "lt_s") (param $x i32) (param $y i32) (result i32)
+		(i32.lt_s (local.get $x) (local.get $y))
+	)
+	(func (export "lt_u") (param $x i32) (param $y i32) (result i32)
+		(i32.lt_u (local.get $x) (local.get $y))
+	)
+	(func (export "le_s") (param $x i32) (param $y i32) (result i32)
+		(i32.le_s (local.get $x) (local.get $y))
+	)
+	(func (export "le_u") (param $x i32) (param $y i32) (result i32)
+		(i32.le_u (local.get $x) (local.get $y))
+	)
+	(func (export "gt_s") (param $x i32) (param $y i32) (result i32)
+		(i32.gt_s (local.get $x) (local.get $y))
+	)
+	(func (export "gt_u") (param $x i32) (param $y i32) (result i32)
+		(i32.gt_u (local.get $x) (local.get $y))
+	)
+	(func (export "ge_s") (param $x i32) (param $y i32) (result i32)
+		(i32.ge_s (local.get $x) (local.get $y))
+	)
+	(func (export "ge_u") (param $x i32) (param $y i32) (result i32)
+		(i32.ge_u (local.get $x) (local.get $y))
+	)
+)
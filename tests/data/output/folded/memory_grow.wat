@@ -0,0 +1,20 @@
+(module
+	(func (export "load_at_zero") (result i32)
+		(i32.load (i32.const 0))
+	)
+	(func (export "store_at_zero")
+		(i32.store (i32.const 0) (i32.const 2))
+	)
+	(func (export "load_at_page_size") (result i32)
+		(i32.load (i32.const 65536))
+	)
+	(func (export "store_at_page_size")
+		(i32.store (i32.const 65536) (i32.const 3))
+	)
+	(func (export "grow") (param $sz i32) (result i32)
+		(memory.grow (local.get $sz))
+	)
+	(func (export "size") (result i32)
+		memory.size
+	)
+)
@@ -0,0 +1,6 @@
+(module
+	(func $main (result i32)
+		(i32.const 1)
+	)
+	(start $main)
+)
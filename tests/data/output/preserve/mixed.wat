@@ -0,0 +1,32 @@
+(module
+	(func $sum (export "sum") (param $n i32) (result i32)
+		(local $i i32)
+		(local $acc i32)
+		block $done
+			loop $loop
+				(br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+				(local.set $acc (i32.add (local.get $acc) (local.get $i)))
+				(local.set $i (i32.add (local.get $i) (i32.const 1)))
+				(br $loop)
+			end
+		end
+		(local.get $acc)
+	)
+	(func $max (param $a i32) (param $b i32) (result i32)
+		(if (result i32)
+			(i32.gt_s (local.get $a) (local.get $b))
+			(then
+				(local.get $a)
+			)
+			(else
+				(local.get $b)
+			)
+		)
+	)
+	(func $mixed (param $x i32) (result i32)
+		(local.get $x)
+		(i32.mul (i32.const 2))
+		(i32.const 1)
+		i32.add
+	)
+)
@@ -0,0 +1,179 @@
+(module
+	(export "i32.wrap_i64" (func 0))
+	(func (type 0) (param $x i64) (result i32)
+		(local.get 0)
+		i32.wrap_i64
+	)
+	(export "i32.trunc_f32_s" (func 1))
+	(func (type 1) (param $x f32) (result i32)
+		(local.get 0)
+		i32.trunc_f32_s
+	)
+	(export "i32.trunc_f32_u" (func 2))
+	(func (type 1) (param $x f32) (result i32)
+		(local.get 0)
+		i32.trunc_f32_u
+	)
+	(export "i32.trunc_f64_s" (func 3))
+	(func (type 2) (param $x f64) (result i32)
+		(local.get 0)
+		i32.trunc_f64_s
+	)
+	(export "i32.trunc_f64_u" (func 4))
+	(func (type 2) (param $x f64) (result i32)
+		(local.get 0)
+		i32.trunc_f64_u
+	)
+	(export "i32.trunc_sat_f32_s" (func 5))
+	(func (type 1) (param $x f32) (result i32)
+		(local.get 0)
+		i32.trunc_sat_f32_s
+	)
+	(export "i32.trunc_sat_f32_u" (func 6))
+	(func (type 1) (param $x f32) (result i32)
+		(local.get 0)
+		i32.trunc_sat_f32_u
+	)
+	(export "i32.trunc_sat_f64_s" (func 7))
+	(func (type 2) (param $x f64) (result i32)
+		(local.get 0)
+		i32.trunc_sat_f64_s
+	)
+	(export "i32.trunc_sat_f64_u" (func 8))
+	(func (type 2) (param $x f64) (result i32)
+		(local.get 0)
+		i32.trunc_sat_f64_u
+	)
+	(export "i64.extend_i32_s" (func 9))
+	(func (type 3) (param $x i32) (result i64)
+		(local.get 0)
+		i64.extend_i32_s
+	)
+	(export "i64.extend_i32_u" (func 10))
+	(func (type 3) (param $x i32) (result i64)
+		(local.get 0)
+		i64.extend_i32_u
+	)
+	(export "i64.trunc_f32_s" (func 11))
+	(func (type 4) (param $x f32) (result i64)
+		(local.get 0)
+		i64.trunc_f32_s
+	)
+	(export "i64.trunc_f32_u" (func 12))
+	(func (type 4) (param $x f32) (result i64)
+		(local.get 0)
+		i64.trunc_f32_u
+	)
+	(export "i64.trunc_f64_s" (func 13))
+	(func (type 5) (param $x f64) (result i64)
+		(local.get 0)
+		i64.trunc_f64_s
+	)
+	(export "i64.trunc_f64_u" (func 14))
+	(func (type 5) (param $x f64) (result i64)
+		(local.get 0)
+		i64.trunc_f64_u
+	)
+	(export "i64.trunc_sat_f32_s" (func 15))
+	(func (type 4) (param $x f32) (result i64)
+		(local.get 0)
+		i64.trunc_sat_f32_s
+	)
+	(export "i64.trunc_sat_f32_u" (func 16))
+	(func (type 4) (param $x f32) (result i64)
+		(local.get 0)
+		i64.trunc_sat_f32_u
+	)
+	(export "i64.trunc_sat_f64_s" (func 17))
+	(func (type 5) (param $x f64) (result i64)
+		(local.get 0)
+		i64.trunc_sat_f64_s
+	)
+	(export "i64.trunc_sat_f64_u" (func 18))
+	(func (type 5) (param $x f64) (result i64)
+		(local.get 0)
+		i64.trunc_sat_f64_u
+	)
+	(export "f32.convert_i32_s" (func 19))
+	(func (type 6) (param $x i32) (result f32)
+		(local.get 0)
+		f32.convert_i32_s
+	)
+	(export "f32.convert_i32_u" (func 20))
+	(func (type 6) (param $x i32) (result f32)
+		(local.get 0)
+		f32.convert_i32_u
+	)
+	(export "f32.convert_i64_s" (func 21))
+	(func (type 7) (param $x i64) (result f32)
+		(local.get 0)
+		f32.convert_i64_s
+	)
+	(export "f32.convert_i64_u" (func 22))
+	(func (type 7) (param $x i64) (result f32)
+		(local.get 0)
+		f32.convert_i64_u
+	)
+	(export "f32.demote_f64" (func 23))
+	(func (type 8) (param $x f64) (result f32)
+		(local.get 0)
+		f32.demote_f64
+	)
+	(export "f64.convert_i32_s" (func 24))
+	(func (type 9) (param $x i32) (result f64)
+		(local.get 0)
+		f64.convert_i32_s
+	)
+	(export "f64.convert_i32_u" (func 25))
+	(func (type 9) (param $x i32) (result f64)
+		(local.get 0)
+		f64.convert_i32_u
+	)
+	(export "f64.convert_i64_s" (func 26))
+	(func (type 10) (param $x i64) (result f64)
+		(local.get 0)
+		f64.convert_i64_s
+	)
+	(export "f64.convert_i64_u" (func 27))
+	(func (type 10) (param $x i64) (result f64)
+		(local.get 0)
+		f64.convert_i64_u
+	)
+	(export "f64.promote_f32" (func 28))
+	(func (type 11) (param $x f32) (result f64)
+		(local.get 0)
+		f64.promote_f32
+	)
+	(export "i32.reinterpret_f32" (func 29))
+	(func (type 1) (param $x f32) (result i32)
+		(local.get 0)
+		i32.reinterpret_f32
+	)
+	(export "i64.reinterpret_f64" (func 30))
+	(func (type 5) (param $x f64) (result i64)
+		(local.get 0)
+		i64.reinterpret_f64
+	)
+	(export "f32.reinterpret_i32" (func 31))
+	(func (type 6) (param $x i32) (result f32)
+		(local.get 0)
+		f32.reinterpret_i32
+	)
+	(export "f64.reinterpret_i64" (func 32))
+	(func (type 10) (param $x i64) (result f64)
+		(local.get 0)
+		f64.reinterpret_i64
+	)
+	(type (func (param i64) (result i32)))
+	(type (func (param f32) (result i32)))
+	(type (func (param f64) (result i32)))
+	(type (func (param i32) (result i64)))
+	(type (func (param f32) (result i64)))
+	(type (func (param f64) (result i64)))
+	(type (func (param i32) (result f32)))
+	(type (func (param i64) (result f32)))
+	(type (func (param f64) (result f32)))
+	(type (func (param i32) (result f64)))
+	(type (func (param i64) (result f64)))
+	(type (func (param f32) (result f64)))
+)
@@ -0,0 +1,44 @@
+(module
+	(export "sum" (func 0))
+	(func $sum (type 0) (param $n i32) (result i32)
+		(local $i i32)
+		(local $acc i32)
+		block $done
+			loop $loop
+				(local.get 1)
+				(local.get 0)
+				i32.ge_u
+				(br_if 1)
+				(local.get 2)
+				(local.get 1)
+				i32.add
+				(local.set 2)
+				(local.get 1)
+				(i32.const 1)
+				i32.add
+				(local.set 1)
+				(br 0)
+			end
+		end
+		(local.get 2)
+	)
+	(func $max (type 1) (param $a i32) (param $b i32) (result i32)
+		(local.get 0)
+		(local.get 1)
+		i32.gt_s
+		if (result i32)
+			(local.get 0)
+		else
+			(local.get 1)
+		end
+	)
+	(func $mixed (type 0) (param $x i32) (result i32)
+		(local.get 0)
+		(i32.const 2)
+		i32.mul
+		(i32.const 1)
+		i32.add
+	)
+	(type (func (param i32) (result i32)))
+	(type (func (param i32 i32) (result i32)))
+)
@@ -0,0 +1,31 @@
+(module
+	(export "a_function_with_a_rather_long_name" (func 0))
+	(func $a_function_with_a_rather_long_name
+		(type 1) (param $first i32) (param $second i32) (param $third i64) (result i64)
+		(local.get 2)
+	)
+	(export "many" (func 1))
+	(func
+		(type 2)
+		(param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)
+		block $a
+			block $b
+				block $c
+					(local.get 0)
+					(br_table 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1 2 0 1
+						2 0 1 2 0 1 2)
+				end
+			end
+		end
+	)
+	(export "memory_export_number_one" (memory 0))
+	(export "memory_export_number_two" (memory 0))
+	(export "memory_export_number_three" (memory 0))
+	(memory 1)
+	(type (func
+		(param i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64)
+		(result i64)))
+	(type (func (param i32 i32 i64) (result i64)))
+	(type (func
+		(param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)))
+)
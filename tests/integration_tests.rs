@@ -3,7 +3,9 @@ use pretty_assertions::assert_eq;
 use std::env;
 use std::fs;
 use std::io::Error;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use wast::{
     parser::{self, ParseBuffer},
     Error as WastError, Wat,
@@ -16,6 +18,7 @@ fn wasmfmt(args: &[&str]) -> Result<String, String> {
         .args(args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .expect("failed to spawn process");
 
@@ -33,6 +36,46 @@ fn wasmfmt(args: &[&str]) -> Result<String, String> {
     }
 }
 
+/// A directory for the files of a single test, removed when it is dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create an empty directory that no other test uses.
+    fn new() -> Result<Self, Error> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "wasmfmt-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let root = env::temp_dir().join(name);
+        fs::create_dir_all(&root)?;
+        Ok(TempDir(root))
+    }
+
+    /// The path of `name` within the directory.
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).into_os_string().into_string().unwrap()
+    }
+
+    /// Write `contents` to the file `name`, creating the directories it is in,
+    /// and return its path.
+    fn write(&self, name: &str, contents: &str) -> Result<String, Error> {
+        let path = self.path(name);
+        if let Some(parent) = Path::new(&path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
 fn parse(input: &str) -> Result<(), WastError> {
     let buffer = ParseBuffer::new(input).unwrap();
     parser::parse::<Wat>(&buffer).map(|_| ())
@@ -76,3 +119,346 @@ fn print_works() {
     let result = wasmfmt(&["print", "tests/data/input/i32.wat"]).expect("failed to print i32.wat");
     assert_eq!(result, formatted);
 }
+
+#[test]
+fn config_file_is_discovered() -> Result<(), Error> {
+    let source = include_str!("data/input/start.wat");
+    let formatted = include_str!("data/output/default/start.wat");
+
+    let dir = TempDir::new()?;
+    dir.write(
+        "wasmfmt.toml",
+        "indent_style = \"spaces\"\nindent_width = 2\n",
+    )?;
+    let file = dir.write("nested/start.wat", source)?;
+    let result = wasmfmt(&["print", &file]);
+    let config = wasmfmt(&["config", "--print", &dir.path("nested"), "-m", "80"]);
+
+    assert_eq!(
+        result.expect("failed to print start.wat"),
+        formatted.replace('\t', "  ")
+    );
+    let config = config.expect("failed to print config");
+    assert!(config.contains("max_width = 80"));
+    assert!(config.contains("indent_style = \"spaces\""));
+    assert!(config.contains("indent_width = 2"));
+    Ok(())
+}
+
+#[test]
+fn flags_turn_off_config_settings() -> Result<(), Error> {
+    let source = include_str!("data/input/start.wat");
+    let formatted = include_str!("data/output/default/start.wat");
+
+    let dir = TempDir::new()?;
+    dir.write("wasmfmt.toml", "minify = true\n")?;
+    let file = dir.write("start.wat", source)?;
+    let off = wasmfmt(&["print", "--no-minify", &file]);
+    let last = wasmfmt(&["print", "--no-minify", "--minify", &file]);
+    let minified = wasmfmt(&["print", &file]);
+
+    assert_eq!(off.expect("failed to print start.wat"), formatted);
+    assert_eq!(last, minified);
+    Ok(())
+}
+
+#[test]
+fn dedupe_types_reports_removed_types() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    let duplicates = dir.write("duplicates.wat", "(module (type (func)) (type (func)))")?;
+    let unique = dir.write("unique.wat", "(module (type (func)))")?;
+    let printed = wasmfmt(&["print", "--dedupe-types", &duplicates]);
+    let checked = wasmfmt(&["check", "-q", "--dedupe-types", &duplicates]);
+    let unchanged = wasmfmt(&["print", "--dedupe-types", &unique]);
+
+    assert_eq!(
+        printed,
+        Err(format!("{}: removed 1 duplicate type\n", duplicates))
+    );
+    assert_eq!(checked, Ok(String::new()));
+    assert_eq!(unchanged, Ok("(module\n\t(type (func))\n)\n".to_string()));
+    Ok(())
+}
+
+#[test]
+fn config_file_with_unknown_keys_is_rejected() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    dir.write(".wasmfmt.toml", "indent = 2\n")?;
+    let result = wasmfmt(&["config", "--print", &dir.path("")]);
+
+    let error = result.expect_err("unknown keys should be rejected");
+    assert!(error.contains("unknown field `indent`"));
+    Ok(())
+}
+
+#[test]
+fn editorconfig_is_applied_below_config_file() -> Result<(), Error> {
+    let source = include_str!("data/input/start.wat");
+    let formatted = include_str!("data/output/default/start.wat");
+
+    let dir = TempDir::new()?;
+    dir.write(
+        ".editorconfig",
+        "root = true\n\n[*.wat]\nindent_style = space\nindent_size = 2\nend_of_line = crlf\ninsert_final_newline = false\n",
+    )?;
+    dir.write("nested/wasmfmt.toml", "indent_width = 3\n")?;
+    let file = dir.write("nested/start.wat", source)?;
+    let result = wasmfmt(&["print", &file]);
+
+    let expected = formatted
+        .trim_end()
+        .replace('\t', "   ")
+        .replace('\n', "\r\n");
+    assert_eq!(result.expect("failed to print start.wat"), expected);
+    Ok(())
+}
+
+#[test]
+fn verify_walks_directory_tree() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    dir.write("fac.wat", include_str!("data/input/fac.wat"))?;
+    dir.write("nested/i32.wat", include_str!("data/input/i32.wat"))?;
+    dir.write("nested/notes.txt", "not a module")?;
+    let result = wasmfmt(&["verify", &dir.path("")]);
+
+    assert_eq!(
+        result.expect("failed to verify"),
+        "verified 2 files, 0 failed\n"
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn verify_skips_symlinked_directories() -> Result<(), Error> {
+    use std::os::unix::fs::symlink;
+
+    let dir = TempDir::new()?;
+    dir.write("nested/i32.wat", include_str!("data/input/i32.wat"))?;
+    symlink(dir.path(""), dir.path("nested/parent"))?;
+    let result = wasmfmt(&["verify", &dir.path("")]);
+
+    assert_eq!(
+        result.expect("verification failed"),
+        "verified 1 files, 0 failed\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_reports_failing_files() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    dir.write("bad.wat", "(module (func (i32.add")?;
+    let status = Command::new(BIN)
+        .args(["verify", &dir.path("")])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+
+    let stdout = String::from_utf8(status.stdout).expect("stdout");
+    assert!(!status.status.success());
+    assert!(stdout.contains("bad.wat: expected an instruction"));
+    assert!(stdout.ends_with("verified 1 files, 1 failed\n"));
+    Ok(())
+}
+
+#[test]
+fn fix_keeps_backup() -> Result<(), Error> {
+    let original = include_str!("data/input/i32.wat");
+    let expected = include_str!("data/output/default/i32.wat");
+
+    let dir = TempDir::new()?;
+    let file = dir.write("i32.wat", original)?;
+    let result = wasmfmt(&["fix", "--backup", &file]);
+
+    result.expect("failed to format i32.wat");
+    assert_eq!(fs::read_to_string(&file)?, expected);
+    assert_eq!(fs::read_to_string(dir.path("i32.wat.orig"))?, original);
+    assert_eq!(
+        fs::read_dir(dir.path(""))?.count(),
+        2,
+        "no temporary files should be left behind"
+    );
+    Ok(())
+}
+
+#[test]
+fn fix_skips_formatted_files() -> Result<(), Error> {
+    let formatted = include_str!("data/output/default/i32.wat");
+
+    let dir = TempDir::new()?;
+    let file = dir.write("i32.wat", formatted)?;
+    let before = fs::metadata(&file)?.modified()?;
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let result = wasmfmt(&["fix", "--backup", &file]);
+    let after = fs::metadata(&file)?.modified()?;
+
+    result.expect("failed to format i32.wat");
+    assert_eq!(before, after);
+    assert!(!Path::new(&dir.path("i32.wat.orig")).exists());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn fix_preserves_permissions_and_symlinks() -> Result<(), Error> {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let original = include_str!("data/input/i32.wat");
+    let expected = include_str!("data/output/default/i32.wat");
+
+    let dir = TempDir::new()?;
+    let file = dir.write("i32.wat", original)?;
+    let link = dir.path("link.wat");
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640))?;
+    symlink(&file, &link)?;
+    let result = wasmfmt(&["fix", &link]);
+
+    result.expect("failed to format i32.wat");
+    assert_eq!(fs::read_to_string(&file)?, expected);
+    assert_eq!(fs::metadata(&file)?.permissions().mode() & 0o777, 0o640);
+    assert!(fs::symlink_metadata(&link)?.file_type().is_symlink());
+    Ok(())
+}
+
+#[test]
+fn check_reports_json() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    let file = dir.write("start.wat", include_str!("data/input/start.wat"))?;
+    let output = Command::new(BIN)
+        .args(["check", "--message-format", "json", &file])
+        .output()?;
+
+    let stdout = String::from_utf8(output.stdout).expect("stdout");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.starts_with(
+        r#"{"hunks":[{"formatted":{"end":5,"start":2},"source":{"end":3,"start":2}}]"#
+    ));
+    assert!(stdout.ends_with("\"status\":\"unformatted\"}\n"));
+    Ok(())
+}
+
+fn exit_code(args: &[&str]) -> Result<(Option<i32>, String), Error> {
+    let output = Command::new(BIN).args(args).output()?;
+    let stdout = String::from_utf8(output.stdout).expect("stdout");
+    Ok((output.status.code(), stdout))
+}
+
+#[test]
+fn check_exit_codes_distinguish_failures() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    let formatted = dir.write(
+        "formatted.wat",
+        include_str!("data/output/default/start.wat"),
+    )?;
+    let unformatted = dir.write("unformatted.wat", include_str!("data/input/start.wat"))?;
+    let invalid = dir.write("invalid.wat", "(module (func (i32.add")?;
+    let unresolved = dir.write("unresolved.wat", "(module (func (call $missing)))")?;
+    let unsupported = dir.write("unsupported.wat", "(module (func (param v128)))")?;
+    let passive = dir.write("passive.wat", "(module (memory 1) (data \"a\"))")?;
+    let codes = [
+        exit_code(&["check", &formatted])?.0,
+        exit_code(&["check", &unformatted])?.0,
+        exit_code(&["check", &invalid])?.0,
+        exit_code(&["check", "-r", &unresolved])?.0,
+        exit_code(&["check", &unsupported])?.0,
+        exit_code(&["check", &passive])?.0,
+        exit_code(&["check", &dir.path("missing.wat")])?.0,
+        exit_code(&["check", "--quiet", "--list-different", &formatted])?.0,
+    ];
+
+    assert_eq!(
+        codes,
+        [
+            Some(0),
+            Some(1),
+            Some(2),
+            Some(2),
+            Some(3),
+            Some(3),
+            Some(4),
+            Some(2)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn check_quiet_and_list_different() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    let formatted = dir.write(
+        "formatted.wat",
+        include_str!("data/output/default/start.wat"),
+    )?;
+    let unformatted = dir.write("unformatted.wat", include_str!("data/input/start.wat"))?;
+    let quiet = exit_code(&["check", "--quiet", &unformatted])?;
+    let listed = exit_code(&["check", "-l", &unformatted])?;
+    let unlisted = exit_code(&["check", "-l", &formatted])?;
+
+    assert_eq!(quiet, (Some(1), String::new()));
+    assert_eq!(listed, (Some(1), format!("{}\n", unformatted)));
+    assert_eq!(unlisted, (Some(0), String::new()));
+    Ok(())
+}
+
+#[test]
+fn print_formats_line_range() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    let source = "(module\n  (global i32   (i32.const 1))\n  (global i32   (i32.const 2))\n)\n";
+    let path = dir.write("range.wat", source)?;
+    let actual = wasmfmt(&["print", "--lines", "3-3", &path]);
+    let invalid = exit_code(&["print", "--lines", "3-1", &path])?;
+
+    assert_eq!(
+        actual,
+        Ok(
+            "(module\n  (global i32   (i32.const 1))\n\t(global i32 (i32.const 2))\n)\n"
+                .to_string()
+        )
+    );
+    assert_eq!(invalid.0, Some(2));
+    Ok(())
+}
+
+#[test]
+fn print_best_effort_copies_unsupported_fields() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    let path = dir.write(
+        "passive.wat",
+        "(module\n  (elem funcref (ref.null func))\n  (global i32   (i32.const 1)))\n",
+    )?;
+    let strict = exit_code(&["print", &path])?;
+    let best_effort = exit_code(&["print", "--best-effort", &path])?;
+
+    assert_eq!(strict, (Some(3), String::new()));
+    assert_eq!(
+        best_effort,
+        (
+            Some(0),
+            "(module\n\t(elem funcref (ref.null func))\n\t(global i32 (i32.const 1))\n)\n"
+                .to_string()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn recover_formats_fields_that_parse() -> Result<(), Error> {
+    let dir = TempDir::new()?;
+    let path = dir.write(
+        "broken.wat",
+        "(module\n  (global i32   (i32.const 1))\n  (func (i32.const)))\n",
+    )?;
+    let printed = exit_code(&["print", "--recover", &path])?;
+    let checked = exit_code(&["check", "--recover", "--quiet", &path])?;
+
+    assert_eq!(
+        printed,
+        (
+            Some(0),
+            "(module\n\t(global i32 (i32.const 1))\n\t(func (i32.const))\n)\n".to_string()
+        )
+    );
+    assert_eq!(checked, (Some(2), String::new()));
+    Ok(())
+}